@@ -66,6 +66,43 @@ export default defineBackground(() => {
 
 
 
+  // Native messaging host of the desktop tracker. While the tracker takes our tab events it
+  // merges them into its own browser sessions, so we don't send sessions ourselves.
+  const NATIVE_HOST = 'com.tick.tracker';
+  let nativePort: ReturnType<typeof browser.runtime.connectNative> | null = null;
+  let trackerConnected = false;
+
+  try {
+    nativePort = browser.runtime.connectNative(NATIVE_HOST);
+    nativePort.onMessage.addListener((message: any) => {
+      if (message?.type === 'status') trackerConnected = message.tracker === true;
+    });
+    // Also fires right away when the host is not installed
+    nativePort.onDisconnect.addListener(() => {
+      nativePort = null;
+      trackerConnected = false;
+    });
+  } catch {
+    nativePort = null;
+  }
+
+  const isTrackable = (url: string | undefined) =>
+    typeof url === 'string' && !url.startsWith('chrome://') && !url.startsWith('about:');
+
+  const reportTab = (tab: { url?: string, title?: string, incognito?: boolean } | null) => {
+    if (!nativePort) return;
+    try {
+      if (tab && !tab.incognito && isTrackable(tab.url)) {
+        nativePort.postMessage({ type: 'tab', url: tab.url, title: tab.title || '' });
+      } else {
+        nativePort.postMessage({ type: 'none' });
+      }
+    } catch {
+      nativePort = null;
+      trackerConnected = false;
+    }
+  };
+
   const addLog = async (log: { type: 'success' | 'error', message: string, detail?: any }) => {
     const { logs = [] } = await browser.storage.local.get('logs');
     const newLog = {
@@ -98,6 +135,9 @@ export default defineBackground(() => {
       await setState('startTime', endTime); // Next session starts now
     }
 
+    // The tracker sends this time as part of its own browser session
+    if (trackerConnected) return;
+
     if (duration > 1000 && typeof currentUrl === 'string' && !currentUrl.startsWith('chrome://') && !currentUrl.startsWith('about:')) {
 
       // Use cached config if available
@@ -171,6 +211,7 @@ export default defineBackground(() => {
 
     if (tabId === null) {
       await setState('currentTabId', null);
+      reportTab(null);
       return;
     }
 
@@ -181,6 +222,7 @@ export default defineBackground(() => {
         await setState('currentUrl', tab.url);
         await setState('currentTitle', tab.title || '');
         await setState('startTime', Date.now());
+        reportTab(tab);
       }
     } catch (err) {
       // If the tab is gone, we just clear the state
@@ -206,6 +248,7 @@ export default defineBackground(() => {
 
         if (oldAppName === newAppName) {
           await setState('currentUrl', tab.url);
+          reportTab(tab);
         } else {
           await updateActiveTab(tabId);
         }
@@ -218,7 +261,18 @@ export default defineBackground(() => {
     if (tabId === currentTabId) {
       await logSession(true);
       await setState('currentTabId', null);
+      reportTab(null);
+    }
+  });
+
+  // The tracker only knows which tab is in front if we say so when windows change focus
+  browser.windows.onFocusChanged.addListener(async (windowId) => {
+    if (windowId === browser.windows.WINDOW_ID_NONE) {
+      reportTab(null);
+      return;
     }
+    const [tab] = await browser.tabs.query({ active: true, windowId });
+    reportTab(tab ?? null);
   });
 
   // Graceful Shutdown for Browser
@@ -9,7 +9,8 @@ export default defineConfig({
     permissions: [
       "tabs",
       "storage",
-      "webNavigation"
+      "webNavigation",
+      "nativeMessaging"
     ],
     host_permissions: [
       "http://localhost:3000/*",
@@ -17,5 +18,11 @@ export default defineConfig({
       "https://api-used-time-tracker.vercel.app/*",
       "https://thetick.vercel.app/*"
     ],
+    // Fixed ID so the desktop tracker's native messaging host can allow it
+    browser_specific_settings: {
+      gecko: {
+        id: "tick-extension@thetick.vercel.app"
+      }
+    },
   }
 });
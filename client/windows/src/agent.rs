@@ -0,0 +1,421 @@
+use crate::session::EditorContext;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use uuid::Uuid;
+
+const AGENT_FILE: &str = "agent.json";
+
+/// Port the agent listens on when it is free; otherwise it takes any free port. Clients find the
+/// port in the data folder either way.
+pub const DEFAULT_PORT: u16 = 49_617;
+
+/// Where the native messaging host posts `BrowserEvent`s.
+pub const BROWSER_EVENTS_PATH: &str = "/browser/events";
+
+/// Base of the WakaTime-compatible API; editor plugins use `http://127.0.0.1:<port>/api/v1` as
+/// their `api_url`.
+pub const WAKATIME_API_PATH: &str = "/api/v1";
+
+/// Where `tickctl timer` starts and stops the project timer.
+pub const TIMER_PATH: &str = "/timer";
+
+const HEARTBEAT_PATH: &str = "/api/v1/users/current/heartbeats";
+const HEARTBEATS_BULK_PATH: &str = "/api/v1/users/current/heartbeats.bulk";
+
+/// Larger request bodies are refused.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Connections that don't finish their request within this time are dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the browser extension reports through the native messaging host.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum BrowserEvent {
+    /// The active tab of the focused browser window, sent when it changes or navigates.
+    Tab {
+        url: String,
+        #[serde(default)]
+        title: String,
+    },
+    /// No tab is in front: browser windows lost focus, the active tab is private, or the
+    /// browser closed.
+    None,
+}
+
+/// Foreground app names each WakaTime plugin's editor runs under, by the plugin's name in the
+/// user agent (`vscode-wakatime/...` is `vscode`). Terminal editors count in any terminal.
+const EDITOR_APPS: &[(&str, &[&str])] = &[
+    ("vscode", &["code", "codium", "cursor", "windsurf"]),
+    (
+        "jetbrains",
+        &[
+            "intellij", "idea", "pycharm", "webstorm", "phpstorm", "clion", "goland", "rider", "rubymine",
+            "datagrip", "rustrover", "android studio",
+        ],
+    ),
+    ("sublime", &["sublime"]),
+    ("visualstudio", &["visual studio", "devenv"]),
+    ("notepadpp", &["notepad++"]),
+    ("vim", &["vim", "terminal", "powershell", "pwsh", "cmd", "conhost", "wezterm", "alacritty", "mintty"]),
+    ("emacs", &["emacs", "terminal", "powershell", "pwsh", "cmd", "conhost", "wezterm", "alacritty", "mintty"]),
+];
+
+/// A WakaTime heartbeat, as editor plugins send it through `wakatime-cli`. Fields the tracker
+/// doesn't use are ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// File, app or domain, depending on `kind`.
+    pub entity: String,
+    /// `file` (the default), `app` or `domain`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub time: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+impl Heartbeat {
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_millis((self.time * 1000.0) as i64)
+    }
+
+    /// Whether the heartbeat comes from the editor in the foreground app, judged by the editor
+    /// and plugin named in its user agent. Editors the tracker doesn't know match an app of the
+    /// same name; heartbeats without a user agent match nothing.
+    pub fn is_from(&self, app_name: &str) -> bool {
+        let app = app_name.to_lowercase();
+        let Some(user_agent) = &self.user_agent else {
+            return false;
+        };
+        user_agent
+            .split_whitespace()
+            .filter_map(|part| part.split_once('/'))
+            .map(|(name, _)| name.to_lowercase())
+            .filter(|name| name != "wakatime")
+            .any(|name| {
+                let editor = name.strip_suffix("-wakatime").unwrap_or(&name);
+                match EDITOR_APPS.iter().find(|(plugin, _)| *plugin == editor) {
+                    Some((_, apps)) => apps.iter().any(|keyword| app.contains(keyword)),
+                    None => app.contains(editor),
+                }
+            })
+    }
+
+    pub fn editor_context(&self) -> EditorContext {
+        let is_file = self.kind.as_deref().is_none_or(|kind| kind == "file");
+        EditorContext {
+            project: self.project.clone().filter(|project| !project.is_empty()),
+            file: Some(self.entity.clone()).filter(|entity| is_file && !entity.is_empty()),
+            language: self.language.clone().filter(|language| !language.is_empty()),
+        }
+    }
+}
+
+/// Starts or stops the tracker's project timer.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum TimerCommand {
+    Start { project: String },
+    Stop,
+}
+
+/// Something a local client told the tracker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    Browser(BrowserEvent),
+    Heartbeat(Heartbeat),
+    Timer(TimerCommand),
+}
+
+/// How local clients reach the running tracker, from `agent.json` in the data folder. Requests
+/// must carry the token as a bearer token, so only processes that can read the user's data
+/// folder are accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AgentInfo {
+    pub port: u16,
+    pub token: String,
+}
+
+impl AgentInfo {
+    pub fn load(data_dir: &Path) -> Option<Self> {
+        fs::read_to_string(data_dir.join(AGENT_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(AGENT_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self).expect("Serialization failed"))?;
+        fs::rename(&tmp_path, &path)
+    }
+}
+
+// --- Server ---
+
+/// Starts the tracker's local agent on the loopback interface and publishes its port and token in
+/// the data folder. The token is kept across restarts, since editor plugins are configured with
+/// it. Events are passed to `events` until the runtime shuts down.
+pub async fn start(data_dir: &Path, events: mpsc::UnboundedSender<AgentEvent>) -> io::Result<AgentInfo> {
+    let listener = match TcpListener::bind(("127.0.0.1", DEFAULT_PORT)).await {
+        Ok(listener) => listener,
+        Err(_) => TcpListener::bind(("127.0.0.1", 0)).await?,
+    };
+    let info = AgentInfo {
+        port: listener.local_addr()?.port(),
+        // WakaTime clients only accept keys that look like a UUID
+        token: AgentInfo::load(data_dir)
+            .map(|info| info.token)
+            .filter(|token| Uuid::try_parse(token).is_ok())
+            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+    };
+    info.save(data_dir)?;
+
+    let token = info.token.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let events = events.clone();
+            let token = token.clone();
+            tokio::spawn(async move {
+                let _ = tokio::time::timeout(REQUEST_TIMEOUT, serve(stream, &token, &events)).await;
+            });
+        }
+    });
+    Ok(info)
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+async fn serve(mut stream: TcpStream, token: &str, events: &mpsc::UnboundedSender<AgentEvent>) -> io::Result<()> {
+    let (status, body) = match read_request(&mut stream).await? {
+        Some(request) => handle(&request, token, events),
+        None => error(413, "Request too large"),
+    };
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn handle(request: &Request, token: &str, events: &mpsc::UnboundedSender<AgentEvent>) -> (u16, Option<Value>) {
+    if !is_authorized(request, token) {
+        return error(401, "Unauthorized");
+    }
+    let path = request.path.split('?').next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("POST", BROWSER_EVENTS_PATH) => match serde_json::from_slice::<BrowserEvent>(&request.body) {
+            Ok(event) => {
+                let _ = events.send(AgentEvent::Browser(event));
+                (204, None)
+            }
+            Err(_) => error(400, "Not a browser event"),
+        },
+        ("POST", TIMER_PATH) => match serde_json::from_slice::<TimerCommand>(&request.body) {
+            Ok(command) => {
+                let _ = events.send(AgentEvent::Timer(command));
+                (204, None)
+            }
+            Err(_) => error(400, "Not a timer command"),
+        },
+        ("POST", HEARTBEAT_PATH) => match serde_json::from_slice::<Value>(&request.body) {
+            Ok(heartbeat) => accept_heartbeat(heartbeat, events),
+            Err(_) => error(400, "Not a heartbeat"),
+        },
+        // Answered in WakaTime's format: one `[body, status]` pair per heartbeat
+        ("POST", HEARTBEATS_BULK_PATH) => match serde_json::from_slice::<Vec<Value>>(&request.body) {
+            Ok(heartbeats) => {
+                let responses: Vec<Value> = heartbeats
+                    .into_iter()
+                    .map(|heartbeat| {
+                        let (status, body) = accept_heartbeat(heartbeat, events);
+                        json!([body, status])
+                    })
+                    .collect();
+                (202, Some(json!({ "responses": responses })))
+            }
+            Err(_) => error(400, "Not a list of heartbeats"),
+        },
+        _ => error(404, "Not found"),
+    }
+}
+
+/// Local clients send the token as a bearer token; WakaTime clients send it as the user name of
+/// basic authentication, with or without a password.
+fn is_authorized(request: &Request, token: &str) -> bool {
+    let Some(value) = request.header("Authorization") else {
+        return false;
+    };
+    if let Some(bearer) = value.strip_prefix("Bearer ") {
+        return bearer.trim() == token;
+    }
+    value
+        .strip_prefix("Basic ")
+        .and_then(|encoded| BASE64.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .is_some_and(|credentials| credentials.split(':').next() == Some(token))
+}
+
+fn accept_heartbeat(heartbeat: Value, events: &mpsc::UnboundedSender<AgentEvent>) -> (u16, Option<Value>) {
+    match serde_json::from_value::<Heartbeat>(heartbeat.clone()) {
+        Ok(parsed) => {
+            let _ = events.send(AgentEvent::Heartbeat(parsed));
+            (201, Some(json!({ "data": heartbeat })))
+        }
+        Err(_) => error(400, "Not a heartbeat"),
+    }
+}
+
+fn error(status: u16, message: &str) -> (u16, Option<Value>) {
+    (status, Some(json!({ "error": message })))
+}
+
+/// Reads one request; `None` if its body is larger than `MAX_BODY`.
+async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_BODY {
+            return Ok(None);
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY {
+        return Ok(None);
+    }
+    let mut body = buf[head_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request { method, path, headers, body }))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        _ => "Error",
+    }
+}
+
+// --- Client ---
+
+/// Sends events to the running tracker's agent, for helper processes such as the native
+/// messaging host and `tickctl`.
+#[derive(Debug, Clone)]
+pub struct AgentClient {
+    data_dir: PathBuf,
+    client: reqwest::Client,
+}
+
+impl AgentClient {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            client: reqwest::Client::builder()
+                .no_proxy()
+                .timeout(Duration::from_secs(2))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub async fn send_browser_event(&self, event: &BrowserEvent) -> Result<(), String> {
+        self.post(BROWSER_EVENTS_PATH, event).await
+    }
+
+    pub async fn send_timer(&self, command: &TimerCommand) -> Result<(), String> {
+        self.post(TIMER_PATH, command).await
+    }
+
+    /// The port is read for every request, since it can change when the tracker restarts.
+    async fn post(&self, path: &str, body: &impl Serialize) -> Result<(), String> {
+        let info = AgentInfo::load(&self.data_dir).ok_or("the tracker is not running")?;
+        let response = self
+            .client
+            .post(format!("http://127.0.0.1:{}{}", info.port, path))
+            .bearer_auth(&info.token)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("the tracker returned {}", response.status()))
+        }
+    }
+}
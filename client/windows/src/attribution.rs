@@ -0,0 +1,174 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Assigns a project, and optionally a ticket, to sessions whose app, window title and process
+/// path match. Every pattern that is set must match; rules are tried in order and the first
+/// match wins.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ProjectRule {
+    /// Project to assign. `$1` or `${name}` insert capture groups; groups are numbered across the
+    /// patterns in the order app, title, path.
+    pub project: String,
+    /// Ticket to assign, with the same capture groups.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    /// Regex for the app name, case-insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Regex for the window title when the session started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Regex for the path of the app's executable, case-insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl ProjectRule {
+    /// Appends any problems with this rule to `problems`; `index` is its position in
+    /// `project_rules`.
+    pub fn validate(&self, index: usize, problems: &mut Vec<String>) {
+        if self.project.trim().is_empty() {
+            problems.push(format!("Project rule {} has no project", index + 1));
+        }
+        for (field, pattern, case_insensitive) in self.patterns() {
+            if let Some(pattern) = pattern
+                && let Err(e) = compile(pattern, case_insensitive)
+            {
+                problems.push(format!("Project rule {}: {} pattern is not a valid regex: {}", index + 1, field, e));
+            }
+        }
+    }
+
+    fn patterns(&self) -> [(&'static str, Option<&str>, bool); 3] {
+        [
+            ("app", self.app.as_deref(), true),
+            ("title", self.title.as_deref(), false),
+            ("path", self.path.as_deref(), true),
+        ]
+    }
+}
+
+/// What the first matching rule assigned to a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribution {
+    pub project: String,
+    pub ticket: Option<String>,
+    /// Position of the rule in `project_rules`, from 0.
+    pub rule: usize,
+}
+
+/// `ProjectRule`s with their patterns compiled, for use on every session.
+#[derive(Debug, Clone, Default)]
+pub struct Attributor {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    index: usize,
+    project: String,
+    ticket: Option<String>,
+    /// App, title and path patterns, in that order.
+    patterns: [Option<Regex>; 3],
+}
+
+impl Attributor {
+    /// Rules with an invalid pattern are left out; `Config::validate` reports them.
+    pub fn new(rules: &[ProjectRule]) -> Self {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rule)| {
+                let [app, title, path] = rule
+                    .patterns()
+                    .map(|(_, pattern, case_insensitive)| pattern.map(|p| compile(p, case_insensitive)).transpose());
+                Some(CompiledRule {
+                    index,
+                    project: rule.project.clone(),
+                    ticket: rule.ticket.clone(),
+                    patterns: [app.ok()?, title.ok()?, path.ok()?],
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The project of the first rule matching the session. Rules whose project comes out empty,
+    /// because the groups it uses did not take part in the match, are passed over.
+    pub fn attribute(&self, app_name: &str, title: &str, path: &str) -> Option<Attribution> {
+        self.rules.iter().find_map(|rule| {
+            let mut groups: Vec<Option<&str>> = Vec::new();
+            let mut named: HashMap<&str, &str> = HashMap::new();
+            for (pattern, text) in rule.patterns.iter().zip([app_name, title, path]) {
+                let Some(pattern) = pattern else {
+                    continue;
+                };
+                let captures = pattern.captures(text)?;
+                groups.extend(captures.iter().skip(1).map(|group| group.map(|m| m.as_str())));
+                for name in pattern.capture_names().flatten() {
+                    if let Some(value) = captures.name(name) {
+                        named.insert(name, value.as_str());
+                    }
+                }
+            }
+
+            let project = expand(&rule.project, &groups, &named);
+            if project.is_empty() {
+                return None;
+            }
+            Some(Attribution {
+                project,
+                ticket: rule
+                    .ticket
+                    .as_deref()
+                    .map(|ticket| expand(ticket, &groups, &named))
+                    .filter(|ticket| !ticket.is_empty()),
+                rule: rule.index,
+            })
+        })
+    }
+}
+
+fn compile(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(case_insensitive).build()
+}
+
+/// Fills `$1`, `$name` and `${name}` in `template`; `$$` is a literal `$`. Groups that did not
+/// match insert nothing.
+fn expand(template: &str, groups: &[Option<&str>], named: &HashMap<&str, &str>) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, after) = if let Some(braced) = rest.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+        {
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            continue;
+        }
+        let value = match name.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|i| groups.get(i).copied().flatten()),
+            Err(_) => named.get(name).copied(),
+        };
+        expanded.push_str(value.unwrap_or_default());
+        rest = after;
+    }
+    expanded.push_str(rest);
+    expanded.trim().to_string()
+}
@@ -0,0 +1,75 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+//! Native messaging host for the browser extension. The browser starts it and passes the
+//! extension's tab events on standard input; they are relayed to the running tracker, which
+//! merges them into its own sessions. Standard output belongs to the browser, so everything
+//! else goes to standard error.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io;
+use std::process::ExitCode;
+use tick::{
+    agent::{AgentClient, BrowserEvent},
+    native,
+    paths::Paths,
+};
+
+/// Tells the extension whether the tracker is taking its events; while it is, the extension
+/// doesn't send sessions to the server itself.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "status")]
+struct Status {
+    tracker: bool,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let paths = match Paths::resolve() {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Error: could not resolve the data folder: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let agent = AgentClient::new(&paths.data_dir);
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    let mut reported = None;
+
+    loop {
+        let message = match native::read_message::<Value>(&mut stdin) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Error: could not read from the browser: {}", e);
+                break;
+            }
+        };
+        // Messages this version doesn't know are skipped, so the extension can be newer
+        let event = match serde_json::from_value::<BrowserEvent>(message) {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Ignoring message: {}", e);
+                continue;
+            }
+        };
+
+        let result = agent.send_browser_event(&event).await;
+        if let Err(e) = &result {
+            eprintln!("Could not reach the tracker: {}", e);
+        }
+        let tracker = result.is_ok();
+        if reported != Some(tracker) {
+            reported = Some(tracker);
+            if let Err(e) = native::write_message(&mut stdout, &Status { tracker }) {
+                eprintln!("Error: could not write to the browser: {}", e);
+                break;
+            }
+        }
+    }
+
+    // The browser closed or the extension went away, so none of its tabs is in front any more
+    let _ = agent.send_browser_event(&BrowserEvent::None).await;
+    ExitCode::SUCCESS
+}
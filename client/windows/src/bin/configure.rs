@@ -1,50 +1,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use clap::Parser;
 use eframe::egui;
-use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, ExitCode};
+use std::sync::mpsc;
 use std::time::{Duration, Instant};
+use tick::{
+    config::{self, Config, ConfigError, Theme},
+    device::{self, DeviceIdentity},
+    export::{self, ExportFormat, ExportOptions},
+    history::{History, HistoryEntry},
+    paths::Paths,
+    policy::{Policy, PolicyField},
+    probe::{self, ProbeOutcome},
+    remote::{RemoteField, RemoteSettings, SettingsSync},
+    secrets::Secrets,
+};
 
 // --- Models & Types ---
 
-#[derive(Serialize, Deserialize, Default, Clone)]
-struct Config {
-    server_url: String,
-    api_key: Option<String>,
-    #[serde(default)]
-    theme: String,
-}
-
-#[derive(Clone, Copy, PartialEq)]
-enum Theme {
-    Light,
-    Dark,
-}
-
-impl Default for Theme {
-    fn default() -> Self {
-        Theme::Dark
-    }
-}
-
-impl Theme {
-    fn from_str(s: &str) -> Self {
-        match s {
-            "light" => Theme::Light,
-            _ => Theme::Dark,
-        }
-    }
-
-    fn to_str(&self) -> &'static str {
-        match self {
-            Theme::Light => "light",
-            Theme::Dark => "dark",
-        }
-    }
-}
-
 #[derive(Clone, Copy)]
 enum StatusType {
     Info,
@@ -106,9 +82,18 @@ impl AppStyle {
 
 // --- UI Components ---
 
-fn modern_input(ui: &mut egui::Ui, label: &str, value: &mut String, hint: &str, password: bool, style: &AppStyle) -> bool {
+const MANAGED_BY_POLICY: &str = "🔒 Managed by your organization";
+const SET_BY_SERVER: &str = "☁ Set on your Tick server";
+
+/// A `managed` field shows its value read-only with a note on where it is set instead.
+fn modern_input(ui: &mut egui::Ui, label: &str, value: &mut String, hint: &str, password: bool, managed: Option<&str>, style: &AppStyle) -> bool {
     ui.vertical(|ui| {
-        ui.label(egui::RichText::new(label).size(13.0).strong().color(style.text_primary));
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(label).size(13.0).strong().color(style.text_primary));
+            if let Some(note) = managed {
+                ui.label(egui::RichText::new(note).size(12.0).color(style.text_secondary));
+            }
+        });
         ui.add_space(6.0);
         
         let mut changed = false;
@@ -119,7 +104,8 @@ fn modern_input(ui: &mut egui::Ui, label: &str, value: &mut String, hint: &str,
             .inner_margin(egui::Margin::symmetric(14.0, 12.0))
             .show(ui, |ui| {
                 ui.set_width(ui.available_width());
-                let response = ui.add(
+                let response = ui.add_enabled(
+                    managed.is_none(),
                     egui::TextEdit::singleline(value)
                         .hint_text(egui::RichText::new(hint).color(style.input_hint))
                         .password(password)
@@ -169,30 +155,78 @@ fn draw_backdrop(ui: &mut egui::Ui, theme: Theme) {
 
 struct SetupApp {
     config: Config,
-    config_path: PathBuf,
+    paths: Paths,
+    secrets: Secrets,
+    policy: Policy,
+    /// Why the machine policy couldn't be read; nothing is saved until it can.
+    policy_error: Option<String>,
+    /// Settings the tracker last received from the server, shown but not editable here.
+    remote: Option<RemoteSettings>,
+    device_id: Option<String>,
+    default_device_name: String,
+    /// Comma-separated `config.excluded_apps`, parsed on save.
+    excluded_apps_text: String,
+    /// `config.idle_threshold_secs` in minutes, parsed on save.
+    idle_minutes_text: String,
     status_message: Option<String>,
     status_type: StatusType,
     successfully_saved_at: Option<Instant>,
     tracker_launched: bool,
     current_theme: Theme,
+    probe_rx: Option<mpsc::Receiver<ProbeOutcome>>,
+    recent_sessions: Vec<HistoryEntry>,
 }
 
 impl SetupApp {
     fn new(cc: &eframe::CreationContext) -> Self {
-        let exe_path = std::env::current_exe().expect("Failed to get current executable path");
-        let exe_dir = exe_path.parent().expect("Failed to get executable directory");
-        let config_path = exe_dir.join("config.json");
-
-        let config: Config = match fs::read_to_string(&config_path) {
-            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-            Err(_) => Config {
-                server_url: "http://localhost:3000/api/log-session".to_string(),
-                api_key: None,
-                theme: "dark".to_string(),
-            },
+        let paths = Paths::resolve().expect("Failed to resolve config and data directories");
+        let config_path = &paths.config_file;
+        let secrets = Secrets::new(&paths.data_dir);
+
+        // Generated here on a fresh install so the tracker never reports an unrelated legacy ID
+        let device_id = match DeviceIdentity::load_or_create(&paths.data_dir, paths.has_legacy_config()) {
+            Ok(identity) => Some(identity.id),
+            Err(e) => {
+                println!("Failed to store device identity: {}", e);
+                None
+            }
         };
 
-        let current_theme = Theme::from_str(&config.theme);
+        // Never silently drop a broken file: keep a copy and pre-fill whatever is still readable
+        let (config, status_message) = match fs::read_to_string(config_path) {
+            Ok(content) => match Config::load_with_secrets(config_path, &secrets) {
+                Ok(config) => (config, None),
+                Err(e) => {
+                    let note = match config::backup(config_path) {
+                        Ok(backup_path) => format!("original saved as {}", backup_path.display()),
+                        Err(e) => format!("backup failed: {}", e),
+                    };
+                    let mut config = Config::recover(&content);
+                    if config.api_key.is_none() {
+                        let _ = config.load_api_keys(&secrets);
+                    }
+                    (config, Some(format!("{} ({})", e, note)))
+                }
+            },
+            Err(_) => (Config::default(), None),
+        };
+        // The user's own settings are edited; policy values are only shown
+        let (policy, policy_error) = match Policy::load_machine() {
+            Ok(policy) => (policy, None),
+            Err(e) => (Policy::default(), Some(e)),
+        };
+        let status_message = policy_error.clone().or(status_message);
+        let status_type = if status_message.is_some() { StatusType::Error } else { StatusType::Info };
+        let remote = SettingsSync::new(&paths.data_dir)
+            .last_known(policy.server_url.as_deref().unwrap_or(&config.server_url));
+        let excluded_apps_text = config.excluded_apps.join(", ");
+        let idle_minutes_text = config
+            .idle_threshold_secs
+            .map(|secs| (secs / 60).to_string())
+            .unwrap_or_default();
+
+        let current_theme = config.theme;
+        let recent_sessions = History::new(&paths.data_dir).recent(RECENT_SESSIONS);
 
         // Custom Fonts
         let mut fonts = egui::FontDefinitions::default();
@@ -204,12 +238,22 @@ impl SetupApp {
 
         let mut app = Self {
             config,
-            config_path,
-            status_message: None,
-            status_type: StatusType::Info,
+            paths,
+            secrets,
+            policy,
+            policy_error,
+            remote,
+            device_id,
+            default_device_name: device::default_name(),
+            excluded_apps_text,
+            idle_minutes_text,
+            status_message,
+            status_type,
             successfully_saved_at: None,
             tracker_launched: false,
             current_theme,
+            probe_rx: None,
+            recent_sessions,
         };
 
         app.apply_visuals(&cc.egui_ctx);
@@ -227,27 +271,46 @@ impl SetupApp {
     }
 
     fn toggle_theme(&mut self, ctx: &egui::Context) {
-        self.current_theme = match self.current_theme {
-            Theme::Light => Theme::Dark,
-            Theme::Dark => Theme::Light,
-        };
-        self.config.theme = self.current_theme.to_str().to_string();
+        self.current_theme = self.current_theme.toggled();
+        self.config.theme = self.current_theme;
         self.apply_visuals(ctx);
         ctx.request_repaint();
     }
 
     fn save(&mut self) {
-        if self.config.server_url.trim().is_empty() {
-            self.status_message = Some("Endpoint cannot be empty".to_string());
+        // Saving without the policy could store values an administrator has locked
+        if let Some(e) = &self.policy_error {
+            self.status_message = Some(e.clone());
             self.status_type = StatusType::Error;
             return;
         }
+        self.config.theme = self.current_theme;
+        self.config.excluded_apps = self
+            .excluded_apps_text
+            .split(',')
+            .map(str::trim)
+            .filter(|app| !app.is_empty())
+            .map(str::to_string)
+            .collect();
+        self.config.idle_threshold_secs = match self.idle_minutes_text.trim() {
+            "" => None,
+            minutes => match minutes.parse::<u64>() {
+                Ok(minutes) => Some(minutes * 60),
+                Err(_) => {
+                    self.status_message = Some("Idle threshold must be a whole number of minutes".to_string());
+                    self.status_type = StatusType::Error;
+                    return;
+                }
+            },
+        };
 
-        self.config.theme = self.current_theme.to_str().to_string();
-        let json = serde_json::to_string_pretty(&self.config).expect("Serialization failed");
-        
-        if let Err(e) = fs::write(&self.config_path, json) {
-            self.status_message = Some(format!("Error: {}", e));
+        if let Err(e) = self.policy.check(&self.config) {
+            self.status_message = Some(e.to_string());
+            self.status_type = StatusType::Error;
+            return;
+        }
+        if let Err(e) = self.config.save_with_secrets(&self.paths.config_file, &self.secrets) {
+            self.status_message = Some(e.to_string());
             self.status_type = StatusType::Error;
         } else {
             self.status_message = Some("Settings synced! Launching...".to_string());
@@ -257,24 +320,102 @@ impl SetupApp {
         }
     }
 
-    fn launch_tracker(&self) {
-        if let Ok(exe_path) = std::env::current_exe() {
-            if let Some(exe_dir) = exe_path.parent() {
-                let tracker_exe = exe_dir.join("tracker.exe");
-                if tracker_exe.exists() {
-                    let _ = Command::new(tracker_exe).spawn();
-                }
+    /// Runs the probe on a worker thread; the result is picked up in `update`.
+    fn test_connection(&mut self, ctx: &egui::Context) {
+        let server_url = self.policy.server_url.clone().unwrap_or_else(|| self.config.server_url.clone());
+        let api_key = self.config.api_key.clone();
+        let ctx = ctx.clone();
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let outcome = match tokio::runtime::Runtime::new() {
+                Ok(rt) => rt.block_on(probe::test_connection(&server_url, api_key.as_deref())),
+                Err(e) => ProbeOutcome::Unreachable(e.to_string()),
+            };
+            let _ = tx.send(outcome);
+            ctx.request_repaint();
+        });
+
+        self.probe_rx = Some(rx);
+        self.status_message = Some("Testing connection...".to_string());
+        self.status_type = StatusType::Info;
+    }
+
+    /// Writes the last `EXPORT_DAYS` days of history to the Downloads folder.
+    fn export_history(&mut self, format: ExportFormat) {
+        let today = chrono::Local::now().date_naive();
+        let from = today - chrono::Duration::days(EXPORT_DAYS - 1);
+        let (start, until) = export::local_days(from, today);
+        let entries = History::new(&self.paths.data_dir).between(start, until);
+        let content = export::export(
+            &entries,
+            format,
+            &ExportOptions {
+                merge_gap: None,
+                redacted_apps: self.config.redacted_apps.clone(),
+                generated_at: chrono::Utc::now(),
+            },
+        );
+
+        let dir = dirs::download_dir()
+            .or_else(dirs::document_dir)
+            .unwrap_or_else(|| self.paths.data_dir.clone());
+        let path = dir.join(format!("tick-{}-to-{}.{}", from, today, format.extension()));
+        match fs::write(&path, content) {
+            Ok(()) => {
+                self.status_message = Some(format!("Exported {} sessions to {}", entries.len(), path.display()));
+                self.status_type = StatusType::Success;
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Export failed: {}", e));
+                self.status_type = StatusType::Error;
             }
         }
     }
 }
 
+const RECENT_SESSIONS: usize = 10;
+
+/// Days covered by the export buttons; `tickctl export` takes any range.
+const EXPORT_DAYS: i64 = 30;
+
+/// One line per session: when, what, how long and where it went.
+fn describe_session(entry: &HistoryEntry) -> String {
+    let start = chrono::DateTime::parse_from_rfc3339(&entry.session.start_time);
+    let end = chrono::DateTime::parse_from_rfc3339(&entry.session.end_time);
+    let (when, minutes) = match (start, end) {
+        (Ok(start), Ok(end)) => (
+            start.with_timezone(&chrono::Local).format("%a %H:%M").to_string(),
+            end.signed_duration_since(start).num_minutes(),
+        ),
+        _ => (String::new(), 0),
+    };
+    let duration = if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes.max(1))
+    };
+    let destinations = if entry.destinations.is_empty() {
+        "not sent".to_string()
+    } else {
+        entry.destinations.join(", ")
+    };
+    format!("{}  {} · {} → {}", when, entry.session.app_name, duration, destinations)
+}
+
+fn launch_tracker(paths: &Paths) {
+    let tracker_exe = paths.exe_dir.join("tracker.exe");
+    if tracker_exe.exists() {
+        let _ = Command::new(tracker_exe).args(paths.forward_args()).spawn();
+    }
+}
+
 impl eframe::App for SetupApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if let Some(saved_at) = self.successfully_saved_at {
             if saved_at.elapsed() >= Duration::from_secs(2) {
                 if !self.tracker_launched {
-                    self.launch_tracker();
+                    launch_tracker(&self.paths);
                     self.tracker_launched = true;
                 }
                 ctx.send_viewport_cmd(egui::ViewportCommand::Close);
@@ -283,6 +424,12 @@ impl eframe::App for SetupApp {
             ctx.request_repaint();
         }
 
+        if let Some(outcome) = self.probe_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.status_type = if outcome.is_ok() { StatusType::Success } else { StatusType::Error };
+            self.status_message = Some(outcome.to_string());
+            self.probe_rx = None;
+        }
+
         let style = AppStyle::from_theme(self.current_theme);
 
         egui::CentralPanel::default()
@@ -290,7 +437,7 @@ impl eframe::App for SetupApp {
             .show(ctx, |ui| {
                 draw_backdrop(ui, self.current_theme);
 
-                egui::Frame::none().inner_margin(28.0).show(ui, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| egui::Frame::none().inner_margin(28.0).show(ui, |ui| {
                     // --- Header ---
                     ui.horizontal(|ui| {
                         let (rect, _) = ui.allocate_at_least(egui::vec2(32.0, 32.0), egui::Sense::hover());
@@ -351,16 +498,70 @@ impl eframe::App for SetupApp {
                         .rounding(24.0)
                         .inner_margin(32.0)
                         .show(ui, |ui| {
-                            if modern_input(ui, "Server Endpoint", &mut self.config.server_url, "https://api.tick.ai", false, &style) {
-                                self.status_message = None;
+                            match self.policy.server_url.clone() {
+                                Some(mut managed) => {
+                                    modern_input(ui, "Server Endpoint", &mut managed, "", false, Some(MANAGED_BY_POLICY), &style);
+                                }
+                                None => {
+                                    if modern_input(ui, "Server Endpoint", &mut self.config.server_url, "https://api.tick.ai", false, None, &style) {
+                                        self.status_message = None;
+                                    }
+                                }
                             }
 
                             let mut api_key_str = self.config.api_key.clone().unwrap_or_default();
-                            if modern_input(ui, "Access token", &mut api_key_str, "••••••••••••••••", true, &style) {
+                            if modern_input(ui, "Access token", &mut api_key_str, "••••••••••••••••", true, None, &style) {
                                 self.config.api_key = if api_key_str.is_empty() { None } else { Some(api_key_str) };
                                 self.status_message = None;
                             }
 
+                            let mut device_name = self.config.device_name.clone().unwrap_or_default();
+                            if modern_input(ui, "Device name", &mut device_name, &self.default_device_name, false, None, &style) {
+                                self.config.device_name = if device_name.trim().is_empty() { None } else { Some(device_name) };
+                                self.status_message = None;
+                            }
+                            if let Some(device_id) = &self.device_id {
+                                ui.label(egui::RichText::new(format!("Device ID: {}", device_id)).size(11.0).color(style.text_secondary));
+                                ui.add_space(12.0);
+                            }
+
+                            let remote_idle = self.remote.as_ref().and_then(|remote| remote.idle_threshold_secs);
+                            match (self.policy.idle_threshold_secs, remote_idle) {
+                                (Some(secs), _) => {
+                                    let mut managed = (secs / 60).to_string();
+                                    modern_input(ui, "Idle threshold (minutes)", &mut managed, "", false, Some(MANAGED_BY_POLICY), &style);
+                                }
+                                (None, Some(secs)) => {
+                                    let mut managed = (secs / 60).to_string();
+                                    modern_input(ui, "Idle threshold (minutes)", &mut managed, "", false, Some(SET_BY_SERVER), &style);
+                                }
+                                (None, None) => {
+                                    if modern_input(ui, "Idle threshold (minutes)", &mut self.idle_minutes_text, "Never", false, None, &style) {
+                                        self.status_message = None;
+                                    }
+                                }
+                            }
+
+                            if modern_input(ui, "Excluded apps", &mut self.excluded_apps_text, "Comma-separated, e.g. KeePass, Signal", false, None, &style) {
+                                self.status_message = None;
+                            }
+                            if self.policy.is_locked(PolicyField::ExcludedApps) {
+                                ui.label(
+                                    egui::RichText::new(format!("🔒 Always excluded: {}", self.policy.excluded_apps.join(", ")))
+                                        .size(12.0)
+                                        .color(style.text_secondary),
+                                );
+                                ui.add_space(12.0);
+                            }
+                            if let Some(remote) = self.remote.as_ref().filter(|remote| remote.is_managed(RemoteField::ExcludedApps)) {
+                                ui.label(
+                                    egui::RichText::new(format!("☁ Also excluded by your server: {}", remote.excluded_apps.join(", ")))
+                                        .size(12.0)
+                                        .color(style.text_secondary),
+                                );
+                                ui.add_space(12.0);
+                            }
+
                             ui.add_space(12.0);
 
                             // Gradient-like Button
@@ -379,6 +580,17 @@ impl eframe::App for SetupApp {
                             if response.clicked() {
                                 self.save();
                             }
+
+                            ui.add_space(10.0);
+                            ui.vertical_centered(|ui| {
+                                let testing = self.probe_rx.is_some();
+                                let label = if testing { "Testing..." } else { "Test connection" };
+                                let button = egui::Button::new(egui::RichText::new(label).size(14.0).color(style.accent_color))
+                                    .frame(false);
+                                if ui.add_enabled(!testing, button).clicked() {
+                                    self.test_connection(ctx);
+                                }
+                            });
                         });
 
                     // --- Status Notification ---
@@ -393,24 +605,212 @@ impl eframe::App for SetupApp {
                             ui.label(egui::RichText::new(format!("• {}", msg)).color(color).size(14.0).strong());
                         });
                     }
-                });
+
+                    // --- Recent Sessions ---
+                    if !self.recent_sessions.is_empty() {
+                        ui.add_space(28.0);
+                        let mut export_format = None;
+                        egui::CollapsingHeader::new(
+                            egui::RichText::new("Recent sessions").size(13.0).strong().color(style.text_primary),
+                        )
+                        .show(ui, |ui| {
+                            for entry in &self.recent_sessions {
+                                ui.label(egui::RichText::new(describe_session(entry)).size(12.0).color(style.text_secondary));
+                            }
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new(format!("Export last {} days:", EXPORT_DAYS))
+                                        .size(12.0)
+                                        .color(style.text_secondary),
+                                );
+                                for format in ExportFormat::ALL {
+                                    let label = egui::RichText::new(format.extension().to_uppercase()).size(12.0).color(style.accent_color);
+                                    if ui.add(egui::Button::new(label).frame(false)).clicked() {
+                                        export_format = Some(format);
+                                    }
+                                }
+                            });
+                        });
+                        if let Some(format) = export_format {
+                            self.export_history(format);
+                        }
+                    }
+                }));
             });
     }
 }
 
+// --- Headless Mode ---
+
+/// Without any of these options the setup wizard opens as usual.
+#[derive(Parser, Debug)]
+#[command(name = "configure", about = "Configure the Tick time tracker", version)]
+struct Cli {
+    /// Config file to use instead of the per-user one
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Start from this config file instead of the current settings
+    #[arg(long, value_name = "PATH")]
+    from_file: Option<PathBuf>,
+
+    /// REST endpoint where sessions are sent
+    #[arg(long, value_name = "URL")]
+    server_url: Option<String>,
+
+    /// Access token for the server
+    #[arg(long, value_name = "TOKEN")]
+    api_key: Option<String>,
+
+    #[arg(long, value_parser = ["light", "dark"])]
+    theme: Option<String>,
+
+    /// Name shown on the dashboard for this computer
+    #[arg(long, value_name = "NAME")]
+    device_name: Option<String>,
+
+    /// Print the resulting config instead of writing it
+    #[arg(long)]
+    print: bool,
+
+    /// Do not start the tracker after writing the config
+    #[arg(long)]
+    no_launch: bool,
+}
+
+impl Cli {
+    fn is_headless(&self) -> bool {
+        self.from_file.is_some()
+            || self.server_url.is_some()
+            || self.api_key.is_some()
+            || self.theme.is_some()
+            || self.device_name.is_some()
+            || self.print
+            || self.no_launch
+    }
+}
+
+const EXIT_INVALID: u8 = 1;
+const EXIT_IO: u8 = 3;
+
+fn run_headless(cli: Cli) -> ExitCode {
+    let paths = match Paths::resolve_with(cli.config.clone()) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Error: could not resolve config directory: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+    };
+    let secrets = Secrets::new(&paths.data_dir);
+
+    let base = match &cli.from_file {
+        Some(path) => Config::load_template(path, &secrets),
+        None if paths.config_file.exists() => Config::load_with_secrets(&paths.config_file, &secrets),
+        None => Ok(Config::default()),
+    };
+    let mut config = match base {
+        Ok(config) => config,
+        Err(e @ (ConfigError::Io(_) | ConfigError::Secret(_))) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_INVALID);
+        }
+    };
+
+    let policy = match Policy::load_machine() {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_INVALID);
+        }
+    };
+    if let Some(server_url) = cli.server_url {
+        if policy.is_locked(PolicyField::ServerUrl) {
+            eprintln!("Warning: the server endpoint is managed by policy; --server-url has no effect");
+        }
+        config.server_url = server_url;
+    }
+    if let Some(api_key) = cli.api_key {
+        config.api_key = Some(api_key);
+    }
+    if let Some(theme) = cli.theme {
+        config.theme = if theme == "light" { Theme::Light } else { Theme::Dark };
+    }
+    if let Some(device_name) = cli.device_name {
+        config.device_name = Some(device_name).filter(|name| !name.trim().is_empty());
+    }
+
+    if let Err(e) = config.validate().and_then(|()| policy.check(&config)) {
+        eprintln!("Error: {}", e);
+        return ExitCode::from(EXIT_INVALID);
+    }
+
+    if cli.print {
+        println!("{}", config.to_json());
+        return ExitCode::SUCCESS;
+    }
+
+    if let Err(e) = DeviceIdentity::load_or_create(&paths.data_dir, paths.has_legacy_config()) {
+        eprintln!("Error: could not store device identity: {}", e);
+        return ExitCode::from(EXIT_IO);
+    }
+
+    if let Err(e) = config.save_with_secrets(&paths.config_file, &secrets) {
+        eprintln!("Error: {}", e);
+        return ExitCode::from(EXIT_IO);
+    }
+    println!("Saved {}", paths.config_file.display());
+
+    if !cli.no_launch {
+        launch_tracker(&paths);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Release builds use the GUI subsystem, which has no console of its own.
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
 // --- Main Entry ---
 
-fn main() -> eframe::Result<()> {
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            attach_console();
+            e.exit();
+        }
+    };
+    if cli.is_headless() {
+        attach_console();
+        return run_headless(cli);
+    }
+
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
-            .with_inner_size([480.0, 640.0])
+            .with_inner_size([480.0, 820.0])
             .with_resizable(false)
             .with_title("Tick Setup"),
         ..Default::default()
     };
-    eframe::run_native(
+    match eframe::run_native(
         "Tick Setup",
         options,
         Box::new(|cc| Ok(Box::new(SetupApp::new(cc)))),
-    )
+    ) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
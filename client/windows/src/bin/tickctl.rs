@@ -0,0 +1,552 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+use tick::{
+    agent::{AgentClient, AgentInfo, TimerCommand, WAKATIME_API_PATH},
+    attribution::Attributor,
+    category::Categorizer,
+    config::{Config, ConfigError},
+    device::{self, DeviceIdentity, LegacyLink},
+    export::{self, ExportFormat, ExportOptions},
+    history::History,
+    import::{self, ImportOptions, ImportSource},
+    native,
+    paths::Paths,
+    policy::Policy,
+    remote::CategorySync,
+    routing,
+    secrets::Secrets,
+    sink::{Delivery, RetryPolicy},
+    timer::TimerState,
+};
+
+const EXIT_INVALID: u8 = 1;
+const EXIT_IO: u8 = 3;
+
+/// Days exported when `--from` is not given.
+const DEFAULT_EXPORT_DAYS: i64 = 7;
+
+#[derive(Parser, Debug)]
+#[command(name = "tickctl", about = "Work with the Tick time tracker's local data", version)]
+struct Cli {
+    /// Config file to use instead of the per-user one
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write recorded sessions as CSV, JSON, iCalendar or Parquet
+    Export(ExportArgs),
+    /// Send sessions from ActivityWatch, RescueTime or Toggl exports to the configured sinks
+    Import(ImportArgs),
+    /// Register or remove the host the browser extension reports tabs through
+    #[command(subcommand)]
+    Browser(BrowserCommand),
+    /// Print the settings that point WakaTime editor plugins at the tracker
+    Wakatime,
+    /// Start, stop or show the project timer that tags sessions
+    #[command(subcommand)]
+    Timer(TimerArgs),
+    /// Try the project rules from the config
+    #[command(subcommand)]
+    Rules(RulesArgs),
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesArgs {
+    /// Show the project and ticket the rules assign to a window title
+    Test {
+        #[arg(value_name = "TITLE")]
+        title: String,
+
+        /// App name, for rules that match the app
+        #[arg(long, value_name = "NAME", default_value = "")]
+        app: String,
+
+        /// Path of the app's executable, for rules that match the path
+        #[arg(long, value_name = "PATH", default_value = "")]
+        path: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TimerArgs {
+    /// Tag sessions with a project from now on, stopping any other project's timer
+    Start {
+        #[arg(value_name = "PROJECT")]
+        project: String,
+    },
+    /// Stop tagging sessions
+    Stop,
+    /// Show the running timer and recent projects
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum BrowserCommand {
+    /// Register the host with Chrome, Chromium, Edge and Firefox for the current user
+    Install {
+        /// ID of the extension in Chromium-based browsers; they only start the host for listed IDs
+        #[arg(long = "chrome-extension-id", value_name = "ID")]
+        chrome_extension_ids: Vec<String>,
+    },
+    /// Remove the host's registrations
+    Uninstall,
+}
+
+#[derive(Args, Debug)]
+struct ExportArgs {
+    /// First day to include, in local time (defaults to a week before --to)
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    from: Option<NaiveDate>,
+
+    /// Last day to include, in local time (defaults to today)
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    to: Option<NaiveDate>,
+
+    /// csv, json, ics or parquet; defaults to the extension of --output, then csv
+    #[arg(long)]
+    format: Option<Format>,
+
+    /// Join sessions of the same app that are at most this many minutes apart
+    #[arg(long, value_name = "MINUTES", num_args = 0..=1, default_missing_value = "5")]
+    merge: Option<u32>,
+
+    /// File to write instead of standard output; the folder to write into for parquet
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ImportArgs {
+    /// ActivityWatch bucket exports (.json), RescueTime or Toggl CSVs
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// activitywatch, rescuetime or toggl; detected from each file when omitted
+    #[arg(long)]
+    source: Option<ImportSource>,
+
+    /// Show what would be imported without sending or recording anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Device the sessions belong to (defaults to this computer)
+    #[arg(long, value_name = "ID")]
+    device_id: Option<String>,
+
+    /// Name shown for that device (defaults to this computer's)
+    #[arg(long, value_name = "NAME")]
+    device_name: Option<String>,
+
+    /// IANA time zone of the sessions, used for CSVs with local times (defaults to this computer's)
+    #[arg(long, value_name = "ZONE")]
+    time_zone: Option<String>,
+}
+
+/// Parquet is written as a folder of daily files rather than a single document.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text(ExportFormat),
+    Parquet,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("parquet") {
+            Ok(Format::Parquet)
+        } else {
+            s.parse().map(Format::Text).map_err(|_| format!("unknown export format `{}`; use csv, json, ics or parquet", s))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let paths = match Paths::resolve_with(cli.config) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Error: could not resolve config directory: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+    };
+
+    // Redaction settings live in the config, so a broken file stops the export instead of
+    // leaking names the user asked to hide
+    let config = if paths.config_file.exists() {
+        match Config::load(&paths.config_file) {
+            Ok(config) => config,
+            Err(e @ ConfigError::Io(_)) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::from(EXIT_IO);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::from(EXIT_INVALID);
+            }
+        }
+    } else {
+        Config::default()
+    };
+
+    match cli.command {
+        Command::Export(args) => run_export(&paths, &config, args),
+        Command::Import(args) => run_import(&paths, config, args),
+        Command::Browser(command) => run_browser(&paths, command),
+        Command::Wakatime => run_wakatime(&paths),
+        Command::Timer(args) => run_timer(&paths, args),
+        Command::Rules(args) => run_rules(&config, args),
+    }
+}
+
+// --- Export ---
+
+fn run_export(paths: &Paths, config: &Config, args: ExportArgs) -> ExitCode {
+    let ExportArgs { from, to, format, merge, output } = args;
+    let to = to.unwrap_or_else(|| Local::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(DEFAULT_EXPORT_DAYS - 1));
+    if from > to {
+        eprintln!("Error: --from must not be after --to");
+        return ExitCode::from(EXIT_INVALID);
+    }
+    let format = format
+        .or_else(|| output.as_ref()?.extension()?.to_str()?.parse().ok())
+        .unwrap_or(Format::Text(ExportFormat::Csv));
+
+    let (start, until) = export::local_days(from, to);
+    let entries = History::new(&paths.data_dir).between(start, until);
+    let options = ExportOptions {
+        merge_gap: merge.map(|minutes| Duration::minutes(minutes.into())),
+        redacted_apps: config.redacted_apps.clone(),
+        generated_at: Utc::now(),
+    };
+
+    let format = match format {
+        Format::Text(format) => format,
+        Format::Parquet => {
+            let Some(dir) = output else {
+                eprintln!("Error: Parquet exports need --output <FOLDER>");
+                return ExitCode::from(EXIT_INVALID);
+            };
+            return match export::write_parquet(&entries, &options, &dir) {
+                Ok(files) => {
+                    eprintln!("Exported {} sessions to {} daily files in {}", entries.len(), files.len(), dir.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    ExitCode::from(EXIT_IO)
+                }
+            };
+        }
+    };
+    let content = export::export(&entries, format, &options);
+
+    let written = match &output {
+        Some(path) => fs::write(path, &content),
+        None => io::stdout().write_all(content.as_bytes()),
+    };
+    if let Err(e) = written {
+        eprintln!("Error: could not write export: {}", e);
+        return ExitCode::from(EXIT_IO);
+    }
+    if let Some(path) = output {
+        eprintln!("Exported {} sessions to {}", entries.len(), path.display());
+    }
+    ExitCode::SUCCESS
+}
+
+// --- Import ---
+
+/// Sessions listed by a dry run.
+const PREVIEW_SESSIONS: usize = 10;
+
+/// How long to keep uploading before the rest is left for the next import.
+const IMPORT_UPLOAD_LIMIT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+fn run_import(paths: &Paths, mut config: Config, args: ImportArgs) -> ExitCode {
+    let policy = match Policy::load_machine() {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_INVALID);
+        }
+    };
+    if let Err(e) = policy.check(&config) {
+        eprintln!("Error: {}", e);
+        return ExitCode::from(EXIT_INVALID);
+    }
+    policy.apply(&mut config);
+
+    let identity = match DeviceIdentity::load_or_create(&paths.data_dir, paths.has_legacy_config()) {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("Error: could not store device identity: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+    };
+    let options = ImportOptions {
+        device_id: args.device_id.unwrap_or_else(|| identity.id.clone()),
+        device_name: args
+            .device_name
+            .or_else(|| config.device_name.clone())
+            .or_else(|| Some(device::default_name())),
+        time_zone: args
+            .time_zone
+            .unwrap_or_else(|| iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string())),
+    };
+
+    let mut parsed = Vec::new();
+    for file in &args.files {
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error: could not read {}: {}", file.display(), e);
+                return ExitCode::from(EXIT_IO);
+            }
+        };
+        let Some(source) = args.source.or_else(|| ImportSource::detect(file, &content)) else {
+            eprintln!("Error: could not tell what kind of export {} is; pass --source", file.display());
+            return ExitCode::from(EXIT_INVALID);
+        };
+        match import::parse(source, &content, &options) {
+            Ok(entries) => {
+                println!("{}: {} sessions ({})", file.display(), entries.len(), source);
+                parsed.extend(entries);
+            }
+            Err(e) => {
+                eprintln!("Error: {}: {}", file.display(), e);
+                return ExitCode::from(EXIT_INVALID);
+            }
+        }
+    }
+
+    // Sessions from trackers without categories get the ones the tracker would have given them
+    let server_categories = config
+        .sync_categories
+        .then(|| CategorySync::new(&paths.data_dir).last_known(&config.server_url))
+        .flatten();
+    let categorizer = Categorizer::new(&config.category_rules, server_categories.as_ref());
+    for entry in parsed.iter_mut().filter(|entry| entry.category.is_none()) {
+        let category = categorizer.categorize(&entry.session.app_name, entry.session.url.as_deref());
+        entry.category = Some(category.to_string());
+    }
+
+    let history = History::new(&paths.data_dir);
+    let range = parsed
+        .iter()
+        .filter_map(|entry| {
+            let start = DateTime::parse_from_rfc3339(&entry.session.start_time).ok()?;
+            let end = DateTime::parse_from_rfc3339(&entry.session.end_time).ok()?;
+            Some((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+        })
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)));
+    let existing = match range {
+        // Recorded sessions that started up to a day earlier can still overlap
+        Some((first, last)) => history.between(first - Duration::days(1), last),
+        None => Vec::new(),
+    };
+    let plan = import::plan(parsed, &existing, &config);
+    println!(
+        "{} new sessions, {} already recorded, {} excluded",
+        plan.sessions.len(),
+        plan.duplicates,
+        plan.excluded
+    );
+
+    if args.dry_run {
+        for entry in plan.sessions.iter().take(PREVIEW_SESSIONS) {
+            let title = entry.session.title.as_deref().map(|t| format!(" \"{}\"", t)).unwrap_or_default();
+            println!("  {}  {}{}", entry.session.start_time, entry.session.app_name, title);
+        }
+        if plan.sessions.len() > PREVIEW_SESSIONS {
+            println!("  ... and {} more", plan.sessions.len() - PREVIEW_SESSIONS);
+        }
+        println!("Dry run: nothing was sent or recorded");
+        return ExitCode::SUCCESS;
+    }
+
+    if let Err(e) = config.load_api_keys(&Secrets::new(&paths.data_dir)) {
+        eprintln!("Error: {}", e);
+        return ExitCode::from(EXIT_IO);
+    }
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+    };
+    // Linking the legacy ID only makes sense for sessions of this computer
+    let legacy_link = if options.device_id == identity.id {
+        LegacyLink::new(&paths.data_dir, &identity)
+    } else {
+        LegacyLink::new(&paths.data_dir, &DeviceIdentity { legacy_id: None, ..identity.clone() })
+    };
+    runtime.block_on(async {
+        // A queue of its own, so a running tracker's queues are left alone; anything not
+        // delivered is resumed by the next import
+        let mut delivery = Delivery::start_with(
+            &config,
+            &paths.data_dir,
+            &paths.data_dir.join("outbox").join("import"),
+            &legacy_link,
+            RetryPolicy {
+                shutdown_grace: IMPORT_UPLOAD_LIMIT,
+                ..RetryPolicy::default()
+            },
+        );
+        let count = plan.sessions.len();
+        for mut entry in plan.sessions {
+            let only = DateTime::parse_from_rfc3339(&entry.session.start_time).ok().and_then(|start| {
+                let title = entry.session.title.as_deref().unwrap_or_default();
+                routing::route(&config.routes, &entry.session.app_name, title, start.with_timezone(&Local).naive_local())
+            });
+            entry.destinations = delivery.submit(entry.session.clone(), only);
+            if let Err(e) = history.record(&entry) {
+                eprintln!("Warning: could not record imported session: {}", e);
+            }
+        }
+        println!("Sending {} sessions...", count);
+        delivery.shutdown().await;
+    });
+    ExitCode::SUCCESS
+}
+
+// --- Browser ---
+
+fn run_browser(paths: &Paths, command: BrowserCommand) -> ExitCode {
+    match command {
+        BrowserCommand::Install { chrome_extension_ids } => {
+            let host_path = paths.exe_dir.join(if cfg!(windows) { "browser-host.exe" } else { "browser-host" });
+            if !host_path.exists() {
+                eprintln!("Error: {} is missing", host_path.display());
+                return ExitCode::from(EXIT_IO);
+            }
+            match native::install(&paths.data_dir, &host_path, &chrome_extension_ids) {
+                Ok(browsers) if browsers.is_empty() => println!("No browser to register with"),
+                Ok(browsers) => {
+                    let names: Vec<String> = browsers.iter().map(ToString::to_string).collect();
+                    println!("Registered {} for {}", native::HOST_NAME, names.join(", "));
+                }
+                Err(e) => {
+                    eprintln!("Error: could not register {}: {}", native::HOST_NAME, e);
+                    return ExitCode::from(EXIT_IO);
+                }
+            }
+            if chrome_extension_ids.is_empty() {
+                println!("Chrome, Chromium and Edge were skipped; pass --chrome-extension-id to include them");
+            }
+            ExitCode::SUCCESS
+        }
+        BrowserCommand::Uninstall => match native::uninstall(&paths.data_dir) {
+            Ok(()) => {
+                println!("Removed {}", native::HOST_NAME);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Error: could not remove {}: {}", native::HOST_NAME, e);
+                ExitCode::from(EXIT_IO)
+            }
+        },
+    }
+}
+
+// --- WakaTime ---
+
+fn run_wakatime(paths: &Paths) -> ExitCode {
+    let Some(info) = AgentInfo::load(&paths.data_dir) else {
+        eprintln!("Error: the tracker has not run yet; start it first");
+        return ExitCode::from(EXIT_IO);
+    };
+    println!("# Add to the [settings] section of .wakatime.cfg in your home folder");
+    println!("api_url = http://127.0.0.1:{}{}", info.port, WAKATIME_API_PATH);
+    println!("api_key = {}", info.token);
+    ExitCode::SUCCESS
+}
+
+// --- Timer ---
+
+fn run_timer(paths: &Paths, args: TimerArgs) -> ExitCode {
+    let command = match args {
+        TimerArgs::Start { project } if project.trim().is_empty() => {
+            eprintln!("Error: the project name is empty");
+            return ExitCode::from(EXIT_INVALID);
+        }
+        TimerArgs::Start { project } => TimerCommand::Start { project: project.trim().to_string() },
+        TimerArgs::Stop => TimerCommand::Stop,
+        TimerArgs::Status => {
+            let timer = TimerState::load(&paths.data_dir);
+            match &timer.active {
+                Some(active) => println!(
+                    "Timer running: {} since {}",
+                    active.project,
+                    active.started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                ),
+                None => println!("No timer running"),
+            }
+            if !timer.recent.is_empty() {
+                println!("Recent projects: {}", timer.recent.join(", "));
+            }
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    // The running tracker owns the timer and splits its session at the change; without it the
+    // file is changed for the next start
+    let sent = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(AgentClient::new(&paths.data_dir).send_timer(&command)).is_ok(),
+        Err(_) => false,
+    };
+    if !sent {
+        let mut timer = TimerState::load(&paths.data_dir);
+        match &command {
+            TimerCommand::Start { project } => {
+                timer.start(project, Utc::now());
+            }
+            TimerCommand::Stop => {
+                timer.stop();
+            }
+        }
+        if let Err(e) = timer.save(&paths.data_dir) {
+            eprintln!("Error: could not save the timer: {}", e);
+            return ExitCode::from(EXIT_IO);
+        }
+    }
+    match command {
+        TimerCommand::Start { project } => println!("Timer started: {}", project),
+        TimerCommand::Stop => println!("Timer stopped"),
+    }
+    ExitCode::SUCCESS
+}
+
+// --- Rules ---
+
+fn run_rules(config: &Config, args: RulesArgs) -> ExitCode {
+    let RulesArgs::Test { title, app, path } = args;
+    if config.project_rules.is_empty() {
+        println!("No project rules are configured");
+        return ExitCode::SUCCESS;
+    }
+    match Attributor::new(&config.project_rules).attribute(&app, &title, &path) {
+        Some(found) => {
+            println!("Rule {} matched", found.rule + 1);
+            println!("  project: {}", found.project);
+            if let Some(ticket) = found.ticket {
+                println!("  ticket:  {}", ticket);
+            }
+        }
+        None => println!("No rule matched"),
+    }
+    ExitCode::SUCCESS
+}
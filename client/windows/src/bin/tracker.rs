@@ -1,24 +1,53 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use active_win_pos_rs::get_active_window;
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf, sync::OnceLock, thread};
+use chrono::{DateTime, Local, Utc};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+use tick::{
+    agent::{self, AgentEvent, BrowserEvent, Heartbeat, TimerCommand},
+    attribution::Attributor,
+    category::{Categorizer, ServerCategories},
+    clock::{self, ClockOffset, TimeZoneWatch},
+    config::{self, Config, ConfigError, ConfigWatcher},
+    device::{self, DeviceIdentity, LegacyLink},
+    discovery::CapabilityCache,
+    history::{History, HistoryEntry},
+    native,
+    paths::Paths,
+    policy::Policy,
+    remote::{self, CategorySync, RemoteSettings, SettingsSync},
+    routing,
+    secrets::Secrets,
+    session::{EditorContext, SessionPayload},
+    sink::Delivery,
+    timer::TimerState,
+};
 use tokio::sync::mpsc;
 use tray_icon::{
-    menu::{IconMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Icon as MenuIcon},
+    menu::{IconMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu, Icon as MenuIcon},
     Icon as TrayIcon, TrayIconBuilder,
 };
 use windows::{
-    core::PCWSTR,
+    core::{w, HSTRING, PCWSTR},
     Win32::{
-        Foundation::{GetLastError, ERROR_ALREADY_EXISTS, HWND},
-        System::Threading::CreateMutexW,
+        Foundation::{GetLastError, ERROR_ALREADY_EXISTS, HWND, LPARAM, LRESULT, WPARAM},
+        System::{
+            SystemInformation::GetTickCount,
+            Threading::{CreateMutexW, GetCurrentThreadId},
+        },
         UI::{
             Accessibility::{SetWinEventHook, HWINEVENTHOOK},
+            Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO},
             WindowsAndMessaging::{
-                DispatchMessageW, GetMessageW, TranslateMessage, EVENT_SYSTEM_FOREGROUND, MSG,
-                WINEVENT_OUTOFCONTEXT,
+                CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, MessageBoxW, PostThreadMessageW,
+                RegisterClassW, TranslateMessage, EVENT_SYSTEM_FOREGROUND, MB_ICONERROR, MB_OK, MSG, WINDOW_EX_STYLE,
+                WINDOW_STYLE, WINEVENT_OUTOFCONTEXT, WM_APP, WM_SETTINGCHANGE, WM_TIMECHANGE, WNDCLASSW,
             },
         },
     },
@@ -26,40 +55,57 @@ use windows::{
 
 // --- Models ---
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Config {
-    pub server_url: String,
-    pub api_key: Option<String>,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server_url: "http://localhost:3000/api/log-session".to_string(),
-            api_key: None,
-        }
-    }
-}
-
-#[derive(Serialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct SessionPayload {
-    device_id: String,
-    device_platform: String,
-    app_name: String,
-    start_time: String, // ISO 8601
-    end_time: String,   // ISO 8601
-    time_zone: String,
-}
-
 #[derive(Debug, Clone)]
 enum AppEvent {
     FocusChange,
+    /// The OS clock or settings changed, possibly including the time zone.
+    TimeChange,
+    StartTimer(String),
+    StopTimer,
     Shutdown,
     TrayExit,
     TrayConfig,
 }
 
+#[derive(Debug, Clone)]
+enum TrayUpdate {
+    Status(String),
+    Clock(String),
+    /// Running timer's label, and the projects to offer.
+    Timer {
+        label: Option<String>,
+        recent: Vec<String>,
+    },
+}
+
+/// Sends updates to the tray thread, which owns the (non-`Send`) menu items.
+struct TrayHandle {
+    thread_id: u32,
+    tx: std::sync::mpsc::Sender<TrayUpdate>,
+}
+
+impl TrayHandle {
+    fn send(&self, update: TrayUpdate) {
+        let _ = self.tx.send(update);
+        // Wake the tray thread's message loop so it drains the channel
+        unsafe {
+            let _ = PostThreadMessageW(self.thread_id, WM_TRAY_UPDATE, WPARAM(0), LPARAM(0));
+        }
+    }
+}
+
+const WM_TRAY_UPDATE: u32 = WM_APP + 1;
+const STATUS_TRACKING: &str = "Status: Tracking";
+const STATUS_CONFIG_INVALID: &str = "⚠ Config invalid — open Configure...";
+/// Menu IDs of the timer items; a project's item is the prefix followed by its name.
+const TIMER_STOP_ID: &str = "timer-stop";
+const TIMER_START_PREFIX: &str = "timer-start:";
+/// Catches time zone changes the OS doesn't announce.
+const TIME_ZONE_POLL_SECS: u64 = 60;
+/// Editor heartbeats this far before the session started still belong to it, since the plugin
+/// can notice the focus change before the tracker does.
+const HEARTBEAT_GRACE_SECS: i64 = 5;
+
 // --- Globals ---
 
 static EVENT_CHANNEL: OnceLock<mpsc::UnboundedSender<AppEvent>> = OnceLock::new();
@@ -82,6 +128,60 @@ unsafe extern "system" fn hook_proc(
     }
 }
 
+unsafe extern "system" fn time_change_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if (msg == WM_TIMECHANGE || msg == WM_SETTINGCHANGE)
+        && let Some(tx) = EVENT_CHANNEL.get()
+    {
+        let _ = tx.send(AppEvent::TimeChange);
+    }
+    unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+}
+
+/// A hidden top-level window for the broadcasts sent when the clock, time zone or other
+/// settings change; message-only windows don't receive them.
+fn create_time_change_window() -> Option<HWND> {
+    unsafe {
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(time_change_proc),
+            lpszClassName: w!("TickTimeChangeWindow"),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return None;
+        }
+        CreateWindowExW(
+            WINDOW_EX_STYLE::default(),
+            w!("TickTimeChangeWindow"),
+            w!(""),
+            WINDOW_STYLE::default(),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            None,
+            None,
+        )
+        .ok()
+    }
+}
+
+fn idle_duration() -> Duration {
+    unsafe {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        if GetLastInputInfo(&mut info).as_bool() {
+            // Both are milliseconds since boot and wrap together every ~49 days
+            Duration::from_millis(GetTickCount().wrapping_sub(info.dwTime) as u64)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
 fn check_single_instance() {
     unsafe {
         let name_utf16: Vec<u16> = "TimeTrackerSingleInstanceLock\0"
@@ -95,123 +195,467 @@ fn check_single_instance() {
     }
 }
 
+/// The tracker has no console in release builds, so problems that stop it are shown in a box.
+fn show_error(message: &str) {
+    unsafe {
+        MessageBoxW(None, &HSTRING::from(message), w!("Tick Time Tracker"), MB_OK | MB_ICONERROR);
+    }
+}
+
 // --- Tracker Application ---
 
 struct TrackerApp {
     config: Config,
+    paths: Paths,
     device_id: String,
-    time_zone: String,
-    client: reqwest::Client,
+    legacy_link: LegacyLink,
+    default_device_name: String,
+    time_zone: TimeZoneWatch,
+    delivery: Delivery,
+    history: History,
     current_app: Option<String>,
+    /// Window title when the session started, for routing and project rules.
+    current_title: String,
+    /// Executable of the app in front, for project rules.
+    current_path: String,
+    /// Address of the browser tab the session was spent on.
+    current_url: Option<String>,
+    /// URL and page title of the tab in front, as reported by the browser extension.
+    browser_tab: Option<(String, String)>,
+    /// Project, file and language from editor heartbeats during the session.
+    current_editor: Option<EditorContext>,
     start_time: Option<DateTime<Utc>>,
+    idle_since: Option<DateTime<Utc>>,
+    /// Last comparison with the server's clock.
+    clock: Option<ClockOffset>,
+    /// Project timer whose project tags the sessions.
+    timer: TimerState,
+    /// The config's project rules, compiled.
+    attributor: Attributor,
+    /// Categories synced from the server while `sync_categories` is on.
+    server_categories: Option<ServerCategories>,
+    /// The config's category rules with the server's categories, for every session.
+    categorizer: Categorizer,
 }
 
 impl TrackerApp {
-    fn new(config: Config) -> Self {
-        let device_id = format!(
-            "{}-{}",
-            whoami::hostname().expect("Failed to get hostname"),
-            whoami::username().expect("Failed to get username")
-        );
-        let time_zone = iana_time_zone::get_timezone().unwrap_or_else(|_| "UTC".to_string());
-        let client = reqwest::Client::new();
+    fn new(config: Config, paths: Paths, device: DeviceIdentity) -> Self {
+        let time_zone = TimeZoneWatch::new(iana_time_zone::get_timezone());
+        println!("Time zone: {}", time_zone.current());
+        let legacy_link = LegacyLink::new(&paths.data_dir, &device);
+        let delivery = Delivery::start(&config, &paths.data_dir, &legacy_link);
+        let history = History::new(&paths.data_dir);
+        let timer = TimerState::load(&paths.data_dir);
+        if let Some(project) = timer.project() {
+            println!("Timer running: {}", project);
+        }
+
+        let attributor = Attributor::new(&config.project_rules);
+        let server_categories = last_known_categories(&config, &paths);
+        let categorizer = Categorizer::new(&config.category_rules, server_categories.as_ref());
 
         Self {
             config,
-            device_id,
+            paths,
+            device_id: device.id,
+            legacy_link,
+            default_device_name: device::default_name(),
             time_zone,
-            client,
+            delivery,
+            history,
             current_app: None,
+            current_title: String::new(),
+            current_path: String::new(),
+            current_url: None,
+            browser_tab: None,
+            current_editor: None,
             start_time: None,
+            idle_since: None,
+            clock: None,
+            timer,
+            attributor,
+            server_categories,
+            categorizer,
         }
     }
 
     async fn handle_event(&mut self, event: AppEvent) -> bool {
         match event {
             AppEvent::TrayConfig => {
-                self.launch_configure();
-                return true; // Stop tracker to allow reconfiguration
+                // Keep tracking; saved changes are picked up by the config watcher
+                launch_configure(&self.paths);
             }
             AppEvent::Shutdown | AppEvent::TrayExit => {
                 println!("Shutting down tracker...");
-                self.flush_session(true).await;
+                self.flush_session();
+                self.delivery.shutdown().await;
                 return true; // Exit loop
             }
             AppEvent::FocusChange => {
                 self.check_focus().await;
             }
+            AppEvent::TimeChange => {
+                self.check_time_zone().await;
+            }
+            AppEvent::StartTimer(project) => {
+                self.start_timer(&project).await;
+            }
+            AppEvent::StopTimer => {
+                self.stop_timer().await;
+            }
         }
         false
     }
 
+    async fn start_timer(&mut self, project: &str) {
+        let mut timer = self.timer.clone();
+        if timer.start(project, Utc::now()) {
+            println!("Timer started: {}", project.trim());
+            self.set_timer(timer).await;
+        }
+    }
+
+    async fn stop_timer(&mut self) {
+        let mut timer = self.timer.clone();
+        if let Some(stopped) = timer.stop() {
+            println!("Timer stopped: {}", stopped.project);
+            self.set_timer(timer).await;
+        }
+    }
+
+    /// Ends the session in progress under the old timer, so only time after the change is tagged
+    /// with the new project.
+    async fn set_timer(&mut self, timer: TimerState) {
+        self.flush_session();
+        self.timer = timer;
+        if let Err(e) = self.timer.save(&self.paths.data_dir) {
+            println!("Failed to save timer: {}", e);
+        }
+        self.check_focus().await;
+    }
+
+    /// Applies a reloaded config in place, keeping the session in progress.
+    async fn apply_config(&mut self, config: Config) {
+        if config == self.config {
+            return;
+        }
+        if config.server_url != self.config.server_url {
+            println!("Endpoint changed to {}", config.server_url);
+        }
+        if config.sinks != self.config.sinks
+            || config.server_url != self.config.server_url
+            || config.api_key != self.config.api_key
+        {
+            println!("Delivery settings changed");
+            self.delivery.restart(&config, &self.paths.data_dir, &self.legacy_link);
+        }
+        if config.project_rules != self.config.project_rules {
+            self.attributor = Attributor::new(&config.project_rules);
+        }
+        if config.sync_categories != self.config.sync_categories || config.server_url != self.config.server_url {
+            // Categories belong to the server they came from
+            self.server_categories = last_known_categories(&config, &self.paths);
+            self.categorizer = Categorizer::new(&config.category_rules, self.server_categories.as_ref());
+        } else if config.category_rules != self.config.category_rules {
+            self.categorizer = Categorizer::new(&config.category_rules, self.server_categories.as_ref());
+        }
+        self.config = config;
+    }
+
+    fn set_server_categories(&mut self, categories: Option<ServerCategories>) {
+        if categories == self.server_categories {
+            return;
+        }
+        match &categories {
+            Some(categories) => println!(
+                "Categories from the server: {} apps, {} URL patterns",
+                categories.apps.len(),
+                categories.url_patterns.len()
+            ),
+            None => println!("No categories from the server"),
+        }
+        self.server_categories = categories;
+        self.categorizer = Categorizer::new(&self.config.category_rules, self.server_categories.as_ref());
+    }
+
     async fn check_focus(&mut self) {
+        // Focus can change without the user (notifications, apps stealing focus)
+        if self.idle_since.is_some() {
+            return;
+        }
         if let Ok(window) = get_active_window() {
             if self.current_app.as_ref() != Some(&window.app_name) {
-                self.flush_session(false).await;
-                
+                self.flush_session();
+
+                if self.config.is_excluded(&window.app_name) {
+                    println!("Switched to excluded app, not tracking");
+                    return;
+                }
+
                 let now = Utc::now();
                 println!("Switched to: {}", window.app_name);
                 self.current_app = Some(window.app_name);
+                self.current_title = window.title;
+                self.current_path = window.process_path.to_string_lossy().to_string();
                 self.start_time = Some(now);
+                self.apply_browser_tab();
+            }
+        }
+    }
+
+    /// Takes the URL and page title of a browser session from the extension's tab in front.
+    fn apply_browser_tab(&mut self) {
+        if let Some(app_name) = &self.current_app
+            && native::is_browser(app_name)
+            && let Some((url, title)) = &self.browser_tab
+        {
+            self.current_url = Some(url.clone());
+            if !title.is_empty() {
+                self.current_title = title.clone();
+            }
+        }
+    }
+
+    /// Splits the browser session in front when its tab moves to another site, so each session
+    /// carries the one URL it was spent on.
+    async fn handle_browser_event(&mut self, event: BrowserEvent) {
+        self.browser_tab = match event {
+            BrowserEvent::Tab { url, title } => Some((url, title)),
+            BrowserEvent::None => None,
+        };
+        if !self.current_app.as_deref().is_some_and(native::is_browser) {
+            return;
+        }
+        let site = self.browser_tab.as_ref().and_then(|(url, _)| native::site(url));
+        if site == self.current_url.as_deref().and_then(native::site) {
+            // Navigating within a site keeps the session, as in the extension
+            if let Some((url, _)) = &self.browser_tab {
+                self.current_url = Some(url.clone());
+            }
+            return;
+        }
+        self.flush_session();
+        self.check_focus().await;
+    }
+
+    /// Attaches an editor plugin's heartbeat to the session in progress. A heartbeat for another
+    /// project starts a new session, as another site does in a browser.
+    async fn handle_heartbeat(&mut self, heartbeat: Heartbeat) {
+        let (Some(start), Some(time)) = (self.start_time, heartbeat.time()) else {
+            return;
+        };
+        // Plugins resend heartbeats queued while offline; those belong to earlier sessions
+        if time < start - chrono::Duration::seconds(HEARTBEAT_GRACE_SECS) {
+            return;
+        }
+        // Editors in the background keep sending heartbeats; they describe another app's session
+        if !self.current_app.as_deref().is_some_and(|app| heartbeat.is_from(app)) {
+            return;
+        }
+        let context = heartbeat.editor_context();
+        let other_project = self
+            .current_editor
+            .as_ref()
+            .is_some_and(|editor| editor.project.is_some() && editor.project != context.project);
+        if other_project {
+            self.flush_session();
+            self.check_focus().await;
+            if self.current_app.is_none() {
+                return;
+            }
+        }
+        self.current_editor = Some(context);
+    }
+
+    /// Ends the session at a time zone change, so each session carries the zone it was recorded
+    /// in, and starts the next one in the new zone.
+    async fn check_time_zone(&mut self) {
+        // The session is flushed with the zone still in `self.time_zone`; the copy takes the new one
+        let mut time_zone = self.time_zone.clone();
+        if time_zone.check(iana_time_zone::get_timezone(), |_| self.flush_session()) {
+            self.time_zone = time_zone;
+            self.check_focus().await;
+        }
+    }
+
+    /// Records a clock comparison, warning when this machine's clock is off.
+    fn set_clock(&mut self, clock: ClockOffset) -> String {
+        let label = if clock.is_skewed() {
+            println!("Clock is {}", clock);
+            if self.config.correct_clock {
+                format!("⚠ Clock {} (corrected)", clock)
+            } else {
+                format!("⚠ Clock {}", clock)
+            }
+        } else {
+            format!("Clock {}", clock)
+        };
+        self.clock = Some(clock);
+        label
+    }
+
+    /// Ends the session at the moment input stopped once the idle threshold is reached, and
+    /// starts a new one when input resumes.
+    async fn check_idle(&mut self) {
+        let Some(threshold) = self.config.idle_threshold_secs else {
+            return;
+        };
+        let idle = idle_duration();
+
+        if idle >= Duration::from_secs(threshold) {
+            if self.idle_since.is_none() {
+                let since = Utc::now() - chrono::Duration::from_std(idle).unwrap_or_default();
+                println!("Idle for {}s, pausing", idle.as_secs());
+                self.flush_session_until(since);
+                self.idle_since = Some(since);
             }
+        } else if self.idle_since.take().is_some() {
+            println!("Activity resumed");
+            self.check_focus().await;
         }
     }
 
-    async fn flush_session(&mut self, is_shutting_down: bool) {
+    fn flush_session(&mut self) {
+        self.flush_session_until(Utc::now());
+    }
+
+    /// Hands the finished session to the sinks, which deliver and retry it in the background.
+    fn flush_session_until(&mut self, now: DateTime<Utc>) {
+        let url = self.current_url.take();
+        let editor = self.current_editor.take();
         if let (Some(app_name), Some(start)) = (self.current_app.take(), self.start_time.take()) {
-            let now = Utc::now();
             let duration = now.signed_duration_since(start);
 
             // Log if duration is meaningful
             if duration.num_seconds() > 0 {
                 println!("Logged: {} ({}s)", app_name, duration.num_seconds());
 
-                let payload = SessionPayload {
+                let (start, now) = match self.clock.filter(|_| self.config.correct_clock) {
+                    Some(clock) => (clock.correct(start), clock.correct(now)),
+                    None => (start, now),
+                };
+
+                let only = routing::route(
+                    &self.config.routes,
+                    &app_name,
+                    &self.current_title,
+                    start.with_timezone(&Local).naive_local(),
+                );
+                // A running timer outranks the rules; a ticket the rules found is kept either way
+                let attribution = self.attributor.attribute(&app_name, &self.current_title, &self.current_path);
+                let session = SessionPayload {
                     device_id: self.device_id.clone(),
+                    device_name: Some(
+                        self.config
+                            .device_name
+                            .clone()
+                            .unwrap_or_else(|| self.default_device_name.clone()),
+                    ),
+                    previous_device_id: None,
                     device_platform: "windows".to_string(),
                     app_name,
+                    title: Some(self.current_title.clone()).filter(|title| !title.is_empty()),
+                    url,
+                    editor,
+                    tag: self
+                        .timer
+                        .project()
+                        .map(str::to_string)
+                        .or_else(|| attribution.as_ref().map(|found| found.project.clone())),
+                    ticket: attribution.and_then(|found| found.ticket),
                     start_time: start.to_rfc3339(),
                     end_time: now.to_rfc3339(),
-                    time_zone: self.time_zone.clone(),
+                    time_zone: self.time_zone.current().to_string(),
                 };
 
-                let server_url = self.config.server_url.clone();
-                let api_key = self.config.api_key.clone();
-                let client = self.client.clone();
-
-                let req_future = async move {
-                    let mut req = client.post(&server_url);
-                    if let Some(key) = api_key {
-                        req = req.header("Authorization", format!("Bearer {}", key));
-                    }
-                    
-                    match req.json(&payload).send().await {
-                        Ok(res) => {
-                            if !res.status().is_success() {
-                                println!("Server returned error: {}", res.status());
-                            }
-                        }
-                        Err(e) => println!("Network error: {}", e),
-                    }
+                let destinations = self.delivery.submit(session.clone(), only);
+                if destinations.is_empty() {
+                    println!("Not sent: no destination for {}", session.app_name);
+                }
+                let category = self.categorizer.categorize(&session.app_name, session.url.as_deref());
+                let tags = session.tag.iter().chain(&session.ticket).cloned().collect();
+                let entry = HistoryEntry {
+                    session,
+                    category: Some(category.to_string()),
+                    tags,
+                    destinations,
                 };
-
-                if is_shutting_down {
-                    req_future.await; // Block on shutdown to ensure delivery
-                } else {
-                    tokio::spawn(req_future);
+                if let Err(e) = self.history.record(&entry) {
+                    println!("Failed to record session history: {}", e);
                 }
             }
         }
     }
+}
 
-    fn launch_configure(&self) {
-        let exe_path = std::env::current_exe().expect("Failed to get current executable path");
-        let exe_dir = exe_path.parent().expect("Failed to get executable directory");
-        let configure_exe = exe_dir.join("configure.exe");
+/// The categories last fetched from the configured server, if they are synced at all.
+fn last_known_categories(config: &Config, paths: &Paths) -> Option<ServerCategories> {
+    if !config.sync_categories {
+        return None;
+    }
+    CategorySync::new(&paths.data_dir).last_known(&config.server_url)
+}
 
-        if configure_exe.exists() {
-            println!("Launching configure.exe...");
-            let _ = std::process::Command::new(configure_exe).spawn();
+fn launch_configure(paths: &Paths) {
+    let configure_exe = paths.exe_dir.join("configure.exe");
+
+    if configure_exe.exists() {
+        println!("Launching configure.exe...");
+        let _ = std::process::Command::new(configure_exe)
+            .args(paths.forward_args())
+            .spawn();
+    }
+}
+
+fn timer_update(timer: &TimerState) -> TrayUpdate {
+    TrayUpdate::Timer {
+        label: timer.active.as_ref().map(|active| {
+            format!("⏹ Stop {} (since {})", active.project, active.started_at.with_timezone(&Local).format("%H:%M"))
+        }),
+        recent: timer.recent.clone(),
+    }
+}
+
+fn tray_tooltip(status: &str) -> String {
+    if status == STATUS_TRACKING {
+        "Tick Time Tracker".to_string()
+    } else {
+        format!("Tick Time Tracker ({})", status)
+    }
+}
+
+/// The user's config with the server's settings and then the machine policy laid over it.
+fn effective_config(mut config: Config, remote: Option<&RemoteSettings>, policy: &Policy) -> Config {
+    if let Some(remote) = remote {
+        remote.apply(&mut config);
+    }
+    policy.apply(&mut config);
+    config
+}
+
+fn print_remote_settings(remote: Option<&RemoteSettings>) {
+    match remote.map(RemoteSettings::managed_fields).filter(|fields| !fields.is_empty()) {
+        Some(fields) => {
+            let names: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+            println!("Settings from the server: {}", names.join(", "));
+        }
+        None => println!("No settings from the server"),
+    }
+}
+
+/// Loads the config, or returns `None` after preserving a broken file so configure can repair it.
+/// A missing file is passed through as an error: that is first run, not a broken install.
+fn load_config(config_path: &Path, secrets: &Secrets) -> Result<Option<Config>, io::Error> {
+    match Config::load_with_secrets(config_path, secrets) {
+        Ok(config) => Ok(Some(config)),
+        Err(ConfigError::Io(e)) => Err(e),
+        Err(e) => {
+            println!("{}", e);
+            match config::backup(config_path) {
+                Ok(backup_path) => println!("Original config saved to {}", backup_path.display()),
+                Err(e) => println!("Failed to back up config: {}", e),
+            }
+            Ok(None)
         }
     }
 }
@@ -222,23 +666,60 @@ impl TrackerApp {
 async fn main() {
     check_single_instance();
 
-    let exe_path = std::env::current_exe().expect("Failed to get current executable path");
-    let exe_dir = exe_path.parent().expect("Failed to get executable directory");
-    let config_path = exe_dir.join("config.json");
+    let paths = Paths::resolve().expect("Failed to resolve config and data directories");
+    let config_path = paths.config_file.clone();
+    let secrets = Secrets::new(&paths.data_dir);
+    let policy = match Policy::load_machine() {
+        Ok(policy) => policy,
+        Err(e) => {
+            // Tracking without the policy would ignore every setting the administrator locked
+            println!("{}", e);
+            show_error(&format!("{}\n\nTick won't track this computer until the policy is fixed.", e));
+            return;
+        }
+    };
+    let locked_fields = policy.locked_fields();
+    let managed_label = if locked_fields.is_empty() {
+        None
+    } else {
+        let names: Vec<String> = locked_fields.iter().map(|f| f.to_string()).collect();
+        println!("Settings controlled by policy: {}", names.join(", "));
+        Some(format!("🔒 Managed: {}", names.join(", ")))
+    };
 
-    // Load Configuration
-    let config = match fs::read_to_string(&config_path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| Config::default()),
+    // Load Configuration; `local_config` is the file as the user wrote it
+    let mut local_config = match load_config(&config_path, &secrets) {
+        Ok(config) => config,
         Err(_) => {
             println!("Config not found, launching setup...");
-            let setup_exe = exe_dir.join("configure.exe");
-            if setup_exe.exists() {
-                let _ = std::process::Command::new(setup_exe).spawn();
-            }
+            launch_configure(&paths);
             return;
         }
     };
 
+    if let Some(Err(e)) = local_config.as_ref().map(|config| policy.check(config)) {
+        println!("{}", e);
+        local_config = None;
+    }
+
+    // Keep running in the tray so the problem is visible; configure recovers what it can
+    if local_config.is_none() {
+        launch_configure(&paths);
+    }
+
+    // Until the server answers, the settings it sent last time apply
+    let settings_sync = SettingsSync::new(&paths.data_dir);
+    let mut remote_settings = local_config.as_ref().and_then(|config| {
+        settings_sync.last_known(&effective_config(config.clone(), None, &policy).server_url)
+    });
+    let config = local_config
+        .clone()
+        .map(|config| effective_config(config, remote_settings.as_ref(), &policy));
+
+    // Only an install that an earlier version ran on has a legacy ID to link
+    let device = DeviceIdentity::load_or_create(&paths.data_dir, paths.has_legacy_config())
+        .expect("Failed to store device identity");
+
     println!("Starting Tick Time Tracker...");
 
     let (tx, mut rx) = mpsc::unbounded_channel();
@@ -248,7 +729,9 @@ async fn main() {
 
     // Unified System Tray and Win32 Hook Thread
     let (tx_menu_ids, rx_menu_ids) = std::sync::mpsc::channel();
-    let exe_dir_clone = exe_dir.to_path_buf();
+    let (tx_tray, rx_tray) = std::sync::mpsc::channel::<TrayUpdate>();
+    let exe_dir_clone = paths.exe_dir.clone();
+    let initial_status = if config.is_some() { STATUS_TRACKING } else { STATUS_CONFIG_INVALID };
 
     thread::spawn(move || unsafe {
         let tray_menu = Menu::new();
@@ -260,16 +743,31 @@ async fn main() {
         // Menu Items
         let title_item = MenuItem::new("Tick Time Tracker", false, None);
         let version_item = MenuItem::new("v0.1.0", false, None);
+        let status_item = MenuItem::new(initial_status, false, None);
+        let clock_item = MenuItem::new("Clock: not checked yet", false, None);
+        let managed_item = managed_label.map(|label| MenuItem::new(label, false, None));
+        let timer_item = MenuItem::with_id(TIMER_STOP_ID, "No timer running", false, None);
+        let timer_menu = Submenu::new("Start timer", true);
+        let timer_hint = MenuItem::new("Start a new project with: tickctl timer start <name>", false, None);
+        let _ = timer_menu.append(&timer_hint);
+        let mut project_items: Vec<MenuItem> = Vec::new();
         let config_item = IconMenuItem::new("Configure...", true, Some(settings_icon), None);
         let exit_item = IconMenuItem::new("Exit", true, Some(exit_icon), None);
 
         let config_id = config_item.id().clone();
         let exit_id = exit_item.id().clone();
-        let _ = tx_menu_ids.send((config_id, exit_id));
 
         // Assemble Menu
         let _ = tray_menu.append(&title_item);
         let _ = tray_menu.append(&version_item);
+        let _ = tray_menu.append(&status_item);
+        let _ = tray_menu.append(&clock_item);
+        if let Some(managed_item) = &managed_item {
+            let _ = tray_menu.append(managed_item);
+        }
+        let _ = tray_menu.append(&PredefinedMenuItem::separator());
+        let _ = tray_menu.append(&timer_item);
+        let _ = tray_menu.append(&timer_menu);
         let _ = tray_menu.append(&PredefinedMenuItem::separator());
         let _ = tray_menu.append(&config_item);
         let _ = tray_menu.append(&exit_item);
@@ -284,11 +782,13 @@ async fn main() {
 
         let tray_icon = TrayIconBuilder::new()
             .with_menu(Box::new(tray_menu))
-            .with_tooltip("Tick Time Tracker")
+            .with_tooltip(tray_tooltip(initial_status))
             .with_icon(icon)
             .build()
             .expect("Failed to create tray icon");
 
+        let _ = tx_menu_ids.send((config_id, exit_id, GetCurrentThreadId()));
+
         // Win32 Hook
         let _hook = SetWinEventHook(
             EVENT_SYSTEM_FOREGROUND,
@@ -300,8 +800,38 @@ async fn main() {
             WINEVENT_OUTOFCONTEXT,
         );
 
+        let _time_change_window = create_time_change_window();
+
         let mut msg = MSG::default();
         while GetMessageW(&mut msg, None, 0, 0).0 > 0 {
+            if msg.message == WM_TRAY_UPDATE {
+                while let Ok(update) = rx_tray.try_recv() {
+                    match update {
+                        TrayUpdate::Status(text) => {
+                            status_item.set_text(&text);
+                            let _ = tray_icon.set_tooltip(Some(tray_tooltip(&text)));
+                        }
+                        TrayUpdate::Clock(text) => clock_item.set_text(&text),
+                        TrayUpdate::Timer { label, recent } => {
+                            timer_item.set_enabled(label.is_some());
+                            // `&` marks a mnemonic in Windows menus
+                            timer_item.set_text(label.as_deref().unwrap_or("No timer running").replace('&', "&&"));
+                            for item in project_items.drain(..) {
+                                let _ = timer_menu.remove(&item);
+                            }
+                            let _ = timer_menu.remove(&timer_hint);
+                            for project in recent {
+                                let id = MenuId::new(format!("{}{}", TIMER_START_PREFIX, project));
+                                let item = MenuItem::with_id(id, project.replace('&', "&&"), true, None);
+                                let _ = timer_menu.append(&item);
+                                project_items.push(item);
+                            }
+                            let _ = timer_menu.append(&timer_hint);
+                        }
+                    }
+                }
+                continue;
+            }
             let _ = TranslateMessage(&msg);
             DispatchMessageW(&msg);
         }
@@ -309,7 +839,11 @@ async fn main() {
         drop(tray_icon);
     });
 
-    let (config_id, exit_id) = rx_menu_ids.recv().expect("Failed to receive menu IDs");
+    let (config_id, exit_id, tray_thread_id) = rx_menu_ids.recv().expect("Failed to receive menu IDs");
+    let tray = TrayHandle {
+        thread_id: tray_thread_id,
+        tx: tx_tray,
+    };
 
     // Signal Listeners
     let tx_ctrlc = tx.clone();
@@ -328,19 +862,188 @@ async fn main() {
                 break;
             } else if event.id == config_id {
                 let _ = tx_menu.send(AppEvent::TrayConfig);
+            } else if event.id.0 == TIMER_STOP_ID {
+                let _ = tx_menu.send(AppEvent::StopTimer);
+            } else if let Some(project) = event.id.0.strip_prefix(TIMER_START_PREFIX) {
+                let _ = tx_menu.send(AppEvent::StartTimer(project.to_string()));
             }
         }
     });
 
     // Main App Loop
-    let mut app = TrackerApp::new(config);
-    
+    let mut app = config.map(|config| TrackerApp::new(config, paths.clone(), device.clone()));
+    let mut config_watcher = ConfigWatcher::new(&config_path);
+    let mut config_poll = tokio::time::interval(Duration::from_secs(2));
+    let mut idle_poll = tokio::time::interval(Duration::from_secs(5));
+    let mut time_zone_poll = tokio::time::interval(Duration::from_secs(TIME_ZONE_POLL_SECS));
+    let mut settings_poll = tokio::time::interval(Duration::from_secs(remote::REFRESH_MINUTES * 60));
+    let (settings_tx, mut settings_rx) = mpsc::unbounded_channel();
+    let category_sync = CategorySync::new(&paths.data_dir);
+    let (categories_tx, mut categories_rx) = mpsc::unbounded_channel();
+    let capability_cache = CapabilityCache::new(&paths.data_dir);
+    let mut clock_poll = tokio::time::interval(Duration::from_secs(clock::CHECK_MINUTES * 60));
+    let (clock_tx, mut clock_rx) = mpsc::unbounded_channel();
+
+    // The browser's native messaging host and editor plugins report through the local agent
+    let (agent_tx, mut agent_rx) = mpsc::unbounded_channel();
+    match agent::start(&paths.data_dir, agent_tx).await {
+        Ok(info) => println!("Agent listening on port {}", info.port),
+        Err(e) => println!("Could not start the local agent, browser tabs and editor plugins won't be tracked: {}", e),
+    }
+
     // Initial check
     let _ = tx.send(AppEvent::FocusChange);
+    if let Some(app) = app.as_ref() {
+        tray.send(timer_update(&app.timer));
+    }
 
-    while let Some(event) = rx.recv().await {
-        if app.handle_event(event).await {
-            break;
+    loop {
+        tokio::select! {
+            Some(event) = rx.recv() => {
+                if let Some(app) = app.as_mut() {
+                    let timer = app.timer.clone();
+                    if app.handle_event(event).await {
+                        break;
+                    }
+                    if app.timer != timer {
+                        tray.send(timer_update(&app.timer));
+                    }
+                    continue;
+                }
+
+                // Nothing is tracked until the config is repaired
+                match event {
+                    AppEvent::TrayConfig => launch_configure(&paths),
+                    AppEvent::Shutdown | AppEvent::TrayExit => break,
+                    AppEvent::FocusChange | AppEvent::TimeChange | AppEvent::StartTimer(_) | AppEvent::StopTimer => {}
+                }
+            }
+            Some(event) = agent_rx.recv() => {
+                if let Some(app) = app.as_mut() {
+                    let timer = app.timer.clone();
+                    match event {
+                        AgentEvent::Browser(event) => app.handle_browser_event(event).await,
+                        AgentEvent::Heartbeat(heartbeat) => app.handle_heartbeat(heartbeat).await,
+                        AgentEvent::Timer(TimerCommand::Start { project }) => app.start_timer(&project).await,
+                        AgentEvent::Timer(TimerCommand::Stop) => app.stop_timer().await,
+                    }
+                    if app.timer != timer {
+                        tray.send(timer_update(&app.timer));
+                    }
+                }
+            }
+            _ = idle_poll.tick(), if app.is_some() => {
+                if let Some(app) = app.as_mut() {
+                    app.check_idle().await;
+                }
+            }
+            _ = time_zone_poll.tick(), if app.is_some() => {
+                if let Some(app) = app.as_mut() {
+                    app.check_time_zone().await;
+                }
+            }
+            _ = settings_poll.tick(), if app.is_some() => {
+                // Fetched in the background so a slow server never delays session tracking
+                if let Some(app) = app.as_ref() {
+                    let sync = settings_sync.clone();
+                    let server_url = app.config.server_url.clone();
+                    let api_key = app.config.api_key.clone();
+                    let device_id = app.device_id.clone();
+                    let settings_tx = settings_tx.clone();
+                    tokio::spawn(async move {
+                        let result = sync.refresh(&server_url, api_key.as_deref(), &device_id).await;
+                        let _ = settings_tx.send((server_url, result));
+                    });
+                }
+                if let Some(app) = app.as_ref().filter(|app| app.config.sync_categories) {
+                    let sync = category_sync.clone();
+                    let server_url = app.config.server_url.clone();
+                    let api_key = app.config.api_key.clone();
+                    let categories_tx = categories_tx.clone();
+                    tokio::spawn(async move {
+                        let result = sync.refresh(&server_url, api_key.as_deref()).await;
+                        let _ = categories_tx.send((server_url, result));
+                    });
+                }
+            }
+            Some((server_url, result)) = settings_rx.recv() => match result {
+                // Answers from a server that is no longer configured are stale
+                _ if app.as_ref().is_none_or(|app| app.config.server_url != server_url) => {}
+                Ok(settings) => {
+                    if settings != remote_settings {
+                        print_remote_settings(settings.as_ref());
+                        remote_settings = settings;
+                        if let (Some(app), Some(config)) = (app.as_mut(), local_config.clone()) {
+                            app.apply_config(effective_config(config, remote_settings.as_ref(), &policy)).await;
+                        }
+                    }
+                }
+                Err(e) => println!("Could not fetch settings from the server, keeping the last ones: {}", e),
+            },
+            Some((server_url, result)) = categories_rx.recv() => match (app.as_mut(), result) {
+                (Some(app), Ok(categories)) if app.config.server_url == server_url && app.config.sync_categories => {
+                    app.set_server_categories(categories);
+                }
+                (_, Err(e)) => println!("Could not fetch categories from the server, keeping the last ones: {}", e),
+                _ => {}
+            },
+            _ = clock_poll.tick(), if app.is_some() => {
+                if let Some(app) = app.as_ref() {
+                    let cache = capability_cache.clone();
+                    let server_url = app.config.server_url.clone();
+                    let clock_tx = clock_tx.clone();
+                    tokio::spawn(async move {
+                        let result = clock::check(&server_url, &cache).await;
+                        let _ = clock_tx.send((server_url, result));
+                    });
+                }
+            }
+            Some((server_url, result)) = clock_rx.recv() => match (app.as_mut(), result) {
+                (Some(app), Ok(clock)) if app.config.server_url == server_url => {
+                    tray.send(TrayUpdate::Clock(app.set_clock(clock)));
+                }
+                (_, Err(e)) => println!("Could not compare the clock with the server: {}", e),
+                _ => {}
+            },
+            _ = config_poll.tick() => match config_watcher
+                .poll(&secrets)
+                .map(|result| result.and_then(|config| policy.check(&config).map(|()| config)))
+            {
+                None => {}
+                Some(Ok(config)) => {
+                    local_config = Some(config.clone());
+                    let server_url = effective_config(config.clone(), None, &policy).server_url;
+                    if app.as_ref().is_some_and(|app| config.sync_categories && !app.config.sync_categories) {
+                        settings_poll.reset_immediately();
+                    }
+                    if app.as_ref().is_none_or(|app| app.config.server_url != server_url) {
+                        // Settings belong to the server they came from; ask the new one right away
+                        remote_settings = settings_sync.last_known(&server_url);
+                        settings_poll.reset_immediately();
+                        if let Some(app) = app.as_mut() {
+                            app.clock = None;
+                        }
+                        clock_poll.reset_immediately();
+                    }
+                    let config = effective_config(config, remote_settings.as_ref(), &policy);
+                    match app.as_mut() {
+                        Some(app) => app.apply_config(config).await,
+                        None => {
+                            println!("Config repaired, starting tracking...");
+                            let repaired = TrackerApp::new(config, paths.clone(), device.clone());
+                            tray.send(timer_update(&repaired.timer));
+                            app = Some(repaired);
+                            let _ = tx.send(AppEvent::FocusChange);
+                        }
+                    }
+                    tray.send(TrayUpdate::Status(STATUS_TRACKING.to_string()));
+                }
+                Some(Err(e)) => {
+                    // Keep running on the last good settings until the file is fixed
+                    println!("Ignoring config change: {}", e);
+                    tray.send(TrayUpdate::Status(STATUS_CONFIG_INVALID.to_string()));
+                }
+            },
         }
     }
 
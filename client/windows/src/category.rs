@@ -0,0 +1,253 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// How a session counts towards productivity, the same categories the Tick server uses.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Productive,
+    Distracting,
+    Neutral,
+    #[default]
+    Uncategorized,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Productive,
+        Category::Distracting,
+        Category::Neutral,
+        Category::Uncategorized,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Productive => "productive",
+            Category::Distracting => "distracting",
+            Category::Neutral => "neutral",
+            Category::Uncategorized => "uncategorized",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown category `{}`", s))
+    }
+}
+
+// --- Rules ---
+
+/// Puts sessions of an app, or of pages in a browser, in a category. Every criterion that is
+/// set must match; rules are tried in order and the first match wins.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct CategoryRule {
+    pub category: Category,
+    /// App name, case-insensitive.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Page address where `*` stands for anything, case-insensitive, as in the server's URL
+    /// patterns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl CategoryRule {
+    /// Appends any problems with this rule to `problems`; `index` is its position in
+    /// `category_rules`.
+    pub fn validate(&self, index: usize, problems: &mut Vec<String>) {
+        if self.app.is_none() && self.url.is_none() {
+            problems.push(format!("Category rule {} needs an app or a URL", index + 1));
+        }
+        if self.app.as_ref().is_some_and(|app| app.trim().is_empty()) {
+            problems.push(format!("Category rule {}: app cannot be blank", index + 1));
+        }
+        if self.url.as_ref().is_some_and(|url| url.trim().is_empty()) {
+            problems.push(format!("Category rule {}: URL cannot be blank", index + 1));
+        }
+    }
+}
+
+/// The user's categories on the Tick server, from its `categories` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerCategories {
+    /// Apps the user has used, with the category the server keeps for them.
+    pub apps: Vec<AppCategory>,
+    /// The user's URL patterns, highest priority first.
+    pub url_patterns: Vec<UrlPattern>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AppCategory {
+    pub name: String,
+    pub category: Category,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UrlPattern {
+    pub pattern: String,
+    pub category: Category,
+}
+
+// --- Categorizing ---
+
+/// Picks the category of every session: the config's rules first, then the categories synced
+/// from the server, then the built-in ruleset.
+#[derive(Debug, Clone, Default)]
+pub struct Categorizer {
+    rules: Vec<CompiledRule>,
+    server_apps: HashMap<String, Category>,
+    server_patterns: Vec<(Regex, Category)>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    category: Category,
+    app: Option<String>,
+    url: Option<Regex>,
+}
+
+impl Categorizer {
+    /// Rules without an app or URL are left out; `Config::validate` reports them.
+    pub fn new(rules: &[CategoryRule], server: Option<&ServerCategories>) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.app.is_some() || rule.url.is_some())
+            .filter_map(|rule| {
+                Some(CompiledRule {
+                    category: rule.category,
+                    app: rule.app.as_ref().map(|app| app.trim().to_lowercase()),
+                    url: match &rule.url {
+                        Some(url) => Some(wildcard(url.trim())?),
+                        None => None,
+                    },
+                })
+            })
+            .collect();
+        let server = server.cloned().unwrap_or_default();
+        Self {
+            rules,
+            server_apps: server
+                .apps
+                .into_iter()
+                .map(|app| (app.name.to_lowercase(), app.category))
+                .collect(),
+            server_patterns: server
+                .url_patterns
+                .into_iter()
+                .filter_map(|pattern| Some((wildcard(&pattern.pattern)?, pattern.category)))
+                .collect(),
+        }
+    }
+
+    /// Categories the server left `uncategorized` don't hide the built-in ruleset.
+    pub fn categorize(&self, app_name: &str, url: Option<&str>) -> Category {
+        let app = app_name.to_lowercase();
+        let rule = self.rules.iter().find(|rule| {
+            rule.app.as_ref().is_none_or(|name| *name == app)
+                && rule.url.as_ref().is_none_or(|pattern| url.is_some_and(|url| pattern.is_match(url)))
+        });
+        if let Some(rule) = rule {
+            return rule.category;
+        }
+
+        let from_patterns = url.and_then(|url| {
+            self.server_patterns
+                .iter()
+                .find(|(pattern, _)| pattern.is_match(url))
+                .map(|(_, category)| *category)
+        });
+        from_patterns
+            .or_else(|| self.server_apps.get(&app).copied())
+            .filter(|category| *category != Category::Uncategorized)
+            .unwrap_or_else(|| suggest(app_name, url))
+    }
+}
+
+/// A server URL pattern as a regex: `*` matches anything and the rest is literal.
+fn wildcard(pattern: &str) -> Option<Regex> {
+    let parts: Vec<String> = pattern.split('*').map(regex::escape).collect();
+    RegexBuilder::new(&format!("^{}$", parts.join(".*")))
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+// --- Built-in ruleset ---
+
+// Kept in step with the server's `suggestCategory`, so sessions land where the dashboard puts
+// apps it hasn't seen before.
+const PRODUCTIVE: &[&str] = &[
+    "visual studio code", "vscode", "code", "vim", "neovim", "sublime",
+    "intellij", "pycharm", "webstorm", "android studio",
+    "github", "gitlab", "bitbucket", "stackoverflow",
+    "openai", "chatgpt", "claude", "gemini",
+    "notion", "obsidian", "evernote", "onenote",
+    "slack", "discord", "teams", "zoom", "meet.google",
+    "gmail", "outlook",
+    "figma", "canva", "sketch",
+    "docs.google", "office", "excel", "word", "powerpoint",
+    "trello", "asana", "jira", "linear",
+    "terminal", "cmd", "powershell", "iterm", "warp",
+];
+
+const DISTRACTING: &[&str] = &[
+    "facebook", "instagram", "twitter", "x.com", "tiktok",
+    "reddit", "pinterest", "snapchat",
+    "youtube", "netflix", "hulu", "twitch", "spotify",
+    "steam", "epic games", "battle.net", "league of legends", "valorant",
+    "whatsapp", "telegram", "wechat", "line",
+];
+
+const NEUTRAL: &[&str] = &[
+    "chrome", "firefox", "safari", "edge", "brave", "arc", "vivaldi", "opera", "msedge",
+    "explorer", "finder", "file explorer",
+    "settings", "preferences", "system preferences",
+];
+
+/// The built-in category for an app, or for a page when the app is a browser. Anything it
+/// doesn't know is `uncategorized`.
+pub fn suggest(app_name: &str, url: Option<&str>) -> Category {
+    let name = app_name.to_lowercase();
+    let url_lower = url.unwrap_or_default().to_lowercase();
+    let lists = [
+        (PRODUCTIVE, Category::Productive),
+        (DISTRACTING, Category::Distracting),
+        (NEUTRAL, Category::Neutral),
+    ];
+    for (keywords, category) in lists {
+        if keywords
+            .iter()
+            .any(|keyword| name.contains(keyword) || url_lower.contains(keyword))
+        {
+            return category;
+        }
+    }
+
+    if let Some(url) = url
+        && let Ok(url) = reqwest::Url::parse(url)
+        && let Some(domain) = url.host_str()
+    {
+        if ["edu", "coursera", "udemy", "edx"].iter().any(|part| domain.contains(part)) {
+            return Category::Productive;
+        }
+        if ["news", "bbc.co", "cnn.com"].iter().any(|part| domain.contains(part)) {
+            return Category::Neutral;
+        }
+    }
+    Category::Uncategorized
+}
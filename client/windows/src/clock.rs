@@ -0,0 +1,158 @@
+use crate::{
+    discovery::{CapabilityCache, ServerUrl},
+    http,
+};
+use chrono::{DateTime, Duration, TimeDelta, Utc};
+use reqwest::header::DATE;
+use serde::Deserialize;
+use std::fmt;
+
+/// How often the tracker compares its clock with the server's.
+pub const CHECK_MINUTES: u64 = 30;
+
+/// Offsets larger than this are reported as a wrong clock.
+pub const SKEW_WARNING_SECS: i64 = 60;
+
+/// Smaller offsets are left alone even when correcting, since they are within what a `Date`
+/// header can measure.
+const MIN_CORRECTION_SECS: i64 = 2;
+
+/// Body of the server's `time` endpoint.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct ServerTime {
+    /// Milliseconds since the Unix epoch.
+    pub now: i64,
+}
+
+/// How far the server's clock is ahead of this machine's, measured around one request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockOffset {
+    /// Add to a local time to get the server's; negative when the local clock is fast.
+    pub offset: Duration,
+    /// How far off the measurement itself may be: half the round trip, plus the one-second
+    /// resolution of a `Date` header.
+    pub uncertainty: Duration,
+}
+
+impl ClockOffset {
+    /// Offset from a server time read between `sent` and `received`, assuming it was taken
+    /// halfway through the round trip.
+    pub fn measure(server: DateTime<Utc>, sent: DateTime<Utc>, received: DateTime<Utc>) -> Self {
+        let half_trip = (received - sent).max(TimeDelta::zero()) / 2;
+        Self {
+            offset: server - (sent + half_trip),
+            uncertainty: half_trip,
+        }
+    }
+
+    /// Offset from an HTTP `Date` header (RFC 9110), which is truncated to the second.
+    pub fn from_date_header(date: &str, sent: DateTime<Utc>, received: DateTime<Utc>) -> Option<Self> {
+        let server = DateTime::parse_from_rfc2822(date.trim()).ok()?.with_timezone(&Utc);
+        let mut offset = Self::measure(server + Duration::milliseconds(500), sent, received);
+        offset.uncertainty += Duration::milliseconds(500);
+        Some(offset)
+    }
+
+    /// Whether the clocks are certainly further apart than `SKEW_WARNING_SECS`.
+    pub fn is_skewed(&self) -> bool {
+        self.offset.abs() - self.uncertainty > Duration::seconds(SKEW_WARNING_SECS)
+    }
+
+    /// `time` on the server's clock.
+    pub fn correct(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if self.offset.abs() < Duration::seconds(MIN_CORRECTION_SECS) {
+            time
+        } else {
+            time + self.offset
+        }
+    }
+}
+
+impl fmt::Display for ClockOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.offset.num_seconds().abs();
+        if secs < MIN_CORRECTION_SECS {
+            return f.write_str("in sync with the server");
+        }
+        let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            write!(f, "{}h {}m", hours, minutes)?;
+        } else if minutes > 0 {
+            write!(f, "{}m {}s", minutes, secs)?;
+        } else {
+            write!(f, "{}s", secs)?;
+        }
+        f.write_str(if self.offset > Duration::zero() { " behind the server" } else { " ahead of the server" })
+    }
+}
+
+/// Compares the local clock with the server's, using its `time` endpoint when discovery lists
+/// one and the `Date` header of a request to the base URL otherwise.
+pub async fn check(server_url: &str, cache: &CapabilityCache) -> Result<ClockOffset, String> {
+    let server = ServerUrl::parse(server_url)?;
+    let client = http::build_client(None);
+    let capabilities = cache.get_or_discover(&client, &server).await?;
+
+    let time_url = server.time_url(&capabilities);
+    let sent = Utc::now();
+    let response = client
+        .get(time_url.clone().unwrap_or_else(|| server.base.clone()))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let received = Utc::now();
+
+    let date = response
+        .headers()
+        .get(DATE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if time_url.is_some()
+        && response.status().is_success()
+        && let Ok(time) = response.json::<ServerTime>().await
+        && let Some(server_time) = DateTime::from_timestamp_millis(time.now)
+    {
+        return Ok(ClockOffset::measure(server_time, sent, received));
+    }
+    let date = date.ok_or("server did not send the time")?;
+    ClockOffset::from_date_header(&date, sent, received)
+        .ok_or_else(|| format!("server sent an unreadable Date header: {}", date))
+}
+
+// --- Time zone ---
+
+/// The system time zone sessions are recorded in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeZoneWatch {
+    current: String,
+}
+
+impl TimeZoneWatch {
+    /// Starts from the zone `lookup` found, or UTC when it failed.
+    pub fn new<E>(lookup: Result<String, E>) -> Self {
+        Self {
+            current: lookup.unwrap_or_else(|_| "UTC".to_string()),
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Takes a fresh `lookup` of the system zone. On a change, `flush` ends the session in
+    /// progress while the old zone is still current, so that session keeps the zone it was
+    /// recorded in and the next one starts in the new zone. A failed lookup is not a change.
+    /// Returns whether the zone changed.
+    pub fn check<E>(&mut self, lookup: Result<String, E>, flush: impl FnOnce(&str)) -> bool {
+        let Ok(time_zone) = lookup else {
+            return false;
+        };
+        if time_zone == self.current {
+            return false;
+        }
+        println!("Time zone changed from {} to {}", self.current, time_zone);
+        flush(&self.current);
+        self.current = time_zone;
+        true
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    attribution::ProjectRule,
+    category::CategoryRule,
+    routing::RouteRule,
+    secrets::{SecretError, SecretRef, Secrets},
+    sink::{SinkConfig, SinkKind},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+// --- Schema ---
+
+/// Schema version written by this build. Bump it together with a new entry in `MIGRATIONS`.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Base URL of the server. Full `.../api/log-session` endpoints from earlier versions still work.
+pub const DEFAULT_SERVER_URL: &str = "http://localhost:3000";
+
+pub const MIN_IDLE_THRESHOLD_SECS: u64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    #[default]
+    Dark,
+}
+
+impl Theme {
+    pub fn toggled(self) -> Self {
+        match self {
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::Light,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    pub server_url: String,
+    /// Only ever read from disk when migrating a file from before v2; the token lives in the
+    /// credential store referenced by `api_key_ref`.
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
+    pub api_key_ref: Option<SecretRef>,
+    pub theme: Theme,
+    /// Shown on the dashboard; defaults to the computer name.
+    pub device_name: Option<String>,
+    /// App names (case-insensitive) that are never recorded.
+    pub excluded_apps: Vec<String>,
+    /// App names (case-insensitive) that are recorded and delivered, but shown as `Private` in
+    /// exports.
+    pub redacted_apps: Vec<String>,
+    /// Ends the session after this long without keyboard or mouse input; `None` never does.
+    pub idle_threshold_secs: Option<u64>,
+    /// Moves session times onto the Tick server's clock when this machine's clock is off.
+    pub correct_clock: bool,
+    /// Where finished sessions are delivered, each with its own retry queue.
+    pub sinks: Vec<SinkConfig>,
+    /// Picks the sinks for each session; the first matching rule wins.
+    pub routes: Vec<RouteRule>,
+    /// Set by `Policy::apply` when the policy manages the server: Tick sinks then receive every
+    /// session, whatever the routes say.
+    #[serde(skip)]
+    pub managed_server: bool,
+    /// Assign projects and tickets to sessions from their app, title and path; the first
+    /// matching rule wins.
+    pub project_rules: Vec<ProjectRule>,
+    /// Put sessions in a productivity category ahead of the server's categories and the
+    /// built-in ones; the first matching rule wins.
+    pub category_rules: Vec<CategoryRule>,
+    /// Fetch the user's app categories from the Tick server for sessions recorded here.
+    pub sync_categories: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            server_url: DEFAULT_SERVER_URL.to_string(),
+            api_key: None,
+            api_key_ref: None,
+            theme: Theme::default(),
+            device_name: None,
+            excluded_apps: Vec::new(),
+            redacted_apps: Vec::new(),
+            idle_threshold_secs: None,
+            correct_clock: false,
+            sinks: vec![SinkConfig::tick()],
+            routes: Vec::new(),
+            managed_server: false,
+            project_rules: Vec::new(),
+            category_rules: Vec::new(),
+            sync_categories: false,
+        }
+    }
+}
+
+// --- Errors ---
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// `line` and `column` are 1-based; both are 0 when the error has no position in the file.
+    Parse { line: usize, column: usize, message: String },
+    UnsupportedVersion(u32),
+    Invalid(Vec<String>),
+    Secret(SecretError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Could not access config file: {}", e),
+            ConfigError::Parse { line: 0, message, .. } => write!(f, "Config file is not valid: {}", message),
+            ConfigError::Parse { line, column, message } => write!(
+                f,
+                "Config file is not valid at line {}, column {}: {}",
+                line, column, message
+            ),
+            ConfigError::UnsupportedVersion(v) => write!(
+                f,
+                "Config file version {} is newer than this client supports ({}); please update Tick",
+                v, CURRENT_VERSION
+            ),
+            ConfigError::Invalid(problems) => write!(f, "{}", problems.join("; ")),
+            ConfigError::Secret(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<SecretError> for ConfigError {
+    fn from(e: SecretError) -> Self {
+        ConfigError::Secret(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        // serde_json appends " at line X column Y" to its message; we report the position ourselves.
+        let mut message = e.to_string();
+        if e.line() > 0
+            && let Some(idx) = message.rfind(" at line ")
+        {
+            message.truncate(idx);
+        }
+        ConfigError::Parse { line: e.line(), column: e.column(), message }
+    }
+}
+
+// --- Migrations ---
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`.
+type Migration = fn(&mut Map<String, Value>);
+
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// v0 is the unversioned file written before the config was shared. The tracker never wrote
+/// `theme`, and configure wrote an empty string when it was unset.
+fn migrate_v0_to_v1(doc: &mut Map<String, Value>) {
+    let theme = match doc.get("theme").and_then(Value::as_str) {
+        Some(t) if t.eq_ignore_ascii_case("light") => "light",
+        _ => "dark",
+    };
+    doc.insert("theme".to_string(), Value::from(theme));
+}
+
+/// v2 moves the plaintext `api_key` into the credential store. That needs the store itself, so
+/// `load_with_secrets` does the move; the document keeps `api_key` until it is next saved.
+fn migrate_v1_to_v2(_doc: &mut Map<String, Value>) {}
+
+fn migrate(doc: &mut Map<String, Value>) -> Result<(), ConfigError> {
+    let mut version = match doc.get("version") {
+        None => 0,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| ConfigError::Invalid(vec!["`version` must be a whole number".to_string()]))?,
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+
+    while version < CURRENT_VERSION {
+        MIGRATIONS[version as usize](doc);
+        version += 1;
+        doc.insert("version".to_string(), Value::from(version));
+    }
+    Ok(())
+}
+
+// --- Loading & Saving ---
+
+impl Config {
+    /// Parses a config document of any supported version, upgrading and validating it.
+    pub fn from_json(content: &str) -> Result<Self, ConfigError> {
+        let mut doc = match serde_json::from_str::<Value>(content)? {
+            Value::Object(map) => map,
+            _ => return Err(ConfigError::Invalid(vec!["config must be a JSON object".to_string()])),
+        };
+        migrate(&mut doc)?;
+
+        let config: Config = serde_json::from_value(Value::Object(doc))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Best-effort salvage of a file that failed to load, so the user does not have to re-enter
+    /// values that are still readable. Works on syntactically broken files too.
+    pub fn recover(content: &str) -> Self {
+        let mut config = Config::default();
+        if let Some(server_url) = find_value(content, "server_url") {
+            config.server_url = server_url;
+        }
+        config.api_key = find_value::<String>(content, "api_key").filter(|key| !key.trim().is_empty());
+        config.api_key_ref = find_value(content, "api_key_ref");
+        if let Some(theme) = find_value::<String>(content, "theme") {
+            config.theme = if theme.eq_ignore_ascii_case("light") { Theme::Light } else { Theme::Dark };
+        }
+        config.device_name = find_value(content, "device_name");
+        config.excluded_apps = find_value(content, "excluded_apps").unwrap_or_default();
+        config.redacted_apps = find_value(content, "redacted_apps").unwrap_or_default();
+        config.idle_threshold_secs = find_value(content, "idle_threshold_secs");
+        config.correct_clock = find_value(content, "correct_clock").unwrap_or_default();
+        if let Some(sinks) = find_value(content, "sinks") {
+            config.sinks = sinks;
+        }
+        config.routes = find_value(content, "routes").unwrap_or_default();
+        config.project_rules = find_value(content, "project_rules").unwrap_or_default();
+        config.category_rules = find_value(content, "category_rules").unwrap_or_default();
+        config.sync_categories = find_value(content, "sync_categories").unwrap_or_default();
+        config
+    }
+
+    pub fn is_excluded(&self, app_name: &str) -> bool {
+        self.excluded_apps
+            .iter()
+            .any(|excluded| excluded.trim().eq_ignore_ascii_case(app_name))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Config serialization cannot fail")
+    }
+
+    /// Loads the file without resolving the access token; see `load_with_secrets`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Loads the file and its access tokens. Plaintext tokens, left by an older version or typed
+    /// into a Tick sink by hand, are moved into the credential store and the file is rewritten
+    /// without them.
+    pub fn load_with_secrets(path: &Path, secrets: &Secrets) -> Result<Self, ConfigError> {
+        let mut config = Self::load(path)?;
+        config.load_api_keys(secrets)?;
+        if config.has_plaintext_tokens() {
+            config.store_api_keys(secrets)?;
+            // The tokens are safe in the store either way; retried on the next load if this fails
+            if let Err(e) = config.save(path) {
+                println!("Failed to remove plaintext access token from config: {}", e);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Loads a file to copy settings from, such as the output of `configure --print`. Its token
+    /// references are resolved like `load_with_secrets` does, so saving the copy keeps the
+    /// tokens, but the file itself is left as it is.
+    pub fn load_template(path: &Path, secrets: &Secrets) -> Result<Self, ConfigError> {
+        let mut config = Self::load(path)?;
+        config.load_api_keys(secrets)?;
+        Ok(config)
+    }
+
+    /// Fills `api_key` from the credential store, for the main server and each Tick sink.
+    pub fn load_api_keys(&mut self, secrets: &Secrets) -> Result<(), ConfigError> {
+        load_token(secrets, &mut self.api_key, &self.api_key_ref)?;
+        for sink in &mut self.sinks {
+            if let SinkKind::Tick { api_key, api_key_ref, .. } = &mut sink.kind {
+                load_token(secrets, api_key, api_key_ref)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the access tokens into the credential store, then writes the file.
+    pub fn save_with_secrets(&mut self, path: &Path, secrets: &Secrets) -> Result<(), ConfigError> {
+        self.validate()?;
+        self.store_api_keys(secrets)?;
+        self.save(path)
+    }
+
+    fn has_plaintext_tokens(&self) -> bool {
+        let sink_tokens = self.sinks.iter().any(|sink| {
+            matches!(&sink.kind, SinkKind::Tick { api_key: Some(_), api_key_ref: None, .. })
+        });
+        (self.api_key.is_some() && self.api_key_ref.is_none()) || sink_tokens
+    }
+
+    /// Each token is filed under its owner, the main server or one sink, and the endpoint it
+    /// belongs to. Replaced tokens are removed once no entry refers to them any more.
+    fn store_api_keys(&mut self, secrets: &Secrets) -> Result<(), ConfigError> {
+        let mut replaced = Vec::new();
+        let account = format!("main:{}", self.server_url.trim());
+        store_token(secrets, &account, &self.api_key, &mut self.api_key_ref, &mut replaced)?;
+        for (index, sink) in self.sinks.iter_mut().enumerate() {
+            let owner = sink.queue_key(index);
+            if let SinkKind::Tick { server_url: Some(server_url), api_key, api_key_ref, .. } = &mut sink.kind {
+                let account = format!("sink:{}:{}", owner, server_url.trim());
+                store_token(secrets, &account, api_key, api_key_ref, &mut replaced)?;
+            }
+        }
+
+        let in_use = self.token_refs();
+        for old_ref in replaced.iter().filter(|old_ref| !in_use.contains(old_ref)) {
+            if let Err(e) = secrets.delete(old_ref) {
+                println!("Failed to remove old access token: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn token_refs(&self) -> Vec<&SecretRef> {
+        let sink_refs = self.sinks.iter().filter_map(|sink| match &sink.kind {
+            SinkKind::Tick { api_key_ref, .. } => api_key_ref.as_ref(),
+            _ => None,
+        });
+        self.api_key_ref.iter().chain(sink_refs).collect()
+    }
+
+    /// Writes via a temporary file so a running tracker never reads a half-written config. The
+    /// access token is never written; call `save_with_secrets` to persist it.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        self.validate()?;
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, self.to_json())?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Collects every problem at once so the user can fix them in a single pass.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = check_server_url(&self.server_url) {
+            problems.push(e);
+        }
+
+        if let Some(key) = &self.api_key {
+            if key.trim().is_empty() {
+                problems.push("Access token cannot be blank; leave it unset instead".to_string());
+            } else if key.chars().any(char::is_whitespace) {
+                problems.push("Access token cannot contain whitespace".to_string());
+            }
+        }
+
+        if self.device_name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+            problems.push("Device name cannot be blank; leave it unset instead".to_string());
+        }
+
+        if self.excluded_apps.iter().any(|app| app.trim().is_empty()) {
+            problems.push("Excluded app names cannot be blank".to_string());
+        }
+
+        if self.redacted_apps.iter().any(|app| app.trim().is_empty()) {
+            problems.push("Redacted app names cannot be blank".to_string());
+        }
+
+        if let Some(secs) = self.idle_threshold_secs
+            && secs < MIN_IDLE_THRESHOLD_SECS
+        {
+            problems.push(format!(
+                "Idle threshold must be at least {} seconds",
+                MIN_IDLE_THRESHOLD_SECS
+            ));
+        }
+
+        let mut sink_names: Vec<&str> = Vec::new();
+        for (index, sink) in self.sinks.iter().enumerate() {
+            sink.validate(index, &mut problems);
+            if let Some(name) = &sink.name {
+                if sink_names.contains(&name.as_str()) {
+                    problems.push(format!("More than one sink is named `{}`", name));
+                }
+                sink_names.push(name);
+            }
+        }
+        for (index, rule) in self.routes.iter().enumerate() {
+            rule.validate(index, &sink_names, &mut problems);
+        }
+        for (index, rule) in self.project_rules.iter().enumerate() {
+            rule.validate(index, &mut problems);
+        }
+        for (index, rule) in self.category_rules.iter().enumerate() {
+            rule.validate(index, &mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+/// Why `server_url` can't be used as the Tick server's endpoint, if it can't.
+pub fn check_server_url(server_url: &str) -> Result<(), String> {
+    let server_url = server_url.trim();
+    if server_url.is_empty() {
+        return Err("Endpoint cannot be empty".to_string());
+    }
+    match reqwest::Url::parse(server_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        Ok(url) => Err(format!("Endpoint must use http or https, not `{}`", url.scheme())),
+        Err(e) => Err(format!("Endpoint `{}` is not a valid URL: {}", server_url, e)),
+    }
+}
+
+fn load_token(secrets: &Secrets, token: &mut Option<String>, token_ref: &Option<SecretRef>) -> Result<(), ConfigError> {
+    if let Some(secret_ref) = token_ref {
+        *token = secrets.get(secret_ref)?;
+    }
+    Ok(())
+}
+
+/// Stores `token` under `account`; a reference it replaces is added to `replaced`.
+fn store_token(
+    secrets: &Secrets,
+    account: &str,
+    token: &Option<String>,
+    token_ref: &mut Option<SecretRef>,
+    replaced: &mut Vec<SecretRef>,
+) -> Result<(), ConfigError> {
+    let new_ref = match token {
+        Some(key) => Some(secrets.put(account, key)?),
+        None => None,
+    };
+    if let Some(old_ref) = token_ref.take()
+        && Some(&old_ref) != new_ref.as_ref()
+    {
+        replaced.push(old_ref);
+    }
+    *token_ref = new_ref;
+    Ok(())
+}
+
+// --- Watching ---
+
+/// Polls a config file for changes; cheap enough to call every couple of seconds.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// The file as it is now counts as seen; only later changes are reported.
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            last_modified: modified_time(path),
+        }
+    }
+
+    /// Returns the reloaded config if the file changed since the last call. A deleted file is
+    /// not reported, so the caller keeps its current settings.
+    pub fn poll(&mut self, secrets: &Secrets) -> Option<Result<Config, ConfigError>> {
+        let modified = modified_time(&self.path);
+        if modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+        modified?;
+        Some(Config::load_with_secrets(&self.path, secrets))
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Copies a config that failed to load to `<name>.bak` so that saving over it never loses data.
+pub fn backup(path: &Path) -> io::Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    let backup_path = path.with_file_name(name);
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Finds the value of the first `"key":` in possibly malformed JSON.
+fn find_value<T: DeserializeOwned>(content: &str, key: &str) -> Option<T> {
+    let needle = format!("\"{}\"", key);
+    let rest = &content[content.find(&needle)? + needle.len()..];
+    let rest = rest.trim_start().strip_prefix(':')?;
+    serde_json::Deserializer::from_str(rest)
+        .into_iter::<T>()
+        .next()?
+        .ok()
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+const DEVICE_FILE: &str = "device.json";
+
+/// Identity of this installation. It lives in the data directory rather than `config.json` so
+/// that copying a config to another machine does not make both report as the same device.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceIdentity {
+    pub id: String,
+    /// The `hostname-username` ID reported by earlier versions. Sent as `previousDeviceId` until
+    /// the server confirms it has moved that device's history to `id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legacy_id: Option<String>,
+}
+
+impl DeviceIdentity {
+    /// Reads the identity, generating one on first run. `upgrading` is set when an existing
+    /// install is picked up, so the ID it used to report is carried over to the server.
+    pub fn load_or_create(data_dir: &Path, upgrading: bool) -> io::Result<Self> {
+        let path = data_dir.join(DEVICE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(identity) => return Ok(identity),
+                Err(e) => println!("{} is not valid ({}), generating a new device ID", path.display(), e),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let identity = Self {
+            id: Uuid::new_v4().to_string(),
+            legacy_id: if upgrading { legacy_id() } else { None },
+        };
+        identity.save(data_dir)?;
+        println!("Generated device ID {}", identity.id);
+        Ok(identity)
+    }
+
+    pub fn save(&self, data_dir: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("Serialization failed");
+        fs::write(data_dir.join(DEVICE_FILE), json)
+    }
+
+    /// Stops sending the legacy ID once the server has linked it.
+    pub fn forget_legacy_id(data_dir: &Path) -> io::Result<()> {
+        let mut identity = Self::load_or_create(data_dir, false)?;
+        if identity.legacy_id.take().is_some() {
+            identity.save(data_dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// The legacy ID still waiting to be linked on the server, shared by everything that posts
+/// sessions so it is dropped everywhere as soon as one request succeeds.
+#[derive(Debug, Clone)]
+pub struct LegacyLink {
+    data_dir: PathBuf,
+    id: Arc<Mutex<Option<String>>>,
+}
+
+impl LegacyLink {
+    pub fn new(data_dir: &Path, identity: &DeviceIdentity) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            id: Arc::new(Mutex::new(identity.legacy_id.clone())),
+        }
+    }
+
+    pub fn pending(&self) -> Option<String> {
+        self.id.lock().unwrap().clone()
+    }
+
+    pub fn linked(&self) {
+        if self.id.lock().unwrap().take().is_some() {
+            println!("Server linked the previous device ID");
+            if let Err(e) = DeviceIdentity::forget_legacy_id(&self.data_dir) {
+                println!("Failed to update device identity: {}", e);
+            }
+        }
+    }
+}
+
+/// Name shown on the dashboard when the user has not chosen one.
+pub fn default_name() -> String {
+    whoami::hostname().unwrap_or_else(|_| "Windows PC".to_string())
+}
+
+/// The ID earlier versions derived from the machine and user names, if both can be read.
+fn legacy_id() -> Option<String> {
+    Some(format!("{}-{}", whoami::hostname().ok()?, whoami::username().ok()?))
+}
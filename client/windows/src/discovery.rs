@@ -0,0 +1,265 @@
+use crate::wire::Compression;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const CACHE_FILE: &str = "servers.json";
+
+/// Highest protocol version this build speaks. Version 1 is one plain session per
+/// `log-session` POST, which every server accepts; version 2 adds discovery and the optional
+/// features in `Features`.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Where the discovery document lives, relative to the server's base URL.
+pub const DISCOVERY_PATH: &str = "api/capabilities";
+
+/// Single-session endpoint of servers without discovery, relative to the base URL.
+pub const LOG_SESSION_PATH: &str = "api/log-session";
+
+/// How long a discovery result is trusted before the server is asked again.
+pub const CACHE_HOURS: i64 = 24;
+
+// --- Capabilities ---
+
+/// What a Tick server says it supports, from `GET api/capabilities`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub protocol_version: u32,
+    /// Oldest protocol the server still accepts.
+    #[serde(default = "default_min_protocol_version")]
+    pub min_protocol_version: u32,
+    #[serde(default)]
+    pub endpoints: Endpoints,
+    #[serde(default)]
+    pub features: Features,
+}
+
+fn default_min_protocol_version() -> u32 {
+    1
+}
+
+/// Endpoint paths, relative to the base URL. Missing ones fall back to the version 1 paths.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Endpoints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_session: Option<String>,
+    /// Takes a `wire::Batch`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_sessions: Option<String>,
+    /// Returns the `remote::RemoteSettings` for the user and device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<String>,
+    /// Returns the user's `category::ServerCategories`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub categories: Option<String>,
+    /// Returns the server's clock as a `clock::ServerTime`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+}
+
+/// Optional parts of the protocol. Anything this build doesn't know about is ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Features {
+    /// `Content-Encoding`s accepted on uploads.
+    #[serde(default)]
+    pub compression: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<BatchFeature>,
+    /// Uploads repeated with the same `Idempotency-Key` header are only stored once.
+    #[serde(default)]
+    pub idempotency: bool,
+    /// Sessions may carry the window title.
+    #[serde(default)]
+    pub titles: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFeature {
+    pub max_sessions: usize,
+}
+
+impl Capabilities {
+    /// A server without discovery: single, uncompressed sessions only.
+    pub fn legacy() -> Self {
+        Self {
+            protocol_version: 1,
+            min_protocol_version: 1,
+            endpoints: Endpoints::default(),
+            features: Features::default(),
+        }
+    }
+
+    /// The best compression both sides support.
+    pub fn compression(&self) -> Option<Compression> {
+        Compression::negotiate(&self.features.compression.join(","))
+    }
+}
+
+// --- Server URLs ---
+
+/// `server_url` as configured: a base URL such as `https://tick.example.com`, or the full
+/// `log-session` endpoint that earlier versions asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerUrl {
+    /// Ends with `/`, so endpoint paths can be joined to it.
+    pub base: Url,
+    /// Where single sessions go when the server has no discovery.
+    pub log_session: Url,
+}
+
+impl ServerUrl {
+    pub fn parse(server_url: &str) -> Result<Self, String> {
+        let url = Url::parse(server_url.trim()).map_err(|e| e.to_string())?;
+        let path = url.path().trim_end_matches('/').to_string();
+        let mut base = url.clone();
+        base.set_query(None);
+        base.set_fragment(None);
+        match path.strip_suffix("/api/log-session") {
+            Some(prefix) => {
+                base.set_path(&format!("{}/", prefix));
+                Ok(Self { base, log_session: url })
+            }
+            None => {
+                base.set_path(&format!("{}/", path));
+                let log_session = base.join(LOG_SESSION_PATH).map_err(|e| e.to_string())?;
+                Ok(Self { base, log_session })
+            }
+        }
+    }
+
+    /// Where single sessions go on a server with these capabilities.
+    pub fn log_session_url(&self, capabilities: &Capabilities) -> Url {
+        self.endpoint(capabilities.endpoints.log_session.as_deref())
+            .unwrap_or_else(|| self.log_session.clone())
+    }
+
+    /// Where batches go, if the server takes them.
+    pub fn log_sessions_url(&self, capabilities: &Capabilities) -> Option<Url> {
+        capabilities.features.batch.as_ref()?;
+        self.endpoint(capabilities.endpoints.log_sessions.as_deref())
+    }
+
+    /// Where the tracker fetches its settings, if the server manages them.
+    pub fn settings_url(&self, capabilities: &Capabilities) -> Option<Url> {
+        self.endpoint(capabilities.endpoints.settings.as_deref())
+    }
+
+    /// Where the tracker fetches the user's categories, if the server shares them.
+    pub fn categories_url(&self, capabilities: &Capabilities) -> Option<Url> {
+        self.endpoint(capabilities.endpoints.categories.as_deref())
+    }
+
+    /// Where the server tells the time, if it has an endpoint for it.
+    pub fn time_url(&self, capabilities: &Capabilities) -> Option<Url> {
+        self.endpoint(capabilities.endpoints.time.as_deref())
+    }
+
+    /// Resolves a path from the discovery document. Paths stay below the base URL even with a
+    /// leading `/`, so servers behind a path prefix work.
+    fn endpoint(&self, path: Option<&str>) -> Option<Url> {
+        self.base.join(path?.trim_start_matches('/')).ok()
+    }
+}
+
+/// Asks the server what it supports. `Ok(None)` means it has no discovery and only speaks
+/// version 1; an error means it could not be asked right now.
+pub async fn discover(client: &reqwest::Client, server: &ServerUrl) -> Result<Option<Capabilities>, String> {
+    let url = server.base.join(DISCOVERY_PATH).map_err(|e| e.to_string())?;
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT || status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(format!("server returned {}", status));
+    }
+    if !status.is_success() {
+        return Ok(None);
+    }
+    // Anything but a discovery document, such as a web page served for every path, means the
+    // server predates discovery
+    Ok(response.json::<Capabilities>().await.ok())
+}
+
+// --- Cache ---
+
+/// Discovery results by base URL, kept in the data folder so a restart doesn't ask again.
+#[derive(Debug, Clone)]
+pub struct CapabilityCache {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CacheEntry {
+    checked_at: DateTime<Utc>,
+    capabilities: Capabilities,
+}
+
+impl CapabilityCache {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(CACHE_FILE),
+        }
+    }
+
+    /// The result for `base` if it was fetched within `CACHE_HOURS`.
+    pub fn get(&self, base: &Url) -> Option<Capabilities> {
+        let entry = self.load().remove(base.as_str())?;
+        (Utc::now() - entry.checked_at < Duration::hours(CACHE_HOURS)).then_some(entry.capabilities)
+    }
+
+    /// The cached result for `server`, or else asks it and caches the answer. Servers without
+    /// discovery are cached as `Capabilities::legacy()`.
+    pub async fn get_or_discover(&self, client: &reqwest::Client, server: &ServerUrl) -> Result<Capabilities, String> {
+        if let Some(capabilities) = self.get(&server.base) {
+            return Ok(capabilities);
+        }
+        let capabilities = discover(client, server).await?.unwrap_or_else(Capabilities::legacy);
+        if let Err(e) = self.put(&server.base, &capabilities) {
+            println!("Failed to cache server capabilities: {}", e);
+        }
+        Ok(capabilities)
+    }
+
+    pub fn put(&self, base: &Url, capabilities: &Capabilities) -> io::Result<()> {
+        let mut entries = self.load();
+        entries.insert(
+            base.to_string(),
+            CacheEntry {
+                checked_at: Utc::now(),
+                capabilities: capabilities.clone(),
+            },
+        );
+        self.save(&entries)
+    }
+
+    pub fn forget(&self, base: &Url) -> io::Result<()> {
+        let mut entries = self.load();
+        if entries.remove(base.as_str()).is_some() {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> BTreeMap<String, CacheEntry> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, entries: &BTreeMap<String, CacheEntry>) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(entries).expect("Serialization failed"))?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
@@ -0,0 +1,441 @@
+use crate::history::HistoryEntry;
+use arrow_array::{
+    builder::{ListBuilder, StringBuilder},
+    ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Duration, Local, NaiveDate, SecondsFormat, Utc};
+use parquet::{arrow::ArrowWriter, basic::Compression, errors::ParquetError, file::properties::WriterProperties};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Shown instead of the name of an app listed in `redacted_apps`.
+pub const REDACTED_APP_NAME: &str = "Private";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    /// iCalendar, one event per session or block.
+    Ics,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Ics];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ics => "ics",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "ics" | "ical" | "icalendar" => Ok(ExportFormat::Ics),
+            _ => Err(format!("unknown export format `{}`; use csv, json or ics", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Joins sessions of the same app on the same device that are at most this far apart into one
+    /// block; `None` keeps every session.
+    pub merge_gap: Option<Duration>,
+    /// App names (case-insensitive) written as `REDACTED_APP_NAME`, without their window titles.
+    pub redacted_apps: Vec<String>,
+    /// Stamped on iCalendar events as `DTSTAMP`.
+    pub generated_at: DateTime<Utc>,
+}
+
+/// One exported row: a session, or a block of merged sessions.
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    app_name: String,
+    title: Option<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    device_id: String,
+    device_name: Option<String>,
+    time_zone: String,
+    category: Option<String>,
+    tags: Vec<String>,
+    destinations: Vec<String>,
+}
+
+/// UTC bounds of the local calendar days `from` through `to`, for `History::between`.
+pub fn local_days(from: NaiveDate, to: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let midnight = |date: NaiveDate| {
+        let naive = date.and_time(chrono::NaiveTime::MIN);
+        match naive.and_local_timezone(Local).earliest() {
+            Some(local) => local.with_timezone(&Utc),
+            None => naive.and_utc(),
+        }
+    };
+    (midnight(from), midnight(to.succ_opt().unwrap_or(to)))
+}
+
+/// Renders `entries` in the requested format. Entries with unreadable timestamps are skipped.
+pub fn export(entries: &[HistoryEntry], format: ExportFormat, options: &ExportOptions) -> String {
+    let blocks = blocks(entries, options);
+    match format {
+        ExportFormat::Csv => to_csv(&blocks),
+        ExportFormat::Json => to_json(&blocks),
+        ExportFormat::Ics => to_ics(&blocks, options.generated_at),
+    }
+}
+
+fn blocks(entries: &[HistoryEntry], options: &ExportOptions) -> Vec<Block> {
+    let mut blocks: Vec<Block> = entries
+        .iter()
+        .filter_map(|entry| {
+            let session = &entry.session;
+            let start = DateTime::parse_from_rfc3339(&session.start_time).ok()?.with_timezone(&Utc);
+            let end = DateTime::parse_from_rfc3339(&session.end_time).ok()?.with_timezone(&Utc);
+            let redacted = options
+                .redacted_apps
+                .iter()
+                .any(|app| app.trim().eq_ignore_ascii_case(&session.app_name));
+            Some(Block {
+                app_name: if redacted { REDACTED_APP_NAME.to_string() } else { session.app_name.clone() },
+                title: if redacted { None } else { session.title.clone() },
+                start,
+                end,
+                device_id: session.device_id.clone(),
+                device_name: session.device_name.clone(),
+                time_zone: session.time_zone.clone(),
+                category: entry.category.clone(),
+                tags: entry.tags.clone(),
+                destinations: entry.destinations.clone(),
+            })
+        })
+        .collect();
+    blocks.sort_by_key(|block| block.start);
+
+    let Some(gap) = options.merge_gap else {
+        return blocks;
+    };
+    // Merged after redaction, so neighbouring private apps cannot be told apart by their blocks
+    let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if let Some(last) = merged.last_mut()
+            && last.app_name == block.app_name
+            && last.device_id == block.device_id
+            && last.category == block.category
+            && block.start - last.end <= gap
+        {
+            last.end = last.end.max(block.end);
+            extend_unique(&mut last.tags, block.tags);
+            extend_unique(&mut last.destinations, block.destinations);
+            continue;
+        }
+        merged.push(block);
+    }
+    merged
+}
+
+fn extend_unique(list: &mut Vec<String>, items: Vec<String>) {
+    for item in items {
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// --- CSV ---
+
+const CSV_HEADER: &str =
+    "start,end,duration_secs,app_name,title,device_id,device_name,time_zone,category,tags,destinations";
+
+fn to_csv(blocks: &[Block]) -> String {
+    let mut out = format!("{}\r\n", CSV_HEADER);
+    for block in blocks {
+        let fields = [
+            timestamp(block.start),
+            timestamp(block.end),
+            (block.end - block.start).num_seconds().to_string(),
+            block.app_name.clone(),
+            block.title.clone().unwrap_or_default(),
+            block.device_id.clone(),
+            block.device_name.clone().unwrap_or_default(),
+            block.time_zone.clone(),
+            block.category.clone().unwrap_or_default(),
+            block.tags.join(";"),
+            block.destinations.join(";"),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&row.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Quotes a field when it contains a delimiter, quote or line break (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// --- JSON ---
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonRow<'a> {
+    start: String,
+    end: String,
+    duration_secs: i64,
+    app_name: &'a str,
+    title: Option<&'a str>,
+    device_id: &'a str,
+    device_name: Option<&'a str>,
+    time_zone: &'a str,
+    category: Option<&'a str>,
+    tags: &'a [String],
+    destinations: &'a [String],
+}
+
+fn to_json(blocks: &[Block]) -> String {
+    let rows: Vec<JsonRow> = blocks
+        .iter()
+        .map(|block| JsonRow {
+            start: timestamp(block.start),
+            end: timestamp(block.end),
+            duration_secs: (block.end - block.start).num_seconds(),
+            app_name: &block.app_name,
+            title: block.title.as_deref(),
+            device_id: &block.device_id,
+            device_name: block.device_name.as_deref(),
+            time_zone: &block.time_zone,
+            category: block.category.as_deref(),
+            tags: &block.tags,
+            destinations: &block.destinations,
+        })
+        .collect();
+    let mut out = serde_json::to_string_pretty(&rows).expect("Serialization failed");
+    out.push('\n');
+    out
+}
+
+// --- iCalendar ---
+
+const ICS_PRODID: &str = "-//Tick//Tick Time Tracker//EN";
+
+fn to_ics(blocks: &[Block], generated_at: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", ICS_PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for block in blocks {
+        let device = block.device_name.as_deref().unwrap_or(&block.device_id);
+        let mut description = match &block.title {
+            Some(title) => format!("Window: {}\n", title),
+            None => String::new(),
+        };
+        description.push_str(&format!("Device: {}", device));
+        if !block.destinations.is_empty() {
+            description.push_str(&format!("\nSent to: {}", block.destinations.join(", ")));
+        }
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}-{}@tick", ics_time(block.start), block.device_id),
+            format!("DTSTAMP:{}", ics_time(generated_at)),
+            format!("DTSTART:{}", ics_time(block.start)),
+            format!("DTEND:{}", ics_time(block.end)),
+            format!("SUMMARY:{}", ics_text(&block.app_name)),
+            format!("DESCRIPTION:{}", ics_text(&description)),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a TEXT value (RFC 5545 section 3.3.11).
+fn ics_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Splits a content line into lines of at most 75 octets, continued with a leading space.
+fn fold(line: &str) -> String {
+    const LIMIT: usize = 75;
+    let mut out = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LIMIT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out
+}
+
+// --- Parquet ---
+
+/// File written in each day's partition.
+pub const PARQUET_FILE_NAME: &str = "sessions.parquet";
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Parquet(ParquetError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "Could not write export: {}", e),
+            ExportError::Parquet(e) => write!(f, "Could not encode Parquet: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<ParquetError> for ExportError {
+    fn from(e: ParquetError) -> Self {
+        ExportError::Parquet(e)
+    }
+}
+
+impl From<ArrowError> for ExportError {
+    fn from(e: ArrowError) -> Self {
+        ExportError::Parquet(e.into())
+    }
+}
+
+/// Columns of every Parquet file; see the README for their meaning.
+pub fn parquet_schema() -> Schema {
+    let utc = || DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    let list = |name| Field::new_list(name, Field::new_list_field(DataType::Utf8, true), false);
+    Schema::new(vec![
+        Field::new("device_id", DataType::Utf8, false),
+        Field::new("device_name", DataType::Utf8, true),
+        Field::new("app_name", DataType::Utf8, false),
+        Field::new("title", DataType::Utf8, true),
+        Field::new("start", utc(), false),
+        Field::new("end", utc(), false),
+        Field::new("duration_secs", DataType::Int64, false),
+        Field::new("time_zone", DataType::Utf8, false),
+        Field::new("category", DataType::Utf8, true),
+        list("tags"),
+        list("destinations"),
+    ])
+}
+
+/// Writes one `date=YYYY-MM-DD/sessions.parquet` file per local day under `dir`, replacing
+/// earlier exports of those days, and returns the files written.
+pub fn write_parquet(entries: &[HistoryEntry], options: &ExportOptions, dir: &Path) -> Result<Vec<PathBuf>, ExportError> {
+    let mut days: BTreeMap<NaiveDate, Vec<Block>> = BTreeMap::new();
+    for block in blocks(entries, options) {
+        days.entry(block.start.with_timezone(&Local).date_naive()).or_default().push(block);
+    }
+
+    let schema = Arc::new(parquet_schema());
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut written = Vec::new();
+    for (day, blocks) in days {
+        let partition = dir.join(format!("date={}", day));
+        fs::create_dir_all(&partition)?;
+        let path = partition.join(PARQUET_FILE_NAME);
+
+        let batch = record_batch(&blocks, schema.clone())?;
+        let mut writer = ArrowWriter::try_new(fs::File::create(&path)?, schema.clone(), Some(properties.clone()))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn record_batch(blocks: &[Block], schema: SchemaRef) -> Result<RecordBatch, ArrowError> {
+    let strings = |f: fn(&Block) -> Option<&str>| -> ArrayRef { Arc::new(blocks.iter().map(f).collect::<StringArray>()) };
+    let times = |f: fn(&Block) -> DateTime<Utc>| -> ArrayRef {
+        Arc::new(TimestampMicrosecondArray::from_iter_values(blocks.iter().map(|b| f(b).timestamp_micros())).with_timezone("UTC"))
+    };
+    let lists = |f: fn(&Block) -> &[String]| -> ArrayRef {
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        for block in blocks {
+            for item in f(block) {
+                builder.values().append_value(item);
+            }
+            builder.append(true);
+        }
+        Arc::new(builder.finish())
+    };
+
+    RecordBatch::try_new(
+        schema,
+        vec![
+            strings(|b| Some(&b.device_id)),
+            strings(|b| b.device_name.as_deref()),
+            strings(|b| Some(&b.app_name)),
+            strings(|b| b.title.as_deref()),
+            times(|b| b.start),
+            times(|b| b.end),
+            Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| (b.end - b.start).num_seconds()))),
+            strings(|b| Some(&b.time_zone)),
+            strings(|b| b.category.as_deref()),
+            lists(|b| &b.tags),
+            lists(|b| &b.destinations),
+        ],
+    )
+}
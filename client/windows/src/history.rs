@@ -0,0 +1,112 @@
+use crate::session::SessionPayload;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A finished session as recorded on this machine.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub session: SessionPayload,
+    /// Productivity category, such as `productive`, for reports. Sessions imported from other
+    /// trackers may keep theirs, such as `Software Development`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Free-form labels for reports.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Sinks the session was queued for; empty when a routing rule discarded it.
+    pub destinations: Vec<String>,
+}
+
+/// Local record of every session, one JSON Lines file per month of session start.
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("history"),
+        }
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut line = serde_json::to_string(entry).expect("Serialization failed");
+        line.push('\n');
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(format!("{}.jsonl", month_of(&entry.session.start_time))))?
+            .write_all(line.as_bytes())
+    }
+
+    /// The most recent sessions, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<HistoryEntry> {
+        let mut entries = Vec::new();
+        for file in self.files().into_iter().rev() {
+            let mut month = read_entries(&file);
+            month.reverse();
+            entries.extend(month);
+            if entries.len() >= limit {
+                break;
+            }
+        }
+        entries.truncate(limit);
+        entries
+    }
+
+    /// Sessions that started in `[from, until)`, oldest first.
+    pub fn between(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<HistoryEntry> {
+        let first = from.format("%Y-%m").to_string();
+        let last = until.format("%Y-%m").to_string();
+        let mut entries: Vec<(DateTime<Utc>, HistoryEntry)> = self
+            .files()
+            .into_iter()
+            .filter(|file| {
+                let month = file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                first.as_str() <= month && month <= last.as_str()
+            })
+            .flat_map(|file| read_entries(&file))
+            .filter_map(|entry| {
+                let start = DateTime::parse_from_rfc3339(&entry.session.start_time).ok()?.with_timezone(&Utc);
+                (from <= start && start < until).then_some((start, entry))
+            })
+            .collect();
+        entries.sort_by_key(|(start, _)| *start);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Monthly files, oldest first.
+    fn files(&self) -> Vec<PathBuf> {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = dir
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+/// `YYYY-MM` of an RFC 3339 timestamp.
+fn month_of(timestamp: &str) -> &str {
+    timestamp.get(..7).unwrap_or("unknown")
+}
+
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
@@ -0,0 +1,19 @@
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::time::Duration;
+
+/// The access token is baked into the client's default headers, so rebuild the client when the
+/// token changes.
+pub fn build_client(api_key: Option<&str>) -> reqwest::Client {
+    let mut headers = HeaderMap::new();
+    if let Some(key) = api_key
+        && let Ok(mut value) = HeaderValue::from_str(&format!("Bearer {}", key))
+    {
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .connect_timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default()
+}
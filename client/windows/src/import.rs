@@ -0,0 +1,377 @@
+use crate::{config::Config, history::HistoryEntry, session::SessionPayload};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, io, path::Path, str::FromStr};
+
+/// Sessions covered at least this much by sessions already recorded are treated as duplicates.
+pub const DUPLICATE_OVERLAP: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    /// Bucket export (JSON) from aw-server; only `currentwindow` buckets are read.
+    ActivityWatch,
+    /// Interval CSV from RescueTime.
+    RescueTime,
+    /// Detailed report CSV from Toggl Track.
+    Toggl,
+}
+
+impl ImportSource {
+    /// Guesses the source from the file extension and the CSV header.
+    pub fn detect(path: &Path, content: &str) -> Option<Self> {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            return Some(ImportSource::ActivityWatch);
+        }
+        let header = content.trim_start_matches('\u{feff}').lines().next()?.to_lowercase();
+        if header.contains("time spent (seconds)") {
+            Some(ImportSource::RescueTime)
+        } else if header.contains("start date") && header.contains("end date") {
+            Some(ImportSource::Toggl)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ImportSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImportSource::ActivityWatch => "activitywatch",
+            ImportSource::RescueTime => "rescuetime",
+            ImportSource::Toggl => "toggl",
+        })
+    }
+}
+
+impl FromStr for ImportSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "activitywatch" | "aw" => Ok(ImportSource::ActivityWatch),
+            "rescuetime" => Ok(ImportSource::RescueTime),
+            "toggl" => Ok(ImportSource::Toggl),
+            _ => Err(format!("unknown import source `{}`; use activitywatch, rescuetime or toggl", s)),
+        }
+    }
+}
+
+// --- Errors ---
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    /// `row` is 1-based and counts the header.
+    Row { row: usize, message: String },
+    UnknownTimeZone(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "Could not read import file: {}", e),
+            ImportError::Json(e) => write!(f, "Not an ActivityWatch export: {}", e),
+            ImportError::Csv(e) => write!(f, "Could not read CSV: {}", e),
+            ImportError::Row { row, message } => write!(f, "Row {}: {}", row, message),
+            ImportError::UnknownTimeZone(tz) => write!(f, "`{}` is not an IANA time zone", tz),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(e: serde_json::Error) -> Self {
+        ImportError::Json(e)
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(e: csv::Error) -> Self {
+        ImportError::Csv(e)
+    }
+}
+
+// --- Parsing ---
+
+/// Who the imported sessions belong to.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub device_id: String,
+    pub device_name: Option<String>,
+    /// IANA time zone reported with every session, and used for sources with local timestamps.
+    pub time_zone: String,
+}
+
+/// Converts an export into history entries, oldest first. Entries without a duration are left
+/// out; `destinations` is empty until the sessions are delivered.
+pub fn parse(source: ImportSource, content: &str, options: &ImportOptions) -> Result<Vec<HistoryEntry>, ImportError> {
+    let tz: Tz = options
+        .time_zone
+        .parse()
+        .map_err(|_| ImportError::UnknownTimeZone(options.time_zone.clone()))?;
+    let mut entries = match source {
+        ImportSource::ActivityWatch => parse_activitywatch(content, options)?,
+        ImportSource::RescueTime => parse_rescuetime(content, options, tz)?,
+        ImportSource::Toggl => parse_toggl(content, options, tz)?,
+    };
+    entries.retain(|entry| bounds(entry).is_some_and(|(start, end)| end > start));
+    entries.sort_by_key(|entry| bounds(entry).map(|(start, _)| start));
+    Ok(entries)
+}
+
+fn entry(options: &ImportOptions, app_name: &str, title: Option<&str>, start: DateTime<Utc>, end: DateTime<Utc>) -> HistoryEntry {
+    HistoryEntry {
+        session: SessionPayload {
+            device_id: options.device_id.clone(),
+            device_name: options.device_name.clone(),
+            previous_device_id: None,
+            device_platform: "windows".to_string(),
+            app_name: app_name.to_string(),
+            title: title.map(str::to_string).filter(|title| !title.is_empty()),
+            url: None,
+            editor: None,
+            tag: None,
+            ticket: None,
+            start_time: start.to_rfc3339(),
+            end_time: end.to_rfc3339(),
+            time_zone: options.time_zone.clone(),
+        },
+        category: None,
+        tags: Vec::new(),
+        destinations: Vec::new(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AwExport {
+    Export { buckets: BTreeMap<String, AwBucket> },
+    Bucket(AwBucket),
+    Events(Vec<AwEvent>),
+}
+
+#[derive(Deserialize)]
+struct AwBucket {
+    #[serde(rename = "type", default)]
+    kind: String,
+    events: Vec<AwEvent>,
+}
+
+#[derive(Deserialize)]
+struct AwEvent {
+    timestamp: DateTime<Utc>,
+    /// Seconds.
+    duration: f64,
+    data: AwWindow,
+}
+
+#[derive(Deserialize)]
+struct AwWindow {
+    app: Option<String>,
+    title: Option<String>,
+}
+
+fn parse_activitywatch(content: &str, options: &ImportOptions) -> Result<Vec<HistoryEntry>, ImportError> {
+    let events = match serde_json::from_str(content)? {
+        AwExport::Export { buckets } => buckets
+            .into_values()
+            .filter(|bucket| bucket.kind == "currentwindow")
+            .flat_map(|bucket| bucket.events)
+            .collect(),
+        AwExport::Bucket(bucket) => bucket.events,
+        AwExport::Events(events) => events,
+    };
+    Ok(events
+        .into_iter()
+        .filter_map(|event| {
+            let app = event.data.app?;
+            let end = event.timestamp + Duration::milliseconds((event.duration * 1000.0) as i64);
+            Some(entry(options, &app, event.data.title.as_deref(), event.timestamp, end))
+        })
+        .collect())
+}
+
+/// Reads a CSV with a header, handing each row's fields to `row` by lowercase column name.
+fn read_csv(
+    content: &str,
+    mut row: impl FnMut(&dyn Fn(&str) -> Option<String>) -> Result<(), String>,
+) -> Result<(), ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let columns: Vec<String> = reader.headers()?.iter().map(|h| h.trim().to_lowercase()).collect();
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |name: &str| {
+            let column = columns.iter().position(|c| c == name)?;
+            record.get(column).map(|value| value.trim().to_string())
+        };
+        row(&field).map_err(|message| ImportError::Row { row: index + 2, message })?;
+    }
+    Ok(())
+}
+
+fn local_time(tz: Tz, naive: NaiveDateTime) -> Result<DateTime<Utc>, String> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in {}", naive, tz))
+}
+
+/// RescueTime reports time per activity in five-minute intervals. Activities sharing an
+/// interval are laid out one after another from its start.
+fn parse_rescuetime(content: &str, options: &ImportOptions, tz: Tz) -> Result<Vec<HistoryEntry>, ImportError> {
+    let mut entries = Vec::new();
+    let mut interval: Option<(NaiveDateTime, DateTime<Utc>)> = None;
+    read_csv(content, |field| {
+        let date = field("date").ok_or("missing Date")?;
+        let naive = NaiveDateTime::parse_from_str(&date, "%Y-%m-%dT%H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S"))
+            .map_err(|_| format!("`{}` is not a date and time", date))?;
+        let seconds: i64 = field("time spent (seconds)")
+            .and_then(|s| s.parse().ok())
+            .ok_or("missing Time Spent (seconds)")?;
+        let activity = field("activity").filter(|a| !a.is_empty()).ok_or("missing Activity")?;
+
+        let start = match interval {
+            Some((current, next_free)) if current == naive => next_free,
+            _ => local_time(tz, naive)?,
+        };
+        let end = start + Duration::seconds(seconds);
+        interval = Some((naive, end));
+
+        let mut imported = entry(options, &activity, None, start, end);
+        imported.category = field("category").filter(|c| !c.is_empty());
+        entries.push(imported);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Toggl entries become sessions of their project, titled with their description.
+fn parse_toggl(content: &str, options: &ImportOptions, tz: Tz) -> Result<Vec<HistoryEntry>, ImportError> {
+    let mut entries = Vec::new();
+    read_csv(content, |field| {
+        let at = |date: &str, time: &str| -> Result<DateTime<Utc>, String> {
+            let date = field(date).ok_or_else(|| format!("missing {}", date))?;
+            let time = field(time).ok_or_else(|| format!("missing {}", time))?;
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| format!("`{}` is not a date", date))?;
+            let time = NaiveTime::parse_from_str(&time, "%H:%M:%S").map_err(|_| format!("`{}` is not a time", time))?;
+            local_time(tz, date.and_time(time))
+        };
+        let start = at("start date", "start time")?;
+        let end = at("end date", "end time")?;
+        let project = field("project").filter(|p| !p.is_empty()).unwrap_or_else(|| "Toggl".to_string());
+
+        let mut imported = entry(options, &project, field("description").as_deref(), start, end);
+        imported.tags = field("tags")
+            .map(|tags| tags.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        entries.push(imported);
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+// --- Planning ---
+
+/// What an import would add, after removing what is already known.
+#[derive(Debug, Default)]
+pub struct ImportPlan {
+    pub sessions: Vec<HistoryEntry>,
+    /// Mostly covered by recorded sessions or by earlier sessions of the same import.
+    pub duplicates: usize,
+    /// Apps in `excluded_apps`.
+    pub excluded: usize,
+}
+
+/// Picks the parsed sessions worth importing. `existing` is the recorded history around the
+/// imported range; only sessions of the same device count.
+pub fn plan(parsed: Vec<HistoryEntry>, existing: &[HistoryEntry], config: &Config) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    let Some(device_id) = parsed.first().map(|entry| entry.session.device_id.clone()) else {
+        return plan;
+    };
+
+    let recorded = Coverage::from_sorted({
+        let mut spans: Vec<_> = existing
+            .iter()
+            .filter(|entry| entry.session.device_id == device_id)
+            .filter_map(bounds)
+            .collect();
+        spans.sort();
+        spans
+    });
+    let mut imported = Coverage::default();
+
+    let mut parsed = parsed;
+    parsed.sort_by_key(|entry| bounds(entry).map(|(start, _)| start));
+    for entry in parsed {
+        let Some((start, end)) = bounds(&entry) else {
+            continue;
+        };
+        if config.is_excluded(&entry.session.app_name) {
+            plan.excluded += 1;
+            continue;
+        }
+        let length = (end - start).num_milliseconds() as f64;
+        let covered = (recorded.covered(start, end) + imported.covered(start, end)).num_milliseconds() as f64;
+        if length <= 0.0 || covered >= length * DUPLICATE_OVERLAP {
+            plan.duplicates += 1;
+            continue;
+        }
+        imported.push(start, end);
+        plan.sessions.push(entry);
+    }
+    plan
+}
+
+fn bounds(entry: &HistoryEntry) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = DateTime::parse_from_rfc3339(&entry.session.start_time).ok()?;
+    let end = DateTime::parse_from_rfc3339(&entry.session.end_time).ok()?;
+    Some((start.with_timezone(&Utc), end.with_timezone(&Utc)))
+}
+
+/// Disjoint time spans in order.
+#[derive(Default)]
+struct Coverage {
+    spans: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Coverage {
+    fn from_sorted(sorted: Vec<(DateTime<Utc>, DateTime<Utc>)>) -> Self {
+        let mut coverage = Coverage::default();
+        for (start, end) in sorted {
+            coverage.push(start, end);
+        }
+        coverage
+    }
+
+    /// Adds a span that starts no earlier than every span already added.
+    fn push(&mut self, start: DateTime<Utc>, end: DateTime<Utc>) {
+        match self.spans.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => self.spans.push((start, end)),
+        }
+    }
+
+    /// How much of `[start, end)` is covered.
+    fn covered(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Duration {
+        let first = self.spans.partition_point(|span| span.1 <= start);
+        self.spans[first..]
+            .iter()
+            .take_while(|span| span.0 < end)
+            .map(|span| span.1.min(end) - span.0.max(start))
+            .sum()
+    }
+}
@@ -0,0 +1,22 @@
+pub mod agent;
+pub mod attribution;
+pub mod category;
+pub mod clock;
+pub mod config;
+pub mod device;
+pub mod discovery;
+pub mod export;
+pub mod history;
+pub mod http;
+pub mod import;
+pub mod native;
+pub mod paths;
+pub mod policy;
+pub mod probe;
+pub mod remote;
+pub mod routing;
+pub mod secrets;
+pub mod session;
+pub mod sink;
+pub mod timer;
+pub mod wire;
@@ -0,0 +1,218 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// Name the browser extension connects to.
+pub const HOST_NAME: &str = "com.tick.tracker";
+
+/// ID of the Firefox build of the extension, from `browser_specific_settings` in its manifest.
+pub const FIREFOX_EXTENSION_ID: &str = "tick-extension@thetick.vercel.app";
+
+/// Browsers limit what a host may send to 1 MB; messages from the extension are kept to the same.
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+/// Foreground app names that belong to a browser, as in the server's ingestion rules.
+const BROWSER_KEYWORDS: &[&str] = &["vivaldi", "chrome", "msedge", "edge", "firefox", "opera", "brave", "arc", "safari"];
+
+pub fn is_browser(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    BROWSER_KEYWORDS.iter().any(|keyword| app_name.contains(keyword))
+}
+
+/// The host a tab is on; a browser session lasts as long as the tab stays on the same one.
+pub fn site(url: &str) -> Option<String> {
+    reqwest::Url::parse(url).ok()?.host_str().map(|host| host.trim_start_matches("www.").to_string())
+}
+
+// --- Framing ---
+
+/// Reads one message: a 32-bit length in native byte order, then that much UTF-8 JSON. `None`
+/// at the end of input, which is how the browser says the extension disconnected.
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<Option<T>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let length = u32::from_ne_bytes(length) as usize;
+    if length > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes is too large", length)));
+    }
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let body = serde_json::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if body.len() > MAX_MESSAGE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message is too large"));
+    }
+    writer.write_all(&(body.len() as u32).to_ne_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
+
+// --- Installation ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Chrome,
+    Chromium,
+    Edge,
+    Firefox,
+}
+
+impl Browser {
+    pub const ALL: [Browser; 4] = [Browser::Chrome, Browser::Chromium, Browser::Edge, Browser::Firefox];
+
+    fn is_chromium_based(self) -> bool {
+        self != Browser::Firefox
+    }
+
+    /// Registry key under `HKEY_CURRENT_USER` whose default value points at the manifest.
+    #[cfg(windows)]
+    fn registry_key(self) -> String {
+        let vendor = match self {
+            Browser::Chrome => r"Google\Chrome",
+            Browser::Chromium => "Chromium",
+            Browser::Edge => r"Microsoft\Edge",
+            Browser::Firefox => "Mozilla",
+        };
+        format!(r"HKCU\Software\{}\NativeMessagingHosts\{}", vendor, HOST_NAME)
+    }
+
+    /// Folder the browser looks in for manifests of the current user.
+    #[cfg(not(windows))]
+    fn manifest_dir(self) -> Option<std::path::PathBuf> {
+        #[cfg(target_os = "macos")]
+        let (base, dir) = (
+            dirs::home_dir()?.join("Library/Application Support"),
+            match self {
+                Browser::Chrome => "Google/Chrome/NativeMessagingHosts",
+                Browser::Chromium => "Chromium/NativeMessagingHosts",
+                Browser::Edge => "Microsoft Edge/NativeMessagingHosts",
+                Browser::Firefox => "Mozilla/NativeMessagingHosts",
+            },
+        );
+        #[cfg(not(target_os = "macos"))]
+        let (base, dir) = match self {
+            Browser::Chrome => (dirs::config_dir()?, "google-chrome/NativeMessagingHosts"),
+            Browser::Chromium => (dirs::config_dir()?, "chromium/NativeMessagingHosts"),
+            Browser::Edge => (dirs::config_dir()?, "microsoft-edge/NativeMessagingHosts"),
+            Browser::Firefox => (dirs::home_dir()?, ".mozilla/native-messaging-hosts"),
+        };
+        Some(base.join(dir))
+    }
+}
+
+impl fmt::Display for Browser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Browser::Chrome => "Chrome",
+            Browser::Chromium => "Chromium",
+            Browser::Edge => "Edge",
+            Browser::Firefox => "Firefox",
+        })
+    }
+}
+
+/// The host manifest for `browser`. Chromium-based browsers only start the host for the
+/// listed extension IDs, so they get no manifest without any.
+pub fn manifest(browser: Browser, host_path: &Path, chrome_extension_ids: &[String]) -> Option<Value> {
+    let mut manifest = json!({
+        "name": HOST_NAME,
+        "description": "Tick time tracker",
+        "path": host_path,
+        "type": "stdio",
+    });
+    if browser.is_chromium_based() {
+        if chrome_extension_ids.is_empty() {
+            return None;
+        }
+        let origins: Vec<String> = chrome_extension_ids
+            .iter()
+            .map(|id| format!("chrome-extension://{}/", id.trim()))
+            .collect();
+        manifest["allowed_origins"] = json!(origins);
+    } else {
+        manifest["allowed_extensions"] = json!([FIREFOX_EXTENSION_ID]);
+    }
+    Some(manifest)
+}
+
+/// Registers the host with every supported browser and returns those it was registered with.
+/// On Windows the manifests are kept in `data_dir` and referenced from the registry; elsewhere
+/// they are written to each browser's manifest folder.
+pub fn install(data_dir: &Path, host_path: &Path, chrome_extension_ids: &[String]) -> io::Result<Vec<Browser>> {
+    let mut installed = Vec::new();
+    for browser in Browser::ALL {
+        let Some(manifest) = manifest(browser, host_path, chrome_extension_ids) else {
+            continue;
+        };
+        let content = serde_json::to_string_pretty(&manifest).expect("Serialization failed");
+
+        #[cfg(windows)]
+        {
+            let dir = data_dir.join("native-messaging");
+            fs::create_dir_all(&dir)?;
+            let path = dir.join(format!("{}.json", browser.to_string().to_lowercase()));
+            fs::write(&path, content)?;
+            reg(&["add", &browser.registry_key(), "/ve", "/t", "REG_SZ", "/d", &path.to_string_lossy(), "/f"])?;
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = data_dir;
+            // Browsers that were never started on this account have no profile folder to use
+            let Some(dir) = browser.manifest_dir().filter(|dir| dir.parent().is_some_and(Path::exists)) else {
+                continue;
+            };
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join(format!("{}.json", HOST_NAME)), content)?;
+        }
+        installed.push(browser);
+    }
+    Ok(installed)
+}
+
+/// Removes the registrations made by `install`.
+pub fn uninstall(data_dir: &Path) -> io::Result<()> {
+    for browser in Browser::ALL {
+        #[cfg(windows)]
+        {
+            // The key is missing when the browser was never registered
+            let _ = reg(&["delete", &browser.registry_key(), "/f"]);
+        }
+        #[cfg(not(windows))]
+        if let Some(dir) = browser.manifest_dir() {
+            remove_if_exists(&dir.join(format!("{}.json", HOST_NAME)))?;
+        }
+    }
+    match fs::remove_dir_all(data_dir.join("native-messaging")) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn reg(args: &[&str]) -> io::Result<()> {
+    let status = std::process::Command::new("reg").args(args).output()?.status;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("reg {} failed", args[0])))
+    }
+}
+
+#[cfg(not(windows))]
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Overrides the config file location, like `--config`.
+pub const CONFIG_ENV: &str = "TICK_CONFIG";
+
+/// When this file sits next to the executables, everything is kept in the install folder.
+pub const PORTABLE_MARKER: &str = "tick.portable";
+
+const CONFIG_FILE: &str = "config.json";
+
+#[cfg(target_os = "linux")]
+const APP_DIR: &str = "tick";
+#[cfg(not(target_os = "linux"))]
+const APP_DIR: &str = "Tick";
+
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub config_file: PathBuf,
+    pub data_dir: PathBuf,
+    pub exe_dir: PathBuf,
+    pub portable: bool,
+}
+
+impl Paths {
+    /// Resolves locations for this process from `--config`, `TICK_CONFIG`, the portable marker
+    /// and the per-user folders, creating directories and migrating a legacy config as needed.
+    pub fn resolve() -> io::Result<Self> {
+        Self::resolve_with(config_arg(std::env::args_os().skip(1)))
+    }
+
+    /// Like `resolve`, for callers that have already parsed `--config` themselves.
+    pub fn resolve_with(config_flag: Option<PathBuf>) -> io::Result<Self> {
+        let exe_path = std::env::current_exe()?;
+        let exe_dir = exe_path
+            .parent()
+            .ok_or_else(|| io::Error::other("executable has no parent directory"))?
+            .to_path_buf();
+
+        let config_override = config_flag
+            .or_else(|| std::env::var_os(CONFIG_ENV).filter(|v| !v.is_empty()).map(PathBuf::from));
+        Self::resolve_in(&exe_dir, config_override)
+    }
+
+    /// Like `resolve_with`, for executables in `exe_dir` and with the environment already read.
+    pub fn resolve_in(exe_dir: &Path, config_override: Option<PathBuf>) -> io::Result<Self> {
+        let paths = Self::locate(exe_dir, config_override.clone())?;
+        paths.create_dirs()?;
+        if config_override.is_none() && !paths.portable {
+            migrate_legacy_config(exe_dir, &paths.config_file)?;
+        }
+        Ok(paths)
+    }
+
+    fn locate(exe_dir: &Path, config_override: Option<PathBuf>) -> io::Result<Self> {
+        let portable = exe_dir.join(PORTABLE_MARKER).exists();
+        let (default_config_dir, data_dir) = if portable {
+            (exe_dir.to_path_buf(), exe_dir.join("data"))
+        } else {
+            let missing = || io::Error::other("could not determine the user profile folders");
+            // Roaming AppData / XDG_CONFIG_HOME for settings, Local AppData / XDG_DATA_HOME for data
+            let config_dir = dirs::config_dir().ok_or_else(missing)?.join(APP_DIR);
+            let data_dir = dirs::data_local_dir().ok_or_else(missing)?.join(APP_DIR);
+            (config_dir, data_dir)
+        };
+
+        Ok(Self {
+            config_file: config_override.unwrap_or_else(|| default_config_dir.join(CONFIG_FILE)),
+            data_dir,
+            exe_dir: exe_dir.to_path_buf(),
+            portable,
+        })
+    }
+
+    fn create_dirs(&self) -> io::Result<()> {
+        if let Some(config_dir) = self.config_file.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(config_dir)?;
+        }
+        fs::create_dir_all(&self.data_dir)
+    }
+
+    /// Whether a version from before per-user folders ran here: those kept `config.json` next to
+    /// the executables and reported the device by its legacy ID. Portable installs keep their
+    /// config there too, so this is only meaningful before the device identity is created.
+    pub fn has_legacy_config(&self) -> bool {
+        self.exe_dir.join(CONFIG_FILE).exists()
+    }
+
+    /// Arguments that make a sibling binary use the same config file as this process.
+    pub fn forward_args(&self) -> [OsString; 2] {
+        ["--config".into(), self.config_file.clone().into_os_string()]
+    }
+}
+
+/// Machine-wide policy written by administrators; users cannot change it.
+pub fn policy_file() -> PathBuf {
+    #[cfg(windows)]
+    let dir = std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+        .join(APP_DIR);
+    #[cfg(target_os = "macos")]
+    let dir = PathBuf::from("/Library/Application Support").join(APP_DIR);
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let dir = PathBuf::from("/etc").join(APP_DIR);
+
+    dir.join("policy.json")
+}
+
+/// Finds `--config <path>` or `--config=<path>` in the command line.
+pub fn config_arg(args: impl IntoIterator<Item = OsString>) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Earlier versions kept `config.json` next to the executables. Copy it once into the per-user
+/// location; the original is left alone because the install folder is usually read-only.
+pub fn migrate_legacy_config(exe_dir: &Path, config_file: &Path) -> io::Result<()> {
+    let legacy = exe_dir.join(CONFIG_FILE);
+    if config_file.exists() || !legacy.exists() {
+        return Ok(());
+    }
+    fs::copy(&legacy, config_file)?;
+    println!("Migrated {} to {}", legacy.display(), config_file.display());
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use crate::{
+    config::{self, Config, ConfigError, MIN_IDLE_THRESHOLD_SECS},
+    paths,
+    sink::SinkKind,
+};
+use serde::Deserialize;
+use std::{fmt, fs, io, path::Path};
+
+/// Settings enforced by the machine-wide policy file. Each value present takes precedence over
+/// the user's config and cannot be changed in configure.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub server_url: Option<String>,
+    /// Added to the user's exclusions; users can exclude more apps but not remove these.
+    pub excluded_apps: Vec<String>,
+    pub idle_threshold_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyField {
+    ServerUrl,
+    ExcludedApps,
+    IdleThreshold,
+}
+
+impl fmt::Display for PolicyField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PolicyField::ServerUrl => "Server endpoint",
+            PolicyField::ExcludedApps => "Excluded apps",
+            PolicyField::IdleThreshold => "Idle threshold",
+        })
+    }
+}
+
+impl Policy {
+    /// `Ok(None)` means there is no policy file.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Could not read policy file {}: {}", path.display(), e)),
+        };
+        let policy: Self = serde_json::from_str(&content)
+            .map_err(|e| format!("Policy file {} is not valid: {}", path.display(), e))?;
+        policy
+            .validate()
+            .map_err(|e| format!("Policy file {} is not valid: {}", path.display(), e))?;
+        Ok(Some(policy))
+    }
+
+    /// The policy at the machine-wide location. A policy that can't be read is an error rather
+    /// than no policy, so a broken file never lifts the locks it was meant to set.
+    pub fn load_machine() -> Result<Self, String> {
+        Self::load(&paths::policy_file()).map(Option::unwrap_or_default)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(server_url) = &self.server_url {
+            config::check_server_url(server_url).map_err(|e| format!("server_url: {}", e))?;
+        }
+        if self.excluded_apps.iter().any(|app| app.trim().is_empty()) {
+            return Err("excluded_apps cannot contain blank names".to_string());
+        }
+        if let Some(secs) = self.idle_threshold_secs
+            && secs < MIN_IDLE_THRESHOLD_SECS
+        {
+            return Err(format!("idle_threshold_secs must be at least {}", MIN_IDLE_THRESHOLD_SECS));
+        }
+        Ok(())
+    }
+
+    pub fn locked_fields(&self) -> Vec<PolicyField> {
+        let mut fields = Vec::new();
+        if self.server_url.is_some() {
+            fields.push(PolicyField::ServerUrl);
+        }
+        if !self.excluded_apps.is_empty() {
+            fields.push(PolicyField::ExcludedApps);
+        }
+        if self.idle_threshold_secs.is_some() {
+            fields.push(PolicyField::IdleThreshold);
+        }
+        fields
+    }
+
+    pub fn is_locked(&self, field: PolicyField) -> bool {
+        self.locked_fields().contains(&field)
+    }
+
+    /// Problems with a user config under this policy: with a managed server, sessions must
+    /// still reach it through a Tick sink.
+    pub fn check(&self, config: &Config) -> Result<(), ConfigError> {
+        if self.server_url.is_some() && !config.sinks.iter().any(|sink| matches!(sink.kind, SinkKind::Tick { .. })) {
+            return Err(ConfigError::Invalid(vec![
+                "The server endpoint is managed by policy, so `sinks` must keep a Tick sink".to_string(),
+            ]));
+        }
+        Ok(())
+    }
+
+    /// Overlays the policy on a user config. A managed server replaces the server of every Tick
+    /// sink too, along with the token the sink kept for its own server, and routes can't keep
+    /// sessions from it.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(server_url) = &self.server_url {
+            config.server_url = server_url.clone();
+            config.managed_server = true;
+            for sink in &mut config.sinks {
+                if let SinkKind::Tick { server_url, api_key, api_key_ref, .. } = &mut sink.kind
+                    && server_url.take().is_some()
+                {
+                    *api_key = None;
+                    *api_key_ref = None;
+                }
+            }
+        }
+        for app in &self.excluded_apps {
+            if !config.is_excluded(app) {
+                config.excluded_apps.push(app.clone());
+            }
+        }
+        if let Some(secs) = self.idle_threshold_secs {
+            config.idle_threshold_secs = Some(secs);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    discovery::{self, ServerUrl},
+    http::build_client,
+    session::{LogResponse, SessionPayload},
+};
+use chrono::Utc;
+use reqwest::{StatusCode, Url};
+use std::{error::Error as _, fmt};
+
+/// Result of checking an endpoint and token, from the first step that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    Connected,
+    InvalidUrl(String),
+    /// The host name did not resolve.
+    UnknownHost(String),
+    Unreachable(String),
+    Tls(String),
+    /// The server answered but has no log endpoint at this path.
+    WrongPath(StatusCode),
+    InvalidKey,
+    /// The server rejected our session format, or is not a Tick server at all.
+    VersionMismatch(String),
+    ServerError(StatusCode),
+}
+
+impl ProbeOutcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, ProbeOutcome::Connected)
+    }
+}
+
+impl fmt::Display for ProbeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeOutcome::Connected => write!(f, "Connected: endpoint and token are valid"),
+            ProbeOutcome::InvalidUrl(e) => write!(f, "Invalid endpoint URL: {}", e),
+            ProbeOutcome::UnknownHost(host) => write!(f, "Unreachable: could not resolve {}", host),
+            ProbeOutcome::Unreachable(e) => write!(f, "Unreachable: {}", e),
+            ProbeOutcome::Tls(e) => write!(f, "Secure connection failed: {}", e),
+            ProbeOutcome::WrongPath(status) => {
+                write!(f, "Wrong path: server answered {} (expected a Tick server's address)", status)
+            }
+            ProbeOutcome::InvalidKey => write!(f, "401: access token is invalid or revoked"),
+            ProbeOutcome::VersionMismatch(e) => write!(f, "Server version mismatch: {}", e),
+            ProbeOutcome::ServerError(status) => write!(f, "Server error: {}", status),
+        }
+    }
+}
+
+/// Checks the endpoint step by step: URL, DNS, TCP/TLS, then an authenticated request to the
+/// `log-session` endpoint the server names in discovery, or the usual one.
+///
+/// The probe posts a zero-length session. The server authenticates it before validating it and
+/// then rejects it with "startTime must be before endTime", so nothing is recorded.
+pub async fn test_connection(server_url: &str, api_key: Option<&str>) -> ProbeOutcome {
+    let url = match Url::parse(server_url.trim()) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        Ok(url) => return ProbeOutcome::InvalidUrl(format!("unsupported scheme `{}`", url.scheme())),
+        Err(e) => return ProbeOutcome::InvalidUrl(e.to_string()),
+    };
+    let Some(host) = url.host_str().map(str::to_string) else {
+        return ProbeOutcome::InvalidUrl("missing host".to_string());
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let resolved = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map(|mut addrs| addrs.next().is_some())
+        .unwrap_or(false);
+    if !resolved {
+        return ProbeOutcome::UnknownHost(host);
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let payload = SessionPayload {
+        device_id: "tick-connection-test".to_string(),
+        device_name: None,
+        previous_device_id: None,
+        device_platform: "windows".to_string(),
+        app_name: "Tick Connection Test".to_string(),
+        title: None,
+        url: None,
+        editor: None,
+        tag: None,
+        ticket: None,
+        start_time: now.clone(),
+        end_time: now,
+        time_zone: "UTC".to_string(),
+    };
+
+    let client = build_client(api_key);
+    let url = match ServerUrl::parse(url.as_str()) {
+        Ok(server) => match discovery::discover(&client, &server).await {
+            Ok(Some(capabilities)) => server.log_session_url(&capabilities),
+            _ => server.log_session,
+        },
+        Err(_) => url,
+    };
+    let response = match client.post(url).json(&payload).send().await {
+        Ok(response) => response,
+        Err(e) => return classify_send_error(&e),
+    };
+
+    let status = response.status();
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => return ProbeOutcome::InvalidKey,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => return ProbeOutcome::WrongPath(status),
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+            return ProbeOutcome::VersionMismatch(format!("server rejected the session format ({})", status));
+        }
+        _ => {}
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<LogResponse>(&body) {
+        // The expected rejection of the zero-length probe session
+        Ok(LogResponse { success: false, error: Some(error), .. }) if error.contains("startTime") => {
+            ProbeOutcome::Connected
+        }
+        Ok(LogResponse { success: true, .. }) => ProbeOutcome::Connected,
+        _ if status.is_server_error() => ProbeOutcome::ServerError(status),
+        Ok(LogResponse { error, .. }) => ProbeOutcome::VersionMismatch(
+            error.unwrap_or_else(|| "unexpected response to test session".to_string()),
+        ),
+        Err(_) => ProbeOutcome::VersionMismatch("response is not from a Tick server".to_string()),
+    }
+}
+
+fn classify_send_error(e: &reqwest::Error) -> ProbeOutcome {
+    // reqwest does not expose TLS failures as a kind, so look for them in the source chain
+    let mut source = e.source();
+    while let Some(inner) = source {
+        let text = inner.to_string().to_lowercase();
+        if text.contains("certificate") || text.contains("tls") || text.contains("handshake") {
+            return ProbeOutcome::Tls(inner.to_string());
+        }
+        source = inner.source();
+    }
+
+    if e.is_timeout() {
+        ProbeOutcome::Unreachable("connection timed out".to_string())
+    } else if e.is_connect() {
+        ProbeOutcome::Unreachable("connection refused".to_string())
+    } else {
+        ProbeOutcome::Unreachable(e.to_string())
+    }
+}
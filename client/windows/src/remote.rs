@@ -0,0 +1,250 @@
+use crate::{
+    category::ServerCategories,
+    config::{Config, MIN_IDLE_THRESHOLD_SECS},
+    discovery::{CapabilityCache, ServerUrl},
+    http,
+};
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{ETAG, IF_NONE_MATCH},
+    StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+const SETTINGS_FILE: &str = "remote-settings.json";
+const CATEGORIES_FILE: &str = "categories.json";
+
+/// How often the tracker asks the server whether its settings and categories changed.
+pub const REFRESH_MINUTES: u64 = 15;
+
+/// Settings managed on the Tick server for the user or this device, from its `settings`
+/// endpoint. They take precedence over the user's config but not over the machine policy.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RemoteSettings {
+    /// Added to the user's exclusions.
+    pub excluded_apps: Vec<String>,
+    pub idle_threshold_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteField {
+    ExcludedApps,
+    IdleThreshold,
+}
+
+impl fmt::Display for RemoteField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RemoteField::ExcludedApps => "Excluded apps",
+            RemoteField::IdleThreshold => "Idle threshold",
+        })
+    }
+}
+
+impl RemoteSettings {
+    pub fn managed_fields(&self) -> Vec<RemoteField> {
+        let mut fields = Vec::new();
+        if !self.excluded_apps.is_empty() {
+            fields.push(RemoteField::ExcludedApps);
+        }
+        if self.idle_threshold_secs.is_some() {
+            fields.push(RemoteField::IdleThreshold);
+        }
+        fields
+    }
+
+    pub fn is_managed(&self, field: RemoteField) -> bool {
+        self.managed_fields().contains(&field)
+    }
+
+    /// Overlays the settings on a user config. Apply the policy afterwards so it wins.
+    pub fn apply(&self, config: &mut Config) {
+        for app in &self.excluded_apps {
+            if !app.trim().is_empty() && !config.is_excluded(app) {
+                config.excluded_apps.push(app.clone());
+            }
+        }
+        if let Some(secs) = self.idle_threshold_secs {
+            config.idle_threshold_secs = Some(secs.max(MIN_IDLE_THRESHOLD_SECS));
+        }
+    }
+}
+
+// --- Sync ---
+
+/// Fetches the settings document and keeps the last one received in the data folder, so it
+/// still applies after a restart while the server can't be reached.
+#[derive(Debug, Clone)]
+pub struct SettingsSync {
+    store: DocumentStore,
+    cache: CapabilityCache,
+}
+
+impl SettingsSync {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            store: DocumentStore::new(data_dir.join(SETTINGS_FILE), "settings"),
+            cache: CapabilityCache::new(data_dir),
+        }
+    }
+
+    /// The settings last received from `server_url`, without asking it.
+    pub fn last_known(&self, server_url: &str) -> Option<RemoteSettings> {
+        self.store.last_known(server_url)
+    }
+
+    /// Asks the server for its settings for this device, sending the ETag of the stored ones
+    /// so an unchanged document isn't downloaded again. `Ok(None)` means the server doesn't
+    /// manage settings; on an error the last known settings still apply.
+    pub async fn refresh(
+        &self,
+        server_url: &str,
+        api_key: Option<&str>,
+        device_id: &str,
+    ) -> Result<Option<RemoteSettings>, String> {
+        let server = ServerUrl::parse(server_url)?;
+        let client = http::build_client(api_key);
+        let capabilities = self.cache.get_or_discover(&client, &server).await?;
+        let url = server.settings_url(&capabilities).map(|mut url| {
+            url.query_pairs_mut().append_pair("deviceId", device_id);
+            url
+        });
+        self.store.refresh(&client, &server, url).await
+    }
+}
+
+/// Fetches the user's app categories and URL patterns for the local categorizer, keeping the
+/// last ones received like `SettingsSync` does.
+#[derive(Debug, Clone)]
+pub struct CategorySync {
+    store: DocumentStore,
+    cache: CapabilityCache,
+}
+
+impl CategorySync {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            store: DocumentStore::new(data_dir.join(CATEGORIES_FILE), "categories"),
+            cache: CapabilityCache::new(data_dir),
+        }
+    }
+
+    /// The categories last received from `server_url`, without asking it.
+    pub fn last_known(&self, server_url: &str) -> Option<ServerCategories> {
+        self.store.last_known(server_url)
+    }
+
+    /// `Ok(None)` means the server doesn't share categories.
+    pub async fn refresh(&self, server_url: &str, api_key: Option<&str>) -> Result<Option<ServerCategories>, String> {
+        let server = ServerUrl::parse(server_url)?;
+        let client = http::build_client(api_key);
+        let capabilities = self.cache.get_or_discover(&client, &server).await?;
+        let url = server.categories_url(&capabilities);
+        self.store.refresh(&client, &server, url).await
+    }
+}
+
+/// One document from the server in a file of the data folder, with the ETag to revalidate it.
+#[derive(Debug, Clone)]
+struct DocumentStore {
+    path: PathBuf,
+    /// What the document holds, for errors.
+    kind: &'static str,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Stored<T> {
+    /// Base URL of the server the document came from.
+    server: String,
+    etag: Option<String>,
+    fetched_at: DateTime<Utc>,
+    /// Files written before categories were synced call it `settings`.
+    #[serde(alias = "settings")]
+    document: T,
+}
+
+impl DocumentStore {
+    fn new(path: PathBuf, kind: &'static str) -> Self {
+        Self { path, kind }
+    }
+
+    fn last_known<T: DeserializeOwned>(&self, server_url: &str) -> Option<T> {
+        let server = ServerUrl::parse(server_url).ok()?;
+        self.load(&server).map(|stored| stored.document)
+    }
+
+    /// Fetches `url`, or forgets the stored document when the server has no such endpoint.
+    async fn refresh<T: Serialize + DeserializeOwned>(
+        &self,
+        client: &reqwest::Client,
+        server: &ServerUrl,
+        url: Option<Url>,
+    ) -> Result<Option<T>, String> {
+        let Some(url) = url else {
+            self.clear().map_err(|e| e.to_string())?;
+            return Ok(None);
+        };
+
+        let stored = self.load::<T>(server);
+        let mut request = client.get(url);
+        if let Some(etag) = stored.as_ref().and_then(|stored| stored.etag.as_deref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => stored
+                .map(|stored| Some(stored.document))
+                .ok_or_else(|| format!("server returned 304 for {} it never sent", self.kind)),
+            StatusCode::NOT_FOUND => {
+                self.clear().map_err(|e| e.to_string())?;
+                Ok(None)
+            }
+            status if status.is_success() => {
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                let document: T = response.json().await.map_err(|e| e.to_string())?;
+                let stored = Stored {
+                    server: server.base.to_string(),
+                    etag,
+                    fetched_at: Utc::now(),
+                    document,
+                };
+                self.save(&stored).map_err(|e| e.to_string())?;
+                Ok(Some(stored.document))
+            }
+            status => Err(format!("server returned {}", status)),
+        }
+    }
+
+    /// Documents stored for another server don't apply to this one.
+    fn load<T: DeserializeOwned>(&self, server: &ServerUrl) -> Option<Stored<T>> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Stored<T>>(&content).ok())
+            .filter(|stored| stored.server == server.base.as_str())
+    }
+
+    fn save<T: Serialize>(&self, stored: &Stored<T>) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(stored).expect("Serialization failed"))?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
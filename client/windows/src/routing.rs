@@ -0,0 +1,93 @@
+use chrono::{NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Sends matching sessions to specific sinks. Every condition that is set must match; rules are
+/// tried in order and the first match wins. Sessions matching no rule go to every sink.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct RouteRule {
+    /// Names of the sinks that receive matching sessions; empty discards them.
+    pub to: Vec<String>,
+    /// App names, case-insensitive.
+    pub apps: Vec<String>,
+    /// Text the window title must contain when the session starts, case-insensitive.
+    pub titles: Vec<String>,
+    /// Weekdays such as `mon` or `friday`.
+    pub days: Vec<String>,
+    /// Local time range such as `09:00-17:30`. Ranges that end before they start wrap past midnight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hours: Option<String>,
+}
+
+impl RouteRule {
+    /// Whether a session of `app_name` whose window was titled `title` and that started at the
+    /// local time `start` matches this rule.
+    pub fn matches(&self, app_name: &str, title: &str, start: NaiveDateTime) -> bool {
+        if !self.apps.is_empty() && !self.apps.iter().any(|app| app.trim().eq_ignore_ascii_case(app_name)) {
+            return false;
+        }
+
+        let title = title.to_lowercase();
+        if !self.titles.is_empty() && !self.titles.iter().any(|t| title.contains(&t.to_lowercase())) {
+            return false;
+        }
+
+        if !self.days.is_empty() {
+            let weekday = chrono::Datelike::weekday(&start);
+            if !self.days.iter().any(|day| day.parse::<Weekday>().ok() == Some(weekday)) {
+                return false;
+            }
+        }
+
+        if let Some(hours) = &self.hours {
+            let Some((from, until)) = parse_hours(hours) else {
+                return false;
+            };
+            let time = start.time();
+            let within = if from <= until {
+                from <= time && time < until
+            } else {
+                time >= from || time < until
+            };
+            if !within {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Appends any problems with this rule to `problems`; `index` is its position in `routes`.
+    pub fn validate(&self, index: usize, sink_names: &[&str], problems: &mut Vec<String>) {
+        for name in &self.to {
+            if !sink_names.contains(&name.as_str()) {
+                problems.push(format!("Route {} sends to `{}`, but no sink has that name", index + 1, name));
+            }
+        }
+        for day in &self.days {
+            if day.parse::<Weekday>().is_err() {
+                problems.push(format!("Route {}: `{}` is not a weekday", index + 1, day));
+            }
+        }
+        if let Some(hours) = &self.hours
+            && parse_hours(hours).is_none()
+        {
+            problems.push(format!("Route {}: hours must look like `09:00-17:30`, not `{}`", index + 1, hours));
+        }
+    }
+}
+
+/// The sink names for a session, or `None` when no rule matches and it goes everywhere.
+pub fn route<'a>(rules: &'a [RouteRule], app_name: &str, title: &str, start: NaiveDateTime) -> Option<&'a [String]> {
+    rules
+        .iter()
+        .find(|rule| rule.matches(app_name, title, start))
+        .map(|rule| rule.to.as_slice())
+}
+
+fn parse_hours(hours: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (from, until) = hours.split_once('-')?;
+    let from = NaiveTime::parse_from_str(from.trim(), "%H:%M").ok()?;
+    let until = NaiveTime::parse_from_str(until.trim(), "%H:%M").ok()?;
+    Some((from, until))
+}
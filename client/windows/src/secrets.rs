@@ -0,0 +1,349 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const KEYRING_SERVICE: &str = "Tick";
+const NONCE_LEN: usize = 12;
+
+// --- Model ---
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// Windows Credential Manager, Secret Service on Linux, Keychain on macOS.
+    Keyring,
+    /// Encrypted file in the data directory, for machines without a usable keychain.
+    File,
+}
+
+/// What `config.json` stores in place of a secret.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    pub backend: SecretBackend,
+    pub account: String,
+}
+
+#[derive(Debug)]
+pub enum SecretError {
+    /// The backend cannot be used on this machine (no keychain daemon, locked store, ...).
+    Unavailable(String),
+    Io(io::Error),
+    Corrupt(String),
+    Backend(String),
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::Unavailable(e) => write!(f, "Credential store unavailable: {}", e),
+            SecretError::Io(e) => write!(f, "Could not access secrets file: {}", e),
+            SecretError::Corrupt(e) => write!(f, "Stored secret is corrupt: {}", e),
+            SecretError::Backend(e) => write!(f, "Credential store error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+impl From<io::Error> for SecretError {
+    fn from(e: io::Error) -> Self {
+        SecretError::Io(e)
+    }
+}
+
+pub trait SecretStore: Send + Sync {
+    fn backend(&self) -> SecretBackend;
+    fn get(&self, account: &str) -> Result<Option<String>, SecretError>;
+    fn set(&self, account: &str, secret: &str) -> Result<(), SecretError>;
+    /// Deleting a missing secret is not an error.
+    fn delete(&self, account: &str) -> Result<(), SecretError>;
+}
+
+// --- Platform Keychain ---
+
+pub struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(account: &str) -> Result<keyring::Entry, SecretError> {
+        keyring::Entry::new(KEYRING_SERVICE, account).map_err(keyring_error)
+    }
+}
+
+fn keyring_error(e: keyring::Error) -> SecretError {
+    match e {
+        keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_) => {
+            SecretError::Unavailable(e.to_string())
+        }
+        keyring::Error::BadEncoding(_) => SecretError::Corrupt(e.to_string()),
+        _ => SecretError::Backend(e.to_string()),
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::Keyring
+    }
+
+    fn get(&self, account: &str) -> Result<Option<String>, SecretError> {
+        match Self::entry(account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), SecretError> {
+        Self::entry(account)?.set_password(secret).map_err(keyring_error)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), SecretError> {
+        match Self::entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+// --- Encrypted File Fallback ---
+
+/// Secrets encrypted with a random key kept in a separate file next to them. This keeps tokens
+/// out of `config.json` (which gets copied around and attached to bug reports) but, unlike the
+/// keychain, offers no protection from someone who can read the user's data directory.
+pub struct EncryptedFileStore {
+    secrets_file: PathBuf,
+    key_file: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            secrets_file: data_dir.join("secrets.json"),
+            key_file: data_dir.join("secrets.key"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn cipher(&self, create: bool) -> Result<Option<ChaCha20Poly1305>, SecretError> {
+        match fs::read(&self.key_file) {
+            Ok(key) if key.len() == 32 => Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&key)))),
+            Ok(_) => Err(SecretError::Corrupt("key file has the wrong length".to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && create => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&self.key_file, &key)?;
+                Ok(Some(ChaCha20Poly1305::new(&key)))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn read_all(&self) -> Result<HashMap<String, String>, SecretError> {
+        match fs::read_to_string(&self.secrets_file) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| SecretError::Corrupt(e.to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_all(&self, secrets: &HashMap<String, String>) -> Result<(), SecretError> {
+        let json = serde_json::to_string_pretty(secrets).expect("Serialization failed");
+        write_private(&self.secrets_file, json.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn backend(&self) -> SecretBackend {
+        SecretBackend::File
+    }
+
+    fn get(&self, account: &str) -> Result<Option<String>, SecretError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(encoded) = self.read_all()?.remove(account) else {
+            return Ok(None);
+        };
+        let Some(cipher) = self.cipher(false)? else {
+            return Err(SecretError::Corrupt("key file is missing".to_string()));
+        };
+
+        let blob = BASE64
+            .decode(encoded)
+            .map_err(|e| SecretError::Corrupt(e.to_string()))?;
+        if blob.len() < NONCE_LEN {
+            return Err(SecretError::Corrupt("secret is truncated".to_string()));
+        }
+        let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| SecretError::Corrupt("secret could not be decrypted".to_string()))?;
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|e| SecretError::Corrupt(e.to_string()))
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), SecretError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let cipher = self.cipher(true)?.expect("key is created on demand");
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| SecretError::Backend("encryption failed".to_string()))?;
+
+        let mut blob = nonce.to_vec();
+        blob.extend_from_slice(&ciphertext);
+
+        let mut secrets = self.read_all()?;
+        secrets.insert(account.to_string(), BASE64.encode(blob));
+        self.write_all(&secrets)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), SecretError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut secrets = self.read_all()?;
+        if secrets.remove(account).is_some() {
+            self.write_all(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents)
+    }
+    #[cfg(not(unix))]
+    {
+        // Files under the user's AppData are already private to that user
+        fs::write(path, contents)
+    }
+}
+
+// --- In-Memory Double ---
+
+/// Stand-in for the platform stores in tests. An unavailable store fails every call the way a
+/// headless machine without a keychain daemon does.
+pub struct MemoryStore {
+    backend: SecretBackend,
+    secrets: Mutex<HashMap<String, String>>,
+    unavailable: bool,
+}
+
+impl MemoryStore {
+    pub fn new(backend: SecretBackend) -> Self {
+        Self {
+            backend,
+            secrets: Mutex::default(),
+            unavailable: false,
+        }
+    }
+
+    pub fn unavailable(backend: SecretBackend) -> Self {
+        Self {
+            unavailable: true,
+            ..Self::new(backend)
+        }
+    }
+
+    fn check(&self) -> Result<(), SecretError> {
+        if self.unavailable {
+            Err(SecretError::Unavailable("no credential store in this session".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl SecretStore for MemoryStore {
+    fn backend(&self) -> SecretBackend {
+        self.backend
+    }
+
+    fn get(&self, account: &str) -> Result<Option<String>, SecretError> {
+        self.check()?;
+        Ok(self.secrets.lock().unwrap().get(account).cloned())
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), SecretError> {
+        self.check()?;
+        self.secrets
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, account: &str) -> Result<(), SecretError> {
+        self.check()?;
+        self.secrets.lock().unwrap().remove(account);
+        Ok(())
+    }
+}
+
+// --- Resolver ---
+
+/// The keychain, with the encrypted file used when the keychain cannot be reached.
+pub struct Secrets {
+    preferred: Box<dyn SecretStore>,
+    fallback: Box<dyn SecretStore>,
+}
+
+impl Secrets {
+    pub fn new(data_dir: &Path) -> Self {
+        Self::with_stores(Box::new(KeyringStore), Box::new(EncryptedFileStore::new(data_dir)))
+    }
+
+    pub fn with_stores(preferred: Box<dyn SecretStore>, fallback: Box<dyn SecretStore>) -> Self {
+        Self { preferred, fallback }
+    }
+
+    fn store(&self, backend: SecretBackend) -> &dyn SecretStore {
+        if self.preferred.backend() == backend {
+            self.preferred.as_ref()
+        } else {
+            self.fallback.as_ref()
+        }
+    }
+
+    pub fn get(&self, secret_ref: &SecretRef) -> Result<Option<String>, SecretError> {
+        self.store(secret_ref.backend).get(&secret_ref.account)
+    }
+
+    pub fn put(&self, account: &str, secret: &str) -> Result<SecretRef, SecretError> {
+        let store = match self.preferred.set(account, secret) {
+            Ok(()) => self.preferred.as_ref(),
+            Err(SecretError::Unavailable(e)) => {
+                println!("{}; using encrypted file instead", e);
+                self.fallback.set(account, secret)?;
+                self.fallback.as_ref()
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(SecretRef {
+            backend: store.backend(),
+            account: account.to_string(),
+        })
+    }
+
+    pub fn delete(&self, secret_ref: &SecretRef) -> Result<(), SecretError> {
+        self.store(secret_ref.backend).delete(&secret_ref.account)
+    }
+}
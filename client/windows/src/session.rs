@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+/// Body of the Tick server's `log-session` endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPayload {
+    pub device_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    /// Set while the server may still know this device by the ID earlier versions reported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_device_id: Option<String>,
+    pub device_platform: String,
+    pub app_name: String,
+    /// Window title when the session started. Only handed to sinks that ask for titles, and
+    /// only sent to Tick servers that say they store them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Page shown in the browser, when the browser extension reports it through the native
+    /// messaging host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Project, file and language reported by an editor plugin during the session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<EditorContext>,
+    /// Project the session is billed to: the running project timer's, otherwise the one a
+    /// project rule found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Ticket a project rule found, such as a Jira key in the title.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    pub start_time: String, // ISO 8601
+    pub end_time: String,   // ISO 8601
+    pub time_zone: String,
+}
+
+/// What an editor plugin knows about a session that the window title doesn't tell.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorContext {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// File edited last. Like titles, only handed to sinks that ask for titles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// What the server answers to `log-session`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogResponse {
+    pub success: bool,
+    pub error: Option<String>,
+    /// The server has moved `previousDeviceId`'s history to this device; older servers omit it.
+    #[serde(default)]
+    pub device_linked: bool,
+}
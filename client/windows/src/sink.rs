@@ -0,0 +1,1196 @@
+use crate::{
+    config::Config,
+    device::{self, LegacyLink},
+    discovery::{self, Capabilities, CapabilityCache, ServerUrl, PROTOCOL_VERSION},
+    http::build_client,
+    secrets::SecretRef,
+    session::{LogResponse, SessionPayload},
+    wire::{Batch, BatchResponse, Compression, BATCH_URL_HEADER, MAX_BATCH_SESSIONS},
+};
+use reqwest::{
+    header::{HeaderMap, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, fs,
+    future::Future,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::Instant,
+};
+
+// --- Configuration ---
+
+/// One place sessions are delivered to, as written in `config.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SinkConfig {
+    /// Lets routing rules send sessions here. Also names the sink's retry queue, so renaming a
+    /// sink abandons sessions still queued for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub kind: SinkKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkKind {
+    /// A Tick server. Without `server_url` this is the main `server_url` and access token.
+    /// Optional protocol features are used once the server says it supports them.
+    Tick {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        server_url: Option<String>,
+        /// Moved into the credential store on load, like the main access token.
+        #[serde(default, skip_serializing)]
+        api_key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key_ref: Option<SecretRef>,
+        /// Compress uploads with gzip or zstd when the server accepts them.
+        #[serde(default = "default_true", skip_serializing_if = "is_true")]
+        compression: bool,
+        /// Send queued sessions together when the server has a batch endpoint.
+        #[serde(default = "default_true", skip_serializing_if = "is_true")]
+        batch: bool,
+        /// Send window titles to servers that store them.
+        #[serde(default, skip_serializing_if = "is_false")]
+        titles: bool,
+    },
+    /// Appends one JSON object per line. Relative paths are resolved against the data folder.
+    Jsonl { path: PathBuf },
+    /// Prints each session; useful for debugging.
+    Stdout,
+    /// POSTs each session to any URL. `body` is a template using `{{field}}` placeholders; without
+    /// it the session is sent as JSON.
+    Webhook {
+        url: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(default = "default_content_type")]
+        content_type: String,
+    },
+    /// An ActivityWatch server. Sessions are sent as `currentwindow` heartbeats, with titles.
+    #[serde(rename = "activitywatch")]
+    ActivityWatch {
+        #[serde(default = "default_activitywatch_url")]
+        url: String,
+        /// Defaults to `tick-window_<hostname>`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bucket: Option<String>,
+        /// Sessions of the same app and title at most this far apart are merged into one event.
+        #[serde(default = "default_pulsetime_secs")]
+        pulsetime_secs: u64,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn default_content_type() -> String {
+    "application/json".to_string()
+}
+
+pub const DEFAULT_ACTIVITYWATCH_URL: &str = "http://localhost:5600";
+
+fn default_activitywatch_url() -> String {
+    DEFAULT_ACTIVITYWATCH_URL.to_string()
+}
+
+fn default_pulsetime_secs() -> u64 {
+    60
+}
+
+impl From<SinkKind> for SinkConfig {
+    fn from(kind: SinkKind) -> Self {
+        Self { name: None, kind }
+    }
+}
+
+impl SinkConfig {
+    /// The main Tick server.
+    pub fn tick() -> Self {
+        SinkKind::Tick {
+            server_url: None,
+            api_key: None,
+            api_key_ref: None,
+            compression: true,
+            batch: true,
+            titles: false,
+        }
+        .into()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self.kind {
+            SinkKind::Tick { .. } => "tick",
+            SinkKind::Jsonl { .. } => "jsonl",
+            SinkKind::Stdout => "stdout",
+            SinkKind::Webhook { .. } => "webhook",
+            SinkKind::ActivityWatch { .. } => "activitywatch",
+        }
+    }
+
+    /// Whether the sink receives window titles; they are removed for every other sink.
+    pub fn sends_titles(&self) -> bool {
+        matches!(self.kind, SinkKind::ActivityWatch { .. } | SinkKind::Tick { titles: true, .. })
+    }
+
+    /// Whether the sink receives browser URLs. Tick servers always do, as they did from the
+    /// browser extension; other sinks get them along with titles.
+    pub fn sends_urls(&self) -> bool {
+        matches!(self.kind, SinkKind::Tick { .. }) || self.sends_titles()
+    }
+
+    /// How the sink is shown to the user.
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.kind())
+    }
+
+    /// Names the retry queue so sessions left from the previous run reach the same sink.
+    pub fn queue_key(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{}-{}", index, self.kind()),
+        }
+    }
+
+    /// Appends any problems with this entry to `problems`; `index` is its position in `sinks`.
+    pub fn validate(&self, index: usize, problems: &mut Vec<String>) {
+        let label = format!("Sink {} ({})", index + 1, self.label());
+        if let Some(name) = &self.name
+            && (name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        {
+            problems.push(format!("{}: names may only use letters, digits, `-` and `_`", label));
+        }
+        match &self.kind {
+            SinkKind::Stdout => {}
+            SinkKind::Tick { server_url, .. } => {
+                if let Some(server_url) = server_url
+                    && !is_http_url(server_url)
+                {
+                    problems.push(format!("{} needs an http or https `server_url`", label));
+                }
+            }
+            SinkKind::Jsonl { path } => {
+                if path.as_os_str().is_empty() {
+                    problems.push(format!("{} needs a `path`", label));
+                }
+            }
+            SinkKind::Webhook { url, headers, .. } => {
+                if !is_http_url(url) {
+                    problems.push(format!("{} needs an http or https `url`", label));
+                }
+                if headers.keys().any(|name| reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err()) {
+                    problems.push(format!("{} has an invalid header name", label));
+                }
+            }
+            SinkKind::ActivityWatch { url, bucket, .. } => {
+                if !is_http_url(url) {
+                    problems.push(format!("{} needs an http or https `url`", label));
+                }
+                if let Some(bucket) = bucket
+                    && (bucket.is_empty()
+                        || !bucket.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                {
+                    problems.push(format!("{}: bucket names may only use letters, digits, `-`, `_` and `.`", label));
+                }
+            }
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    matches!(reqwest::Url::parse(url.trim()), Ok(url) if matches!(url.scheme(), "http" | "https"))
+}
+
+// --- Sinks ---
+
+#[derive(Debug)]
+pub enum SinkError {
+    /// Worth trying again later: the network is down, the server is overloaded, ...
+    Retryable(String),
+    /// Sending the same session again will fail the same way, so it is dropped.
+    Rejected(String),
+}
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::Retryable(e) => write!(f, "{}", e),
+            SinkError::Rejected(e) => write!(f, "rejected: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+impl From<io::Error> for SinkError {
+    fn from(e: io::Error) -> Self {
+        SinkError::Retryable(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for SinkError {
+    fn from(e: reqwest::Error) -> Self {
+        SinkError::Retryable(e.to_string())
+    }
+}
+
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = Result<(), SinkError>> + Send + 'a>>;
+
+/// What became of each session a batch attempt handled, in order; rejected ones carry the reason.
+pub type BatchFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Result<(), String>>, SinkError>> + Send + 'a>>;
+
+/// Somewhere finished sessions are delivered. Implementations make one attempt; retrying is
+/// up to the `SinkQueue` that owns them.
+pub trait SessionSink: Send + Sync {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a>;
+
+    /// Delivers a leading part of `sessions`, which is never empty, in one attempt. An error
+    /// means none of them were handled. By default only the first session is sent.
+    fn send_batch<'a>(&'a self, sessions: &'a [SessionPayload]) -> BatchFuture<'a> {
+        Box::pin(async move { first_only(self.send(&sessions[0]).await) })
+    }
+}
+
+/// The outcome of sending a single session, as the outcome of a batch.
+fn first_only(result: Result<(), SinkError>) -> Result<Vec<Result<(), String>>, SinkError> {
+    match result {
+        Ok(()) => Ok(vec![Ok(())]),
+        Err(SinkError::Rejected(e)) => Ok(vec![Err(e)]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Maps an HTTP status to whether the same request could succeed later.
+fn check_status(status: StatusCode) -> Result<(), SinkError> {
+    if status.is_success() {
+        Ok(())
+    } else if status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        // The token may be fixed in configure; keep the session until then
+        || status == StatusCode::UNAUTHORIZED
+        || status == StatusCode::FORBIDDEN
+    {
+        Err(SinkError::Retryable(format!("server returned {}", status)))
+    } else {
+        Err(SinkError::Rejected(format!("server returned {}", status)))
+    }
+}
+
+pub struct TickSink {
+    client: reqwest::Client,
+    url: String,
+    server: Option<ServerUrl>,
+    legacy_link: LegacyLink,
+    cache: CapabilityCache,
+    features: TickFeatures,
+    support: Mutex<Option<ServerSupport>>,
+}
+
+/// Optional protocol features a Tick sink may use when the server offers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickFeatures {
+    pub compression: bool,
+    pub batch: bool,
+    pub titles: bool,
+}
+
+/// How long what is known about a server is used before it is looked up again. Compact
+/// uploads the server refused stay off for as long.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How soon a discovery that failed is tried again.
+const DISCOVERY_RETRY: Duration = Duration::from_secs(5 * 60);
+
+/// How to talk to the server, from discovery or, for servers without it, response headers.
+/// Until something is known every upload is a single, uncompressed session, which any version
+/// of the server accepts.
+#[derive(Debug, Clone)]
+struct ServerSupport {
+    log_url: String,
+    batch_url: Option<String>,
+    max_batch: usize,
+    compression: Option<Compression>,
+    idempotency: bool,
+    titles: bool,
+    /// Set for servers without discovery, which advertise compression and batching in the
+    /// headers of every response instead.
+    from_headers: bool,
+    expires: Instant,
+}
+
+impl TickSink {
+    pub fn new(
+        server_url: &str,
+        api_key: Option<&str>,
+        legacy_link: LegacyLink,
+        cache: CapabilityCache,
+        features: TickFeatures,
+    ) -> Self {
+        Self {
+            client: build_client(api_key),
+            url: server_url.trim().to_string(),
+            server: ServerUrl::parse(server_url).ok(),
+            legacy_link,
+            cache,
+            features,
+            support: Mutex::new(None),
+        }
+    }
+
+    /// What the server supports, asking it when nothing current is known.
+    async fn support(&self) -> ServerSupport {
+        if let Some(support) = self.support.lock().unwrap().clone()
+            && support.expires > Instant::now()
+        {
+            return support;
+        }
+        let Some(server) = &self.server else {
+            return self.remember(self.adapt(&Capabilities::legacy(), false), DISCOVERY_RETRY);
+        };
+
+        if let Some(capabilities) = self.cache.get(&server.base) {
+            let from_headers = capabilities.protocol_version < 2;
+            return self.remember(self.adapt(&capabilities, from_headers), RECHECK_INTERVAL);
+        }
+        match discovery::discover(&self.client, server).await {
+            Ok(discovered) => {
+                let from_headers = discovered.is_none();
+                let capabilities = discovered.unwrap_or_else(Capabilities::legacy);
+                if capabilities.min_protocol_version > PROTOCOL_VERSION {
+                    println!(
+                        "{} needs protocol {} but this version of Tick speaks {}; please update",
+                        server.base, capabilities.min_protocol_version, PROTOCOL_VERSION
+                    );
+                }
+                if let Err(e) = self.cache.put(&server.base, &capabilities) {
+                    println!("Failed to cache what {} supports: {}", server.base, e);
+                }
+                self.remember(self.adapt(&capabilities, from_headers), RECHECK_INTERVAL)
+            }
+            Err(e) => {
+                println!("Could not ask {} what it supports: {}", server.base, e);
+                self.remember(self.adapt(&Capabilities::legacy(), true), DISCOVERY_RETRY)
+            }
+        }
+    }
+
+    fn remember(&self, mut support: ServerSupport, valid_for: Duration) -> ServerSupport {
+        support.expires = Instant::now() + valid_for;
+        *self.support.lock().unwrap() = Some(support.clone());
+        support
+    }
+
+    /// The parts of `capabilities` this sink is allowed to use.
+    fn adapt(&self, capabilities: &Capabilities, from_headers: bool) -> ServerSupport {
+        let server = self.server.as_ref();
+        ServerSupport {
+            log_url: server.map_or_else(|| self.url.clone(), |server| server.log_session_url(capabilities).into()),
+            batch_url: server
+                .and_then(|server| server.log_sessions_url(capabilities))
+                .map(String::from)
+                .filter(|_| self.features.batch),
+            max_batch: capabilities.features.batch.as_ref().map_or(MAX_BATCH_SESSIONS, |batch| batch.max_sessions),
+            compression: capabilities.compression().filter(|_| self.features.compression),
+            idempotency: capabilities.features.idempotency,
+            titles: capabilities.features.titles && self.features.titles,
+            from_headers,
+            expires: Instant::now(),
+        }
+    }
+
+    /// Picks up what a server without discovery advertises, limited to what the sink is
+    /// allowed to use. Servers that stop advertising get plain JSON again.
+    fn learn(&self, headers: &HeaderMap) {
+        let mut support = self.support.lock().unwrap();
+        let Some(support) = support.as_mut().filter(|support| support.from_headers) else {
+            return;
+        };
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        support.compression = header(ACCEPT_ENCODING.as_str())
+            .and_then(Compression::negotiate)
+            .filter(|_| self.features.compression);
+        support.batch_url = header(BATCH_URL_HEADER)
+            .and_then(|path| reqwest::Url::parse(&support.log_url).ok()?.join(path).ok())
+            .map(String::from)
+            .filter(|_| self.features.batch);
+    }
+
+    /// Goes back to plain single sessions for a while and asks the server again after that.
+    fn fall_back(&self) {
+        let support = self.adapt(&Capabilities::legacy(), false);
+        self.remember(support, RECHECK_INTERVAL);
+        if let Some(server) = &self.server
+            && let Err(e) = self.cache.forget(&server.base)
+        {
+            println!("Failed to update what {} supports: {}", server.base, e);
+        }
+    }
+
+    /// POSTs a JSON body as `support` allows. `compact` requests fall back to plain single
+    /// sessions if the server refuses them.
+    async fn post(
+        &self,
+        support: &ServerSupport,
+        url: &str,
+        body: Vec<u8>,
+        compact: bool,
+        sessions: &[SessionPayload],
+    ) -> Result<reqwest::Response, SinkError> {
+        let mut request = self.client.post(url).header(CONTENT_TYPE, "application/json");
+        if support.idempotency {
+            request = request.header("Idempotency-Key", idempotency_key(sessions));
+        }
+        let body = match support.compression {
+            Some(compression) => {
+                request = request.header(CONTENT_ENCODING, compression.content_encoding());
+                compression.compress(&body)?
+            }
+            None => body,
+        };
+        let response = request.body(body).send().await?;
+        let status = response.status();
+        if let Err(SinkError::Rejected(e)) = check_status(status)
+            && (compact || support.compression.is_some())
+        {
+            // The server no longer takes what it advertised; try again the plain way
+            self.fall_back();
+            return Err(SinkError::Retryable(format!("compact upload refused: {}", e)));
+        }
+        self.learn(response.headers());
+        check_status(status)?;
+        Ok(response)
+    }
+}
+
+/// The same sessions always get the same key, so an upload retried after a lost response is
+/// only stored once.
+fn idempotency_key(sessions: &[SessionPayload]) -> String {
+    let first = &sessions[0];
+    format!("{}/{}/{}", first.device_id, first.start_time, sessions.len())
+}
+
+impl SessionSink for TickSink {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a> {
+        Box::pin(async move {
+            let support = self.support().await;
+            let mut session = session.clone();
+            session.previous_device_id = self.legacy_link.pending();
+            if !support.titles {
+                session.title = None;
+            }
+
+            let body = serde_json::to_vec(&session).expect("Serialization failed");
+            let response = self
+                .post(&support, &support.log_url, body, false, std::slice::from_ref(&session))
+                .await?;
+            match response.json::<LogResponse>().await {
+                Ok(LogResponse { success: true, device_linked, .. }) => {
+                    if device_linked {
+                        self.legacy_link.linked();
+                    }
+                    Ok(())
+                }
+                Ok(LogResponse { error, .. }) => Err(SinkError::Rejected(
+                    error.unwrap_or_else(|| "server did not accept the session".to_string()),
+                )),
+                Err(e) => Err(SinkError::Retryable(format!("unexpected response: {}", e))),
+            }
+        })
+    }
+
+    fn send_batch<'a>(&'a self, sessions: &'a [SessionPayload]) -> BatchFuture<'a> {
+        Box::pin(async move {
+            let support = self.support().await;
+            let batch = Batch::encode(sessions, support.max_batch, support.titles);
+            let (Some(batch_url), Some(mut batch)) = (support.batch_url.as_ref(), batch) else {
+                return first_only(self.send(&sessions[0]).await);
+            };
+            if batch.sessions.len() == 1 {
+                return first_only(self.send(&sessions[0]).await);
+            }
+            batch.device.previous_device_id = self.legacy_link.pending();
+
+            let body = serde_json::to_vec(&batch).expect("Serialization failed");
+            let sent = &sessions[..batch.sessions.len()];
+            let response = self.post(&support, batch_url, body, true, sent).await?;
+            let response = match response.json::<BatchResponse>().await {
+                Ok(response) if response.results.len() <= batch.sessions.len() => response,
+                Ok(_) => return Err(SinkError::Retryable("unexpected response: too many results".to_string())),
+                Err(e) => return Err(SinkError::Retryable(format!("unexpected response: {}", e))),
+            };
+            if response.device_linked {
+                self.legacy_link.linked();
+            }
+            if response.results.is_empty() {
+                return Err(SinkError::Retryable(
+                    response.error.unwrap_or_else(|| "server did not store the batch".to_string()),
+                ));
+            }
+            Ok(response
+                .results
+                .into_iter()
+                .map(|result| match result {
+                    LogResponse { success: true, .. } => Ok(()),
+                    LogResponse { error, .. } => {
+                        Err(error.unwrap_or_else(|| "server did not accept the session".to_string()))
+                    }
+                })
+                .collect())
+        })
+    }
+}
+
+pub struct JsonlSink {
+    path: PathBuf,
+}
+
+impl JsonlSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl SessionSink for JsonlSink {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a> {
+        Box::pin(async move {
+            let mut line = serde_json::to_string(session).expect("Serialization failed");
+            line.push('\n');
+            if let Some(dir) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)?;
+            }
+            // A single write of the whole line keeps concurrent appenders from interleaving
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?
+                .write_all(line.as_bytes())?;
+            Ok(())
+        })
+    }
+}
+
+pub struct StdoutSink;
+
+impl SessionSink for StdoutSink {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a> {
+        Box::pin(async move {
+            println!("Session: {}", serde_json::to_string(session).expect("Serialization failed"));
+            Ok(())
+        })
+    }
+}
+
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    content_type: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str, headers: BTreeMap<String, String>, body: Option<String>, content_type: String) -> Self {
+        Self {
+            client: build_client(None),
+            url: url.trim().to_string(),
+            headers,
+            body,
+            content_type,
+        }
+    }
+}
+
+impl SessionSink for WebhookSink {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a> {
+        Box::pin(async move {
+            let body = match &self.body {
+                Some(template) => render_template(template, session, self.content_type.contains("json")),
+                None => serde_json::to_string(session).expect("Serialization failed"),
+            };
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, &self.content_type)
+                .body(body);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+            check_status(request.send().await?.status())
+        })
+    }
+}
+
+/// Sends sessions to aw-server as `currentwindow` heartbeats, creating the bucket on first use.
+pub struct ActivityWatchSink {
+    client: reqwest::Client,
+    bucket_url: String,
+    hostname: String,
+    pulsetime_secs: u64,
+    bucket_ready: AtomicBool,
+}
+
+impl ActivityWatchSink {
+    pub fn new(url: &str, bucket: &str, hostname: &str, pulsetime_secs: u64) -> Self {
+        Self {
+            client: build_client(None),
+            bucket_url: format!("{}/api/0/buckets/{}", url.trim().trim_end_matches('/'), bucket),
+            hostname: hostname.to_string(),
+            pulsetime_secs,
+            bucket_ready: AtomicBool::new(false),
+        }
+    }
+
+    async fn create_bucket(&self) -> Result<(), SinkError> {
+        let body = serde_json::json!({
+            "client": "tick",
+            "type": "currentwindow",
+            "hostname": self.hostname,
+        });
+        let status = self.client.post(&self.bucket_url).json(&body).send().await?.status();
+        // aw-server answers 304 when the bucket already exists
+        if status != StatusCode::NOT_MODIFIED {
+            check_status(status)?;
+        }
+        self.bucket_ready.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl SessionSink for ActivityWatchSink {
+    fn send<'a>(&'a self, session: &'a SessionPayload) -> SendFuture<'a> {
+        Box::pin(async move {
+            let (Ok(start), Ok(end)) = (
+                chrono::DateTime::parse_from_rfc3339(&session.start_time),
+                chrono::DateTime::parse_from_rfc3339(&session.end_time),
+            ) else {
+                return Err(SinkError::Rejected("session has invalid timestamps".to_string()));
+            };
+            if !self.bucket_ready.load(Ordering::Relaxed) {
+                self.create_bucket().await?;
+            }
+
+            let event = serde_json::json!({
+                "timestamp": session.start_time,
+                "duration": end.signed_duration_since(start).num_milliseconds() as f64 / 1000.0,
+                "data": {
+                    "app": session.app_name,
+                    "title": session.title.as_deref().unwrap_or_default(),
+                },
+            });
+            let status = self
+                .client
+                .post(format!("{}/heartbeat?pulsetime={}", self.bucket_url, self.pulsetime_secs))
+                .json(&event)
+                .send()
+                .await?
+                .status();
+            if status == StatusCode::NOT_FOUND {
+                // Someone deleted the bucket; create it again on the next attempt
+                self.bucket_ready.store(false, Ordering::Relaxed);
+                return Err(SinkError::Retryable("bucket does not exist".to_string()));
+            }
+            check_status(status)
+        })
+    }
+}
+
+/// Replaces `{{field}}` with the session's values. `device_id`, `device_name`, `app_name`,
+/// `start_time`, `end_time`, `time_zone` and `duration_secs` are available. For JSON bodies the
+/// values are escaped so they can sit inside string literals.
+pub fn render_template(template: &str, session: &SessionPayload, json_escape: bool) -> String {
+    let duration_secs = match (
+        chrono::DateTime::parse_from_rfc3339(&session.start_time),
+        chrono::DateTime::parse_from_rfc3339(&session.end_time),
+    ) {
+        (Ok(start), Ok(end)) => end.signed_duration_since(start).num_seconds().to_string(),
+        _ => String::new(),
+    };
+    let fields = [
+        ("device_id", session.device_id.as_str()),
+        ("device_name", session.device_name.as_deref().unwrap_or_default()),
+        ("app_name", session.app_name.as_str()),
+        ("start_time", session.start_time.as_str()),
+        ("end_time", session.end_time.as_str()),
+        ("time_zone", session.time_zone.as_str()),
+        ("duration_secs", duration_secs.as_str()),
+    ];
+
+    let mut rendered = template.to_string();
+    for (name, value) in fields {
+        let value = if json_escape {
+            let quoted = serde_json::to_string(value).expect("Serialization failed");
+            quoted[1..quoted.len() - 1].to_string()
+        } else {
+            value.to_string()
+        };
+        rendered = rendered.replace(&format!("{{{{{}}}}}", name), &value);
+    }
+    rendered
+}
+
+/// Creates the sink described by one `sinks` entry.
+pub fn build_sink(sink: &SinkConfig, config: &Config, data_dir: &Path, legacy_link: &LegacyLink) -> Box<dyn SessionSink> {
+    match &sink.kind {
+        SinkKind::Tick { server_url, api_key, compression, batch, titles, .. } => {
+            // Without its own URL the sink uses the main server and token
+            let (server_url, api_key) = match server_url {
+                Some(server_url) => (server_url, api_key),
+                None => (&config.server_url, &config.api_key),
+            };
+            Box::new(TickSink::new(
+                server_url,
+                api_key.as_deref(),
+                legacy_link.clone(),
+                CapabilityCache::new(data_dir),
+                TickFeatures {
+                    compression: *compression,
+                    batch: *batch,
+                    titles: *titles,
+                },
+            ))
+        }
+        SinkKind::Jsonl { path } => Box::new(JsonlSink::new(data_dir.join(path))),
+        SinkKind::Stdout => Box::new(StdoutSink),
+        SinkKind::Webhook { url, headers, body, content_type } => Box::new(WebhookSink::new(
+            url,
+            headers.clone(),
+            body.clone(),
+            content_type.clone(),
+        )),
+        SinkKind::ActivityWatch { url, bucket, pulsetime_secs } => {
+            let hostname = device::default_name();
+            let bucket = bucket.clone().unwrap_or_else(|| format!("tick-window_{}", hostname));
+            Box::new(ActivityWatchSink::new(url, &bucket, &hostname, *pulsetime_secs))
+        }
+    }
+}
+
+// --- Queueing ---
+
+/// How long a queue waits between attempts while a sink keeps failing.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub initial: Duration,
+    pub max: Duration,
+    /// How long shutdown keeps trying before the rest is saved for the next run.
+    pub shutdown_grace: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(2),
+            max: Duration::from_secs(300),
+            shutdown_grace: Duration::from_secs(5),
+        }
+    }
+}
+
+enum QueueCommand {
+    Send(Box<SessionPayload>),
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Delivers sessions to one sink in order on its own task, so a slow or unreachable sink never
+/// holds up the others. Queued sessions are kept in `<outbox>/<key>.jsonl` until delivered,
+/// so a crash or power loss doesn't lose them, and are sent on the next run.
+pub struct SinkQueue {
+    key: String,
+    tx: mpsc::UnboundedSender<QueueCommand>,
+    task: JoinHandle<()>,
+}
+
+impl SinkQueue {
+    pub fn start(key: String, sink: Box<dyn SessionSink>, outbox_dir: &Path, retry: RetryPolicy) -> Self {
+        Self::start_after(None, key, sink, outbox_dir, retry)
+    }
+
+    /// Like `start`, taking over from `previous`: it is shut down first, so the sessions it
+    /// still held reach the new queue through the outbox. Sessions pushed meanwhile wait in
+    /// order, and the caller doesn't wait at all.
+    pub fn start_after(
+        previous: Option<SinkQueue>,
+        key: String,
+        sink: Box<dyn SessionSink>,
+        outbox_dir: &Path,
+        retry: RetryPolicy,
+    ) -> Self {
+        let outbox = outbox_dir.join(format!("{}.jsonl", key));
+        let (tx, rx) = mpsc::unbounded_channel();
+        let worker_key = key.clone();
+        let task = tokio::spawn(async move {
+            if let Some(previous) = previous {
+                previous.shutdown().await;
+            }
+            let pending = load_outbox(&outbox);
+            if !pending.is_empty() {
+                println!("[{}] Resuming {} queued sessions", worker_key, pending.len());
+            }
+            let worker = QueueWorker {
+                key: worker_key,
+                sink,
+                pending,
+                outbox,
+                retry,
+            };
+            worker.run(rx).await;
+        });
+        Self { key, tx, task }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn push(&self, session: SessionPayload) {
+        let _ = self.tx.send(QueueCommand::Send(Box::new(session)));
+    }
+
+    /// Makes a last delivery attempt within the grace period and saves whatever is left.
+    pub async fn shutdown(self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(QueueCommand::Shutdown(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+        let _ = self.task.await;
+    }
+}
+
+struct QueueWorker {
+    key: String,
+    sink: Box<dyn SessionSink>,
+    pending: VecDeque<SessionPayload>,
+    outbox: PathBuf,
+    retry: RetryPolicy,
+}
+
+impl QueueWorker {
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<QueueCommand>) {
+        let mut backoff = self.retry.initial;
+        loop {
+            if self.pending.is_empty() {
+                match rx.recv().await {
+                    Some(QueueCommand::Send(session)) => self.enqueue(*session),
+                    Some(QueueCommand::Shutdown(done)) => return self.finish(Some(done)).await,
+                    None => return self.finish(None).await,
+                }
+                continue;
+            }
+
+            if self.attempt().await {
+                backoff = self.retry.initial;
+                continue;
+            }
+
+            // Keep accepting sessions while waiting to retry
+            let retry_at = Instant::now() + backoff;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(retry_at) => break,
+                    command = rx.recv() => match command {
+                        Some(QueueCommand::Send(session)) => self.enqueue(*session),
+                        Some(QueueCommand::Shutdown(done)) => return self.finish(Some(done)).await,
+                        None => return self.finish(None).await,
+                    },
+                }
+            }
+            backoff = (backoff * 2).min(self.retry.max);
+        }
+    }
+
+    fn enqueue(&mut self, session: SessionPayload) {
+        if let Err(e) = append_outbox(&self.outbox, &session) {
+            println!("[{}] Failed to save queued session for {}: {}", self.key, session.app_name, e);
+        }
+        self.pending.push_back(session);
+    }
+
+    /// Sends the oldest sessions and trims the outbox to what is left. Returns false if they
+    /// should be retried later.
+    async fn attempt(&mut self) -> bool {
+        if self.pending.is_empty() {
+            return true;
+        }
+        match self.sink.send_batch(self.pending.make_contiguous()).await {
+            Ok(results) => {
+                for result in results {
+                    let Some(session) = self.pending.pop_front() else {
+                        break;
+                    };
+                    if let Err(e) = result {
+                        println!("[{}] Dropping session for {}: {}", self.key, session.app_name, e);
+                    }
+                }
+            }
+            Err(SinkError::Rejected(e)) => {
+                if let Some(session) = self.pending.pop_front() {
+                    println!("[{}] Dropping session for {}: {}", self.key, session.app_name, e);
+                }
+            }
+            Err(SinkError::Retryable(e)) => {
+                println!("[{}] Delivery failed, {} sessions queued: {}", self.key, self.pending.len(), e);
+                return false;
+            }
+        }
+        if let Err(e) = save_outbox(&self.outbox, &self.pending) {
+            println!("[{}] Failed to save {} queued sessions: {}", self.key, self.pending.len(), e);
+        }
+        true
+    }
+
+    async fn finish(mut self, done: Option<oneshot::Sender<()>>) {
+        let deadline = Instant::now() + self.retry.shutdown_grace;
+        while !self.pending.is_empty() {
+            match tokio::time::timeout_at(deadline, self.attempt()).await {
+                Ok(true) => {}
+                Ok(false) | Err(_) => break,
+            }
+        }
+        if let Err(e) = save_outbox(&self.outbox, &self.pending) {
+            println!("[{}] Failed to save {} queued sessions: {}", self.key, self.pending.len(), e);
+        } else if !self.pending.is_empty() {
+            println!("[{}] Saved {} queued sessions for the next run", self.key, self.pending.len());
+        }
+        if let Some(done) = done {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn load_outbox(path: &Path) -> VecDeque<SessionPayload> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return VecDeque::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                println!("Skipping unreadable queued session in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn append_outbox(path: &Path, session: &SessionPayload) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(session).expect("Serialization failed");
+    line.push('\n');
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// Rewrites the outbox with `pending`, removing it once nothing is left.
+fn save_outbox(path: &Path, pending: &VecDeque<SessionPayload>) -> io::Result<()> {
+    if pending.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut content = String::new();
+    for session in pending {
+        content.push_str(&serde_json::to_string(session).expect("Serialization failed"));
+        content.push('\n');
+    }
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+// --- Delivery ---
+
+struct Target {
+    name: Option<String>,
+    label: String,
+    titles: bool,
+    urls: bool,
+    settings: TargetSettings,
+    queue: SinkQueue,
+}
+
+/// Everything a sink is built from; a target whose settings are unchanged keeps running.
+#[derive(Debug, Clone, PartialEq)]
+struct TargetSettings {
+    sink: SinkConfig,
+    /// The main server and token, for Tick sinks without their own.
+    main_server: Option<(String, Option<String>)>,
+}
+
+impl TargetSettings {
+    fn new(sink: &SinkConfig, config: &Config) -> Self {
+        let main_server = match &sink.kind {
+            SinkKind::Tick { server_url: None, .. } => Some((config.server_url.clone(), config.api_key.clone())),
+            _ => None,
+        };
+        Self {
+            sink: sink.clone(),
+            main_server,
+        }
+    }
+}
+
+/// Fans each finished session out to the configured sinks.
+pub struct Delivery {
+    targets: Vec<Target>,
+    outbox_dir: PathBuf,
+    retry: RetryPolicy,
+    /// Tick sinks get every session, as `Config::managed_server` asks.
+    managed_server: bool,
+    /// Queues of removed sinks that are still making their last delivery attempt.
+    retiring: Vec<JoinHandle<()>>,
+}
+
+impl Delivery {
+    pub fn start(config: &Config, data_dir: &Path, legacy_link: &LegacyLink) -> Self {
+        Self::start_with(config, data_dir, &data_dir.join("outbox"), legacy_link, RetryPolicy::default())
+    }
+
+    /// Like `start`, with queues kept in `outbox_dir` so another process's queues are left alone.
+    pub fn start_with(
+        config: &Config,
+        data_dir: &Path,
+        outbox_dir: &Path,
+        legacy_link: &LegacyLink,
+        retry: RetryPolicy,
+    ) -> Self {
+        let mut delivery = Self {
+            targets: Vec::new(),
+            outbox_dir: outbox_dir.to_path_buf(),
+            retry,
+            managed_server: false,
+            retiring: Vec::new(),
+        };
+        delivery.restart(config, data_dir, legacy_link);
+        delivery
+    }
+
+    /// Queues the session for the named sinks, or every sink when `only` is `None`. Tick sinks
+    /// are always included while the server is managed by policy. Returns the labels of the sinks
+    /// it was queued for.
+    pub fn submit(&self, session: SessionPayload, only: Option<&[String]>) -> Vec<String> {
+        let mut labels = Vec::new();
+        for target in &self.targets {
+            let selected = match only {
+                None => true,
+                Some(names) => {
+                    target.name.as_ref().is_some_and(|name| names.contains(name))
+                        || (self.managed_server && matches!(target.settings.sink.kind, SinkKind::Tick { .. }))
+                }
+            };
+            if selected {
+                let mut session = session.clone();
+                if !target.titles {
+                    session.title = None;
+                    if let Some(editor) = &mut session.editor {
+                        editor.file = None;
+                    }
+                }
+                if !target.urls {
+                    session.url = None;
+                }
+                target.queue.push(session);
+                labels.push(target.label.clone());
+            }
+        }
+        labels
+    }
+
+    /// Applies a config change to the sinks. Sinks whose settings are unchanged keep running;
+    /// changed ones are rebuilt and get the sessions still queued for them through the outbox.
+    /// Replaced and removed queues wind down in the background, so this never waits on a sink.
+    pub fn restart(&mut self, config: &Config, data_dir: &Path, legacy_link: &LegacyLink) {
+        self.managed_server = config.managed_server;
+        let mut previous: Vec<Target> = std::mem::take(&mut self.targets);
+        for (index, sink) in config.sinks.iter().enumerate() {
+            let key = sink.queue_key(index);
+            let settings = TargetSettings::new(sink, config);
+            let old = previous
+                .iter()
+                .position(|target| target.queue.key() == key)
+                .map(|position| previous.remove(position));
+            let queue = match old {
+                Some(target) if target.settings == settings => target.queue,
+                old => {
+                    if old.is_some() {
+                        println!("[{}] Settings changed, restarting", key);
+                    }
+                    SinkQueue::start_after(
+                        old.map(|target| target.queue),
+                        key,
+                        build_sink(sink, config, data_dir, legacy_link),
+                        &self.outbox_dir,
+                        self.retry,
+                    )
+                }
+            };
+            self.targets.push(Target {
+                name: sink.name.clone(),
+                label: sink.label().to_string(),
+                titles: sink.sends_titles(),
+                urls: sink.sends_urls(),
+                settings,
+                queue,
+            });
+        }
+
+        self.retiring.retain(|task| !task.is_finished());
+        for target in previous {
+            println!("[{}] Removed, delivering what is left", target.queue.key());
+            self.retiring.push(tokio::spawn(target.queue.shutdown()));
+        }
+    }
+
+    pub async fn shutdown(&mut self) {
+        shutdown_all(std::mem::take(&mut self.targets)).await;
+        for task in std::mem::take(&mut self.retiring) {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Shuts the queues down at once so their grace periods overlap.
+async fn shutdown_all(targets: Vec<Target>) {
+    let tasks: Vec<_> = targets
+        .into_iter()
+        .map(|target| tokio::spawn(target.queue.shutdown()))
+        .collect();
+    for task in tasks {
+        let _ = task.await;
+    }
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+const TIMER_FILE: &str = "timer.json";
+
+/// Projects offered in the tray menu.
+pub const MAX_RECENT: usize = 8;
+
+/// Project timer of the tracker, kept in the data folder so a running timer survives restarts.
+/// Sessions recorded while it runs are tagged with its project.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TimerState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<ActiveTimer>,
+    /// Projects timed before, most recent first.
+    #[serde(default)]
+    pub recent: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveTimer {
+    pub project: String,
+    pub started_at: DateTime<Utc>,
+}
+
+impl TimerState {
+    /// The saved state; a missing or unreadable file means no timer.
+    pub fn load(data_dir: &Path) -> Self {
+        fs::read_to_string(data_dir.join(TIMER_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, data_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(TIMER_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self).expect("Serialization failed"))?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    /// Project sessions are tagged with right now.
+    pub fn project(&self) -> Option<&str> {
+        self.active.as_ref().map(|timer| timer.project.as_str())
+    }
+
+    /// Starts the timer for `project`, stopping the one running for another project. Returns
+    /// whether anything changed; blank names and the project already running change nothing.
+    pub fn start(&mut self, project: &str, now: DateTime<Utc>) -> bool {
+        let project = project.trim();
+        if project.is_empty() || self.project() == Some(project) {
+            return false;
+        }
+        self.active = Some(ActiveTimer {
+            project: project.to_string(),
+            started_at: now,
+        });
+        self.recent.retain(|recent| recent != project);
+        self.recent.insert(0, project.to_string());
+        self.recent.truncate(MAX_RECENT);
+        true
+    }
+
+    /// Stops the running timer and returns it.
+    pub fn stop(&mut self) -> Option<ActiveTimer> {
+        self.active.take()
+    }
+}
@@ -0,0 +1,184 @@
+use crate::session::{EditorContext, LogResponse, SessionPayload};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// Response header naming the server's batch endpoint, relative to the `log-session` URL. Used
+/// for servers without discovery; those that don't send it only receive single sessions.
+pub const BATCH_URL_HEADER: &str = "tick-batch-url";
+
+/// Most sessions sent in one batch, whatever the server allows.
+pub const MAX_BATCH_SESSIONS: usize = 100;
+
+// --- Compression ---
+
+/// How request bodies are compressed, once the server has said it can decode them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Picks the best encoding from a server's `Accept-Encoding` response header (RFC 7694).
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let offered: Vec<String> = accept_encoding
+            .split(',')
+            .map(|coding| coding.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+            .collect();
+        if offered.iter().any(|coding| coding == "zstd") {
+            Some(Compression::Zstd)
+        } else if offered.iter().any(|coding| coding == "gzip") {
+            Some(Compression::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// Value of the `Content-Encoding` header.
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+}
+
+// --- Batches ---
+
+/// Body of the server's `log-sessions` endpoint: sessions of one device with the fields they
+/// share sent once, and app names and time zones replaced by their index in `apps` and
+/// `time_zones`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Batch {
+    pub device: BatchDevice,
+    pub apps: Vec<String>,
+    pub time_zones: Vec<String>,
+    /// `[app, time zone, start, end]`, with times in milliseconds since the Unix epoch.
+    pub sessions: Vec<(usize, usize, i64, i64)>,
+    /// Window title of each session, for servers that store them; otherwise left out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub titles: Vec<Option<String>>,
+    /// Browser page of each session, when any session has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<Option<String>>,
+    /// Editor plugin context of each session, when any session has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub editors: Vec<Option<EditorContext>>,
+    /// Project tag of each session, when any session has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Option<String>>,
+    /// Ticket of each session, when any session has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tickets: Vec<Option<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchDevice {
+    pub device_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_device_id: Option<String>,
+    pub device_platform: String,
+}
+
+impl Batch {
+    /// Encodes the leading sessions that can travel together: at most `max` of the first
+    /// session's device, up to the first one with unreadable timestamps. `None` if the first
+    /// session can't be batched.
+    pub fn encode(sessions: &[SessionPayload], max: usize, titles: bool) -> Option<Self> {
+        let first = sessions.first()?;
+        let mut batch = Batch {
+            device: BatchDevice {
+                device_id: first.device_id.clone(),
+                device_name: first.device_name.clone(),
+                previous_device_id: first.previous_device_id.clone(),
+                device_platform: first.device_platform.clone(),
+            },
+            apps: Vec::new(),
+            time_zones: Vec::new(),
+            sessions: Vec::new(),
+            titles: Vec::new(),
+            urls: Vec::new(),
+            editors: Vec::new(),
+            tags: Vec::new(),
+            tickets: Vec::new(),
+        };
+        for session in sessions.iter().take(max.min(MAX_BATCH_SESSIONS)) {
+            let same_device = session.device_id == first.device_id
+                && session.device_name == first.device_name
+                && session.device_platform == first.device_platform;
+            let (true, Ok(start), Ok(end)) = (
+                same_device,
+                DateTime::parse_from_rfc3339(&session.start_time),
+                DateTime::parse_from_rfc3339(&session.end_time),
+            ) else {
+                break;
+            };
+            let app = index_of(&mut batch.apps, &session.app_name);
+            let time_zone = index_of(&mut batch.time_zones, &session.time_zone);
+            batch
+                .sessions
+                .push((app, time_zone, start.timestamp_millis(), end.timestamp_millis()));
+            batch.titles.push(session.title.clone().filter(|_| titles));
+            batch.urls.push(session.url.clone());
+            batch.editors.push(session.editor.clone());
+            batch.tags.push(session.tag.clone());
+            batch.tickets.push(session.ticket.clone());
+        }
+        if batch.titles.iter().all(Option::is_none) {
+            batch.titles.clear();
+        }
+        if batch.urls.iter().all(Option::is_none) {
+            batch.urls.clear();
+        }
+        if batch.editors.iter().all(Option::is_none) {
+            batch.editors.clear();
+        }
+        if batch.tags.iter().all(Option::is_none) {
+            batch.tags.clear();
+        }
+        if batch.tickets.iter().all(Option::is_none) {
+            batch.tickets.clear();
+        }
+        (!batch.sessions.is_empty()).then_some(batch)
+    }
+}
+
+/// Position of `value` in `dictionary`, adding it if missing.
+fn index_of(dictionary: &mut Vec<String>, value: &str) -> usize {
+    match dictionary.iter().position(|entry| entry == value) {
+        Some(index) => index,
+        None => {
+            dictionary.push(value.to_string());
+            dictionary.len() - 1
+        }
+    }
+}
+
+/// What the server answers to `log-sessions`: a result for each leading session it handled, in
+/// order. Sessions without a result were not stored and can be sent again.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResponse {
+    pub success: bool,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub results: Vec<LogResponse>,
+    #[serde(default)]
+    pub device_linked: bool,
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{json, Value};
+use tick::{
+    agent::{self, AgentClient, AgentEvent, AgentInfo, BrowserEvent, Heartbeat, BROWSER_EVENTS_PATH, WAKATIME_API_PATH},
+    session::EditorContext,
+};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn relays_browser_events_from_clients_with_the_token() {
+    let dir = common::temp_dir("agent", "relay");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let info = agent::start(&dir, tx).await.unwrap();
+    assert_eq!(AgentInfo::load(&dir), Some(info.clone()));
+
+    let event = BrowserEvent::Tab { url: "https://github.com/".to_string(), title: "GitHub".to_string() };
+    AgentClient::new(&dir).send_browser_event(&event).await.unwrap();
+    assert_eq!(rx.recv().await, Some(AgentEvent::Browser(event)));
+
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let url = format!("http://127.0.0.1:{}{}", info.port, BROWSER_EVENTS_PATH);
+    let status = |response: reqwest::Response| response.status().as_u16();
+    let wrong_token = client.post(&url).bearer_auth("guess").body(r#"{"type":"none"}"#).send().await.unwrap();
+    assert_eq!(status(wrong_token), 401);
+    let not_an_event = client.post(&url).bearer_auth(&info.token).body("{}").send().await.unwrap();
+    assert_eq!(status(not_an_event), 400);
+    assert!(rx.try_recv().is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn clients_fail_without_a_running_tracker() {
+    let dir = common::temp_dir("agent", "missing");
+    let result = AgentClient::new(&dir).send_browser_event(&BrowserEvent::None).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn accepts_wakatime_heartbeats() {
+    let dir = common::temp_dir("agent", "wakatime");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let info = agent::start(&dir, tx).await.unwrap();
+
+    // wakatime-cli sends the API key as the user name of basic authentication
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let base = format!("http://127.0.0.1:{}{}/users/current", info.port, WAKATIME_API_PATH);
+    let authorization = format!("Basic {}", BASE64.encode(&info.token));
+    let heartbeats = json!([
+        {"entity": "C:/src/tick/src/agent.rs", "type": "file", "time": 1792310400.5, "project": "tick",
+         "language": "Rust", "is_write": true, "user_agent": "wakatime/v1.90.0 vscode/1.95.0"},
+        {"entity": "not a heartbeat"},
+    ]);
+    let response = client
+        .post(format!("{}/heartbeats.bulk", base))
+        .header("Authorization", &authorization)
+        .json(&heartbeats)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 202);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["responses"][0], json!([{"data": heartbeats[0]}, 201]));
+    assert_eq!(body["responses"][1][1], 400);
+
+    let Some(AgentEvent::Heartbeat(heartbeat)) = rx.recv().await else {
+        panic!("expected a heartbeat");
+    };
+    assert_eq!(heartbeat.time().unwrap().timestamp_millis(), 1_792_310_400_500);
+    assert_eq!(
+        heartbeat.editor_context(),
+        EditorContext {
+            project: Some("tick".to_string()),
+            file: Some("C:/src/tick/src/agent.rs".to_string()),
+            language: Some("Rust".to_string()),
+        }
+    );
+    assert!(rx.try_recv().is_err());
+
+    let single = client
+        .post(format!("{}/heartbeats", base))
+        .basic_auth(&info.token, Some(""))
+        .json(&json!({"entity": "Figma", "type": "app", "time": 1792310460.0}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(single.status().as_u16(), 201);
+    let Some(AgentEvent::Heartbeat(app)) = rx.recv().await else {
+        panic!("expected a heartbeat");
+    };
+    // Only file heartbeats name a file
+    assert_eq!(app.editor_context(), EditorContext::default());
+
+    // The token survives a restart, so configured plugins keep working
+    let (tx, _rx) = mpsc::unbounded_channel();
+    assert_eq!(agent::start(&dir, tx).await.unwrap().token, info.token);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn heartbeats_ignore_unknown_fields() {
+    let heartbeat: Heartbeat = serde_json::from_value(json!({
+        "entity": "main.py", "time": 1.0, "lines": 40, "category": "debugging"
+    }))
+    .unwrap();
+    assert_eq!(heartbeat.editor_context().file.as_deref(), Some("main.py"));
+}
+
+#[test]
+fn heartbeats_belong_to_the_editor_that_sent_them() {
+    let from = |user_agent: Option<&str>| Heartbeat {
+        entity: "main.rs".to_string(),
+        kind: None,
+        time: 1.0,
+        project: None,
+        language: None,
+        user_agent: user_agent.map(str::to_string),
+    };
+    let vscode = from(Some(
+        "wakatime/v1.90.0 (windows-10.0.22631-x86_64) go1.22.5 vscode/1.95.0 vscode-wakatime/24.6.1",
+    ));
+    assert!(vscode.is_from("Code"));
+    assert!(vscode.is_from("Visual Studio Code"));
+    assert!(!vscode.is_from("WindowsTerminal"));
+    assert!(!vscode.is_from("chrome"));
+
+    let neovim = from(Some("wakatime/v1.90.0 neovim/0.10.1 vim-wakatime/11.2.0"));
+    assert!(neovim.is_from("WindowsTerminal"));
+    assert!(!neovim.is_from("Code"));
+
+    let jetbrains = from(Some("wakatime/v1.90.0 IntelliJ IDEA/2024.2 jetbrains-wakatime/15.0.2"));
+    assert!(jetbrains.is_from("rustrover64"));
+    assert!(!jetbrains.is_from("Slack"));
+
+    // Editors without a known plugin match an app of the same name
+    assert!(from(Some("wakatime/v1.90.0 zed/0.150.0 zed-wakatime/0.1.2")).is_from("Zed"));
+    assert!(!from(None).is_from("Code"));
+}
@@ -0,0 +1,64 @@
+use tick::attribution::{Attribution, Attributor, ProjectRule};
+use tick::config::{Config, ConfigError};
+
+fn rules() -> Vec<ProjectRule> {
+    let config = Config::from_json(
+        r#"{
+            "version": 2,
+            "project_rules": [
+                { "app": "^code$", "title": "^(?:● )?.+ - (?<repo>[^-]+) - Visual Studio Code$", "project": "${repo}" },
+                { "path": "WindowsTerminal\\.exe$", "title": "~/src/([^/ ]+)", "project": "$1" },
+                { "title": "\\b(?<ticket>(?<key>[A-Z][A-Z0-9]+)-\\d+)\\b", "project": "jira-${key}", "ticket": "$ticket" },
+                { "title": "\\[(?<client>[^\\]]+)\\]", "project": "$client $missing" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    config.project_rules
+}
+
+#[test]
+fn first_matching_rule_assigns_the_project_from_capture_groups() {
+    let attributor = Attributor::new(&rules());
+    let found = |app: &str, title: &str, path: &str| attributor.attribute(app, title, path);
+
+    assert_eq!(
+        found("Code", "● main.rs - tick - Visual Studio Code", "C:\\Apps\\Code.exe"),
+        Some(Attribution { project: "tick".to_string(), ticket: None, rule: 0 })
+    );
+    // App and path patterns ignore case; title patterns don't
+    assert_eq!(
+        found("Windows Terminal", "vim ~/src/tick/README.md", "C:\\Program Files\\WINDOWSTERMINAL.EXE").unwrap().project,
+        "tick"
+    );
+    assert!(found("Windows Terminal", "vim ~/SRC/tick", "WindowsTerminal.exe").is_none());
+
+    let jira = found("Chrome", "TICK-142 Fix idle detection - Jira", "chrome.exe").unwrap();
+    assert_eq!(jira.project, "jira-TICK");
+    assert_eq!(jira.ticket.as_deref(), Some("TICK-142"));
+    assert_eq!(jira.rule, 2);
+
+    // Groups that don't exist insert nothing
+    assert_eq!(found("Slack", "[Acme] general", "slack.exe").unwrap().project, "Acme");
+    assert!(found("Slack", "general", "slack.exe").is_none());
+}
+
+#[test]
+fn invalid_patterns_are_reported_and_skipped() {
+    let json = r#"{ "version": 2, "project_rules": [
+        { "title": "(unclosed", "project": "x" },
+        { "app": "code", "project": " " }
+    ] }"#;
+    let Err(ConfigError::Invalid(problems)) = Config::from_json(json) else {
+        panic!("expected validation problems");
+    };
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert!(problems[0].starts_with("Project rule 1: title pattern is not a valid regex"));
+    assert_eq!(problems[1], "Project rule 2 has no project");
+
+    let rules = vec![
+        ProjectRule { title: Some("(unclosed".to_string()), project: "broken".to_string(), ..ProjectRule::default() },
+        ProjectRule { project: "fallback".to_string(), ..ProjectRule::default() },
+    ];
+    assert_eq!(Attributor::new(&rules).attribute("Code", "(unclosed", "").unwrap().rule, 1);
+}
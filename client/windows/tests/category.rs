@@ -0,0 +1,123 @@
+mod common;
+
+use tick::{
+    category::{self, AppCategory, Categorizer, Category, CategoryRule, ServerCategories, UrlPattern},
+    config::{Config, ConfigError},
+    remote::CategorySync,
+};
+
+const CAPABILITIES: &str = r#"{
+    "protocolVersion": 2,
+    "endpoints": { "logSession": "api/log-session", "categories": "api/categories" }
+}"#;
+
+#[test]
+fn the_built_in_ruleset_matches_the_server() {
+    assert_eq!(category::suggest("Code", None), Category::Productive);
+    assert_eq!(category::suggest("WindowsTerminal", None), Category::Productive);
+    assert_eq!(category::suggest("steam", None), Category::Distracting);
+    assert_eq!(category::suggest("chrome", None), Category::Neutral);
+    // Pages are checked with the same keywords, productive ones first
+    assert_eq!(category::suggest("chrome", Some("https://www.youtube.com/watch?v=1")), Category::Distracting);
+    assert_eq!(category::suggest("firefox", Some("https://github.com/tick/tick")), Category::Productive);
+    assert_eq!(category::suggest("helium", Some("https://www.coursera.org/learn/rust")), Category::Productive);
+    assert_eq!(category::suggest("helium", Some("https://news.ycombinator.com/")), Category::Neutral);
+    assert_eq!(category::suggest("Paint", None), Category::Uncategorized);
+}
+
+#[test]
+fn user_rules_beat_server_categories_which_beat_the_built_in_ones() {
+    let server = ServerCategories {
+        apps: vec![
+            AppCategory { name: "Slack".to_string(), category: Category::Distracting },
+            AppCategory { name: "Paint".to_string(), category: Category::Uncategorized },
+        ],
+        url_patterns: vec![UrlPattern {
+            pattern: "*youtube.com/watch*".to_string(),
+            category: Category::Productive,
+        }],
+    };
+    let rules = vec![
+        CategoryRule {
+            category: Category::Neutral,
+            app: Some("SLACK".to_string()),
+            url: None,
+        },
+        CategoryRule {
+            category: Category::Distracting,
+            app: Some("chrome".to_string()),
+            url: Some("https://www.youtube.com/shorts/*".to_string()),
+        },
+    ];
+    let categorizer = Categorizer::new(&rules, Some(&server));
+
+    assert_eq!(categorizer.categorize("slack", None), Category::Neutral);
+    assert_eq!(categorizer.categorize("chrome", Some("https://www.youtube.com/shorts/abc")), Category::Distracting);
+    assert_eq!(categorizer.categorize("chrome", Some("https://www.youtube.com/watch?v=1")), Category::Productive);
+    // A rule for a page only matches while that page is open
+    assert_eq!(categorizer.categorize("chrome", None), Category::Neutral);
+    // Apps the server left uncategorized fall through to the built-in ruleset
+    assert_eq!(categorizer.categorize("Paint", None), Category::Uncategorized);
+    assert_eq!(categorizer.categorize("Discord", None), Category::Productive);
+
+    let offline = Categorizer::new(&rules, None);
+    assert_eq!(offline.categorize("chrome", Some("https://www.youtube.com/watch?v=1")), Category::Distracting);
+}
+
+#[test]
+fn categories_read_like_the_servers_enum() {
+    assert_eq!(serde_json::to_string(&Category::Distracting).unwrap(), "\"distracting\"");
+    assert_eq!("Productive".parse::<Category>(), Ok(Category::Productive));
+    assert!("work".parse::<Category>().is_err());
+    assert_eq!(Category::default().to_string(), "uncategorized");
+}
+
+#[test]
+fn category_rules_need_an_app_or_a_url() {
+    let json = r#"{ "version": 2, "category_rules": [
+        { "category": "productive", "app": "Figma" },
+        { "category": "distracting" },
+        { "category": "neutral", "url": " " }
+    ] }"#;
+    match Config::from_json(json) {
+        Err(ConfigError::Invalid(problems)) => assert_eq!(
+            problems,
+            ["Category rule 2 needs an app or a URL", "Category rule 3: URL cannot be blank"]
+        ),
+        other => panic!("expected validation problems, got {:?}", other),
+    }
+    assert!(Config::from_json(r#"{ "version": 2, "category_rules": [{ "category": "work", "app": "Figma" }] }"#).is_err());
+}
+
+#[tokio::test]
+async fn server_categories_are_kept_for_the_server_they_came_from() {
+    let server = common::serve_with_headers(&[("ETag", "\"c1\"")], |request| match request.path.as_str() {
+        "/api/capabilities" => (200, CAPABILITIES.to_string()),
+        "/api/categories" if request.header("If-None-Match") == Some("\"c1\"") => (304, String::new()),
+        "/api/categories" => (
+            200,
+            r#"{"apps":[{"name":"Slack","category":"distracting"}],"urlPatterns":[{"pattern":"*.edu/*","category":"neutral"}]}"#
+                .to_string(),
+        ),
+        _ => (404, String::new()),
+    })
+    .await;
+    let dir = common::temp_dir("category", "sync");
+    let sync = CategorySync::new(&dir);
+    let expected = ServerCategories {
+        apps: vec![AppCategory { name: "Slack".to_string(), category: Category::Distracting }],
+        url_patterns: vec![UrlPattern { pattern: "*.edu/*".to_string(), category: Category::Neutral }],
+    };
+
+    assert_eq!(sync.refresh(&server.base_url, Some("sk_test")).await.unwrap(), Some(expected.clone()));
+    assert_eq!(sync.refresh(&server.base_url, Some("sk_test")).await.unwrap(), Some(expected.clone()));
+    assert_eq!(CategorySync::new(&dir).last_known(&server.base_url), Some(expected));
+    assert_eq!(sync.last_known("https://other.example.com"), None);
+    {
+        let requests = server.requests.lock().unwrap();
+        let fetches: Vec<_> = requests.iter().filter(|r| r.path == "/api/categories").collect();
+        assert_eq!(fetches.len(), 2);
+        assert_eq!(fetches[1].header("If-None-Match"), Some("\"c1\""));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use chrono::{DateTime, Duration, Utc};
+use tick::{
+    clock::{self, ClockOffset, TimeZoneWatch},
+    discovery::CapabilityCache,
+};
+
+fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+#[test]
+fn offset_is_measured_from_the_middle_of_the_round_trip() {
+    let sent = at("2026-10-18T10:00:00Z");
+    let received = at("2026-10-18T10:00:02Z");
+
+    let fast = ClockOffset::measure(at("2026-10-18T09:55:01Z"), sent, received);
+    assert_eq!(fast.offset, Duration::minutes(-5));
+    assert_eq!(fast.uncertainty, Duration::seconds(1));
+    assert!(fast.is_skewed());
+    assert_eq!(fast.to_string(), "5m 0s ahead of the server");
+    assert_eq!(fast.correct(at("2026-10-18T11:00:00Z")), at("2026-10-18T10:55:00Z"));
+
+    let close = ClockOffset::measure(at("2026-10-18T10:00:02Z"), sent, received);
+    assert!(!close.is_skewed());
+    assert_eq!(close.to_string(), "in sync with the server");
+    assert_eq!(close.correct(sent), sent);
+
+    let slow = ClockOffset::from_date_header("Sun, 18 Oct 2026 13:00:00 GMT", sent, sent).unwrap();
+    assert_eq!(slow.offset, Duration::hours(3) + Duration::milliseconds(500));
+    assert_eq!(slow.to_string(), "3h 0m behind the server");
+    assert!(ClockOffset::from_date_header("yesterday", sent, sent).is_none());
+}
+
+#[tokio::test]
+async fn uses_the_time_endpoint_when_the_server_has_one() {
+    let server_now = Utc::now() + Duration::hours(2);
+    let server = common::serve(move |request| match request.path.as_str() {
+        "/api/capabilities" => (200, r#"{"protocolVersion":2,"endpoints":{"time":"api/time"}}"#.to_string()),
+        "/api/time" => (200, format!(r#"{{"now":{}}}"#, server_now.timestamp_millis())),
+        _ => (404, String::new()),
+    })
+    .await;
+    let dir = common::temp_dir("clock", "endpoint");
+
+    let clock = clock::check(&server.base_url, &CapabilityCache::new(&dir)).await.unwrap();
+    assert!((clock.offset - Duration::hours(2)).abs() < Duration::seconds(5), "{:?}", clock);
+    assert!(clock.is_skewed());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn falls_back_to_the_date_header() {
+    let server = common::serve_with_headers(&[("Date", "Tue, 01 Jan 2036 00:00:00 GMT")], |_| (404, String::new())).await;
+    let dir = common::temp_dir("clock", "header");
+
+    let clock = clock::check(&server.base_url, &CapabilityCache::new(&dir)).await.unwrap();
+    let expected = at("2036-01-01T00:00:00.5Z") - Utc::now();
+    assert!((clock.offset - expected).abs() < Duration::seconds(5), "{:?}", clock);
+    assert_eq!(server.requests.lock().unwrap().last().unwrap().path, "/");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_time_zone_change_ends_the_session_in_the_old_zone() {
+    let mut watch = TimeZoneWatch::new(Ok::<_, ()>("Europe/Berlin".to_string()));
+    let mut closed = Vec::new();
+
+    assert!(!watch.check(Ok::<_, ()>("Europe/Berlin".to_string()), |zone| closed.push(zone.to_string())));
+    assert!(!watch.check(Err("lookup failed"), |zone| closed.push(zone.to_string())));
+    assert!(closed.is_empty());
+
+    assert!(watch.check(Ok::<_, ()>("America/New_York".to_string()), |zone| closed.push(zone.to_string())));
+    assert_eq!(closed, ["Europe/Berlin"]);
+    // The next session starts in the new zone
+    assert_eq!(watch.current(), "America/New_York");
+
+    assert_eq!(TimeZoneWatch::new(Err("lookup failed")).current(), "UTC");
+}
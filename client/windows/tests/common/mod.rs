@@ -0,0 +1,141 @@
+//! Helpers shared by the integration tests: a scratch directory per test, and a minimal
+//! HTTP/1.1 stand-in server for exercising the client against canned responses.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// An empty directory named after the test file's `prefix` and the test's `name`, so tests
+/// running in parallel never share one.
+pub fn temp_dir(prefix: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tick-{}-{}-{}", prefix, name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is JSON")
+    }
+}
+
+pub struct StandIn {
+    pub base_url: String,
+    pub requests: Arc<Mutex<Vec<Request>>>,
+}
+
+/// Serves every request with `handler` until the test ends.
+pub async fn serve<F>(handler: F) -> StandIn
+where
+    F: Fn(&Request) -> (u16, String) + Send + Sync + 'static,
+{
+    serve_with_headers(&[], handler).await
+}
+
+/// Like `serve`, adding `headers` to every response.
+pub async fn serve_with_headers<F>(headers: &'static [(&'static str, &'static str)], handler: F) -> StandIn
+where
+    F: Fn(&Request) -> (u16, String) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let handler = Arc::new(handler);
+
+    let recorded = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let handler = handler.clone();
+            let recorded = recorded.clone();
+            tokio::spawn(async move {
+                if let Some(request) = handle_connection(stream, headers, handler.as_ref()).await {
+                    recorded.lock().unwrap().push(request);
+                }
+            });
+        }
+    });
+
+    StandIn { base_url, requests }
+}
+
+async fn handle_connection<F>(
+    mut stream: TcpStream,
+    extra_headers: &[(&str, &str)],
+    handler: &F,
+) -> Option<Request>
+where
+    F: Fn(&Request) -> (u16, String),
+{
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = buf[head_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let request = Request { method, path, headers, body };
+    let (status, response_body) = handler(&request);
+    let extra: String = extra_headers
+        .iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    let response = format!(
+        "HTTP/1.1 {} Stand-In\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        response_body.len(),
+        extra,
+        response_body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    Some(request)
+}
@@ -0,0 +1,152 @@
+use tick::config::{self, Config, ConfigError, Theme, CURRENT_VERSION};
+use tick::secrets::{MemoryStore, SecretBackend, Secrets};
+
+#[test]
+fn default_config_round_trips() {
+    let config = Config::default();
+    let parsed = Config::from_json(&config.to_json()).unwrap();
+    assert_eq!(parsed, config);
+}
+
+#[test]
+fn customised_config_round_trips() {
+    let config = Config {
+        server_url: "https://tick.example.com/api/log-session".to_string(),
+        theme: Theme::Light,
+        ..Config::default()
+    };
+    let parsed = Config::from_json(&config.to_json()).unwrap();
+    assert_eq!(parsed, config);
+}
+
+#[test]
+fn access_token_is_never_serialized() {
+    let config = Config {
+        api_key: Some("sk_live_123".to_string()),
+        ..Config::default()
+    };
+    assert!(!config.to_json().contains("sk_live_123"));
+}
+
+#[test]
+fn migrates_unversioned_tracker_file() {
+    let legacy = r#"{ "server_url": "http://localhost:3000/api/log-session", "api_key": "sk_abc" }"#;
+    let config = Config::from_json(legacy).unwrap();
+    assert_eq!(config.version, CURRENT_VERSION);
+    assert_eq!(config.api_key.as_deref(), Some("sk_abc"));
+    assert_eq!(config.theme, Theme::Dark);
+}
+
+#[test]
+fn migrates_unversioned_configure_file() {
+    let legacy = r#"{ "server_url": "http://localhost:3000/api/log-session", "api_key": null, "theme": "light" }"#;
+    assert_eq!(Config::from_json(legacy).unwrap().theme, Theme::Light);
+
+    let blank_theme = r#"{ "server_url": "http://localhost:3000/api/log-session", "api_key": null, "theme": "" }"#;
+    assert_eq!(Config::from_json(blank_theme).unwrap().theme, Theme::Dark);
+}
+
+#[test]
+fn rejects_newer_versions() {
+    let future = format!(r#"{{ "version": {}, "server_url": "http://localhost" }}"#, CURRENT_VERSION + 1);
+    assert!(matches!(
+        Config::from_json(&future),
+        Err(ConfigError::UnsupportedVersion(v)) if v == CURRENT_VERSION + 1
+    ));
+}
+
+#[test]
+fn reports_every_validation_problem() {
+    let config = Config {
+        server_url: "ftp://example.com".to_string(),
+        api_key: Some("has space".to_string()),
+        ..Config::default()
+    };
+    match config.validate() {
+        Err(ConfigError::Invalid(problems)) => {
+            assert_eq!(problems.len(), 2);
+            assert!(problems[0].contains("http or https"));
+            assert!(problems[1].contains("whitespace"));
+        }
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_empty_endpoint() {
+    let config = Config {
+        server_url: "   ".to_string(),
+        ..Config::default()
+    };
+    let err = config.validate().unwrap_err().to_string();
+    assert_eq!(err, "Endpoint cannot be empty");
+}
+
+#[test]
+fn reports_syntax_error_position() {
+    let broken = "{\n  \"server_url\": \"http://localhost:3000\",\n  \"api_key\" \"sk_abc\"\n}";
+    match Config::from_json(broken) {
+        Err(ConfigError::Parse { line, column, .. }) => {
+            assert_eq!(line, 3);
+            assert!(column > 0);
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn recovers_readable_fields_from_broken_file() {
+    let broken = "{\n  \"server_url\": \"https://tick.example.com/api/log-session\",\n  \"api_key\": \"sk_abc\",\n  \"theme\": \"light\"\n  \"extra\": \n";
+    let recovered = Config::recover(broken);
+    assert_eq!(recovered.server_url, "https://tick.example.com/api/log-session");
+    assert_eq!(recovered.api_key.as_deref(), Some("sk_abc"));
+    assert_eq!(recovered.theme, Theme::Light);
+}
+
+#[test]
+fn recovery_falls_back_to_defaults() {
+    let recovered = Config::recover("not json at all");
+    assert_eq!(recovered, Config::default());
+}
+
+#[test]
+fn backup_preserves_original_file() {
+    let dir = std::env::temp_dir().join(format!("tick-config-backup-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.json");
+    std::fs::write(&path, "{ broken").unwrap();
+
+    let backup_path = config::backup(&path).unwrap();
+    assert_eq!(backup_path, dir.join("config.json.bak"));
+    assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), "{ broken");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ broken");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watcher_reports_only_changes() {
+    let dir = std::env::temp_dir().join(format!("tick-config-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.json");
+    Config::default().save(&path).unwrap();
+
+    let secrets = Secrets::with_stores(
+        Box::new(MemoryStore::new(SecretBackend::Keyring)),
+        Box::new(MemoryStore::new(SecretBackend::File)),
+    );
+    let mut watcher = config::ConfigWatcher::new(&path);
+    assert!(watcher.poll(&secrets).is_none());
+
+    let updated = Config {
+        server_url: "https://tick.example.com/api/log-session".to_string(),
+        ..Config::default()
+    };
+    // Make sure the modification time moves even on coarse-grained filesystems
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    updated.save(&path).unwrap();
+    assert_eq!(watcher.poll(&secrets).unwrap().unwrap(), updated);
+    assert!(watcher.poll(&secrets).is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use tick::device::{DeviceIdentity, LegacyLink};
+
+#[test]
+fn a_fresh_install_gets_an_id_and_nothing_to_link() {
+    let dir = common::temp_dir("device", "fresh");
+    let identity = DeviceIdentity::load_or_create(&dir, false).unwrap();
+    assert!(uuid::Uuid::parse_str(&identity.id).is_ok());
+    assert_eq!(identity.legacy_id, None);
+
+    // Later runs keep the ID, even when they think they are upgrading
+    assert_eq!(DeviceIdentity::load_or_create(&dir, false).unwrap(), identity);
+    assert_eq!(DeviceIdentity::load_or_create(&dir, true).unwrap(), identity);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn an_upgrade_carries_the_old_id_until_the_server_links_it() {
+    let dir = common::temp_dir("device", "upgrade");
+    let identity = DeviceIdentity::load_or_create(&dir, true).unwrap();
+    let legacy_id = identity.legacy_id.clone().expect("legacy ID");
+    assert!(legacy_id.contains('-'));
+    assert_eq!(DeviceIdentity::load_or_create(&dir, true).unwrap(), identity);
+
+    let link = LegacyLink::new(&dir, &identity);
+    assert_eq!(link.pending(), Some(legacy_id));
+    link.linked();
+    assert_eq!(link.pending(), None);
+    let linked = DeviceIdentity::load_or_create(&dir, true).unwrap();
+    assert_eq!(linked.id, identity.id);
+    assert_eq!(linked.legacy_id, None);
+
+    // Forgetting again, or in another process, changes nothing
+    link.linked();
+    DeviceIdentity::forget_legacy_id(&dir).unwrap();
+    assert_eq!(DeviceIdentity::load_or_create(&dir, false).unwrap(), linked);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn an_unreadable_identity_is_replaced() {
+    let dir = common::temp_dir("device", "unreadable");
+    std::fs::write(dir.join("device.json"), "{ \"id\": ").unwrap();
+    let identity = DeviceIdentity::load_or_create(&dir, false).unwrap();
+    assert!(uuid::Uuid::parse_str(&identity.id).is_ok());
+    assert_eq!(DeviceIdentity::load_or_create(&dir, false).unwrap(), identity);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,65 @@
+use tick::discovery::{Capabilities, CapabilityCache, ServerUrl};
+
+#[test]
+fn server_url_accepts_a_base_url_or_the_old_endpoint() {
+    for (configured, base, log_session) in [
+        ("https://tick.example.com", "https://tick.example.com/", "https://tick.example.com/api/log-session"),
+        (" https://example.com/tick/ ", "https://example.com/tick/", "https://example.com/tick/api/log-session"),
+        (
+            "http://localhost:3000/api/log-session",
+            "http://localhost:3000/",
+            "http://localhost:3000/api/log-session",
+        ),
+        (
+            "https://example.com/tick/api/log-session?team=a",
+            "https://example.com/tick/",
+            "https://example.com/tick/api/log-session?team=a",
+        ),
+    ] {
+        let server = ServerUrl::parse(configured).unwrap();
+        assert_eq!(server.base.as_str(), base, "{}", configured);
+        assert_eq!(server.log_session.as_str(), log_session, "{}", configured);
+    }
+    assert!(ServerUrl::parse("not a url").is_err());
+}
+
+#[test]
+fn endpoints_come_from_discovery_with_the_usual_ones_as_fallback() {
+    let server = ServerUrl::parse("https://example.com/tick").unwrap();
+    let legacy = Capabilities::legacy();
+    assert_eq!(server.log_session_url(&legacy).as_str(), "https://example.com/tick/api/log-session");
+    assert_eq!(server.log_sessions_url(&legacy), None);
+
+    let discovered: Capabilities = serde_json::from_str(
+        r#"{
+            "protocolVersion": 2,
+            "endpoints": { "logSession": "/api/v2/log-session", "logSessions": "api/v2/log-sessions" },
+            "features": { "compression": ["br", "gzip"], "batch": { "maxSessions": 50 }, "streaming": true }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(server.log_session_url(&discovered).as_str(), "https://example.com/tick/api/v2/log-session");
+    assert_eq!(
+        server.log_sessions_url(&discovered).unwrap().as_str(),
+        "https://example.com/tick/api/v2/log-sessions"
+    );
+    assert_eq!(discovered.compression(), Some(tick::wire::Compression::Gzip));
+    assert!(!discovered.features.titles);
+}
+
+#[test]
+fn discovery_results_are_cached_per_server() {
+    let dir = std::env::temp_dir().join(format!("tick-discovery-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let cache = CapabilityCache::new(&dir);
+    let work = ServerUrl::parse("https://work.example.com").unwrap().base;
+    let home = ServerUrl::parse("https://home.example.com").unwrap().base;
+
+    cache.put(&work, &Capabilities::legacy()).unwrap();
+    assert_eq!(CapabilityCache::new(&dir).get(&work), Some(Capabilities::legacy()));
+    assert_eq!(cache.get(&home), None);
+
+    cache.forget(&work).unwrap();
+    assert_eq!(cache.get(&work), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Duration, Utc};
+use std::path::Path;
+use tick::export::{export, parquet_schema, write_parquet, ExportFormat, ExportOptions};
+use tick::history::{History, HistoryEntry};
+use tick::session::SessionPayload;
+
+fn entry(app_name: &str, start: &str, end: &str, destinations: &[&str]) -> HistoryEntry {
+    HistoryEntry {
+        session: SessionPayload {
+            device_id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+            device_name: Some("Workstation".to_string()),
+            previous_device_id: None,
+            device_platform: "windows".to_string(),
+            app_name: app_name.to_string(),
+            title: None,
+            url: None,
+            editor: None,
+            tag: None,
+            ticket: None,
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            time_zone: "Europe/Berlin".to_string(),
+        },
+        category: None,
+        tags: Vec::new(),
+        destinations: destinations.iter().map(|d| d.to_string()).collect(),
+    }
+}
+
+/// A morning of sessions covering merging, redaction, quoting and line folding.
+fn fixture() -> Vec<HistoryEntry> {
+    let mut notes = entry(
+        "Notes, \"draft\"",
+        "2026-03-02T10:10:00+00:00",
+        "2026-03-02T10:40:00+00:00",
+        &["tick"],
+    );
+    notes.session.device_name = Some("Workstation in the second-floor meeting room at the Berlin office".to_string());
+    let mut review = entry("Code", "2026-03-02T09:27:00+00:00", "2026-03-02T10:00:00+00:00", &["tick", "work"]);
+    review.session.title = Some("review.rs - tick".to_string());
+    review.category = Some("Development".to_string());
+    review.tags = vec!["review".to_string()];
+    let mut code = entry("Code", "2026-03-02T09:00:00+00:00", "2026-03-02T09:25:00+00:00", &["tick"]);
+    code.session.title = Some("main.rs - tick".to_string());
+    code.category = Some("Development".to_string());
+    let mut banking = entry("Banking", "2026-03-02T10:00:00+00:00", "2026-03-02T10:05:00+00:00", &[]);
+    banking.session.title = Some("Account 1234".to_string());
+    vec![review, code, banking, notes, entry("Broken", "yesterday", "today", &["tick"])]
+}
+
+fn options(merge_gap: Option<Duration>) -> ExportOptions {
+    ExportOptions {
+        merge_gap,
+        redacted_apps: vec!["banking".to_string()],
+        generated_at: "2026-03-03T08:00:00Z".parse().unwrap(),
+    }
+}
+
+/// Compares against `tests/golden/<name>`; run with `UPDATE_GOLDEN=1` to rewrite the file.
+fn assert_golden(name: &str, expected: &str, actual: &str) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let path = Path::new(file!()).parent().unwrap().join("golden").join(name);
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+    assert_eq!(actual, expected, "output differs from tests/golden/{}", name);
+}
+
+#[test]
+fn exports_csv() {
+    let csv = export(&fixture(), ExportFormat::Csv, &options(None));
+    assert_golden("sessions.csv", include_str!("golden/sessions.csv"), &csv);
+}
+
+#[test]
+fn exports_json() {
+    let json = export(&fixture(), ExportFormat::Json, &options(None));
+    assert_golden("sessions.json", include_str!("golden/sessions.json"), &json);
+}
+
+#[test]
+fn exports_one_event_per_session() {
+    let ics = export(&fixture(), ExportFormat::Ics, &options(None));
+    assert_golden("sessions.ics", include_str!("golden/sessions.ics"), &ics);
+}
+
+#[test]
+fn exports_merged_blocks() {
+    let ics = export(&fixture(), ExportFormat::Ics, &options(Some(Duration::minutes(5))));
+    assert_golden("blocks.ics", include_str!("golden/blocks.ics"), &ics);
+}
+
+#[test]
+fn redacted_apps_never_appear_in_exports() {
+    for format in ExportFormat::ALL {
+        let out = export(&fixture(), format, &options(Some(Duration::minutes(5))));
+        assert!(!out.contains("Banking"), "{} export leaks a redacted app", format);
+        assert!(!out.contains("Account 1234"), "{} export leaks a redacted title", format);
+        assert!(out.contains("Private"));
+    }
+}
+
+#[test]
+fn history_reads_sessions_in_a_range_across_months() {
+    let dir = std::env::temp_dir().join(format!("tick-export-history-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let history = History::new(&dir);
+    for (app, start) in [
+        ("Late", "2026-03-01T08:00:00+00:00"),
+        ("Early", "2026-02-28T23:30:00+00:00"),
+        ("Before", "2026-02-27T12:00:00+00:00"),
+        ("After", "2026-03-02T00:00:00+00:00"),
+    ] {
+        history.record(&entry(app, start, start, &[])).unwrap();
+    }
+
+    let from: DateTime<Utc> = "2026-02-28T00:00:00Z".parse().unwrap();
+    let until: DateTime<Utc> = "2026-03-02T00:00:00Z".parse().unwrap();
+    let apps: Vec<_> = history
+        .between(from, until)
+        .into_iter()
+        .map(|e| e.session.app_name)
+        .collect();
+    assert_eq!(apps, vec!["Early", "Late"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parquet_is_partitioned_by_day_with_typed_columns() {
+    use arrow_array::{Array, Int64Array, StringArray, TimestampMicrosecondArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let dir = std::env::temp_dir().join(format!("tick-export-parquet-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    // Partitions follow the local day; midday UTC is the same day from UTC-11 to UTC+11
+    let mut code = entry("Code", "2026-03-02T11:00:00+00:00", "2026-03-02T11:25:00+00:00", &["tick"]);
+    code.session.title = Some("main.rs - tick".to_string());
+    let mut banking = entry("Banking", "2026-03-02T12:00:00+00:00", "2026-03-02T12:05:00+00:00", &[]);
+    banking.session.title = Some("Account 1234".to_string());
+    let entries = vec![
+        code,
+        entry("Code", "2026-03-02T11:27:00+00:00", "2026-03-02T12:00:00+00:00", &["tick", "work"]),
+        banking,
+        entry("Code", "2026-03-03T12:00:00+00:00", "2026-03-03T12:30:00+00:00", &["tick"]),
+    ];
+
+    let files = write_parquet(&entries, &options(None), &dir).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            dir.join("date=2026-03-02").join("sessions.parquet"),
+            dir.join("date=2026-03-03").join("sessions.parquet"),
+        ]
+    );
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&files[0]).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.schema().as_ref(), &parquet_schema());
+    assert_eq!(batch.num_rows(), 3);
+
+    let column = |name: &str| batch.column(batch.schema().index_of(name).unwrap()).clone();
+    let apps = column("app_name");
+    let apps = apps.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(apps.iter().collect::<Vec<_>>(), vec![Some("Code"), Some("Code"), Some("Private")]);
+    let titles = column("title");
+    let titles = titles.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(titles.value(0), "main.rs - tick");
+    assert!(titles.is_null(2));
+    let starts = column("start");
+    let starts = starts.as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+    let first: DateTime<Utc> = "2026-03-02T11:00:00Z".parse().unwrap();
+    assert_eq!(starts.value(0), first.timestamp_micros());
+    let durations = column("duration_secs");
+    let durations = durations.as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(durations.value(0), 1500);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
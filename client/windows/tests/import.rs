@@ -0,0 +1,196 @@
+use std::path::Path;
+use tick::config::Config;
+use tick::history::HistoryEntry;
+use tick::import::{parse, plan, ImportError, ImportOptions, ImportSource};
+
+fn options() -> ImportOptions {
+    ImportOptions {
+        device_id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        device_name: Some("Workstation".to_string()),
+        time_zone: "Europe/Berlin".to_string(),
+    }
+}
+
+/// `(app, title, start, end)` of each entry.
+fn summary(entries: &[HistoryEntry]) -> Vec<(String, Option<String>, String, String)> {
+    entries
+        .iter()
+        .map(|e| {
+            (
+                e.session.app_name.clone(),
+                e.session.title.clone(),
+                e.session.start_time.clone(),
+                e.session.end_time.clone(),
+            )
+        })
+        .collect()
+}
+
+const ACTIVITYWATCH: &str = r#"{
+  "buckets": {
+    "aw-watcher-window_workstation": {
+      "id": "aw-watcher-window_workstation",
+      "type": "currentwindow",
+      "client": "aw-watcher-window",
+      "hostname": "workstation",
+      "events": [
+        { "id": 2, "timestamp": "2026-03-02T09:10:00+00:00", "duration": 90.5, "data": { "app": "firefox.exe", "title": "Docs" } },
+        { "id": 1, "timestamp": "2026-03-02T09:00:00+00:00", "duration": 600, "data": { "app": "Code.exe", "title": "main.rs" } },
+        { "id": 3, "timestamp": "2026-03-02T09:20:00+00:00", "duration": 0, "data": { "app": "explorer.exe", "title": "" } }
+      ]
+    },
+    "aw-watcher-afk_workstation": {
+      "id": "aw-watcher-afk_workstation",
+      "type": "afkstatus",
+      "events": [
+        { "id": 1, "timestamp": "2026-03-02T09:00:00+00:00", "duration": 1200, "data": { "status": "not-afk" } }
+      ]
+    }
+  }
+}"#;
+
+#[test]
+fn reads_window_buckets_from_activitywatch() {
+    let entries = parse(ImportSource::ActivityWatch, ACTIVITYWATCH, &options()).unwrap();
+    assert_eq!(
+        summary(&entries),
+        vec![
+            (
+                "Code.exe".to_string(),
+                Some("main.rs".to_string()),
+                "2026-03-02T09:00:00+00:00".to_string(),
+                "2026-03-02T09:10:00+00:00".to_string(),
+            ),
+            (
+                "firefox.exe".to_string(),
+                Some("Docs".to_string()),
+                "2026-03-02T09:10:00+00:00".to_string(),
+                "2026-03-02T09:11:30.500+00:00".to_string(),
+            ),
+        ]
+    );
+    assert_eq!(entries[0].session.device_id, options().device_id);
+    assert_eq!(entries[0].session.time_zone, "Europe/Berlin");
+}
+
+#[test]
+fn lays_out_rescuetime_intervals_in_local_time() {
+    let csv = "\u{feff}Date,Time Spent (seconds),Number of People,Activity,Category,Productivity\n\
+        2026-03-02T09:00:00,240,1,Visual Studio Code,Editing & IDEs,2\n\
+        2026-03-02T09:00:00,60,1,slack,Instant Message,0\n\
+        2026-03-02T09:05:00,300,1,Visual Studio Code,Editing & IDEs,2\n";
+    let entries = parse(ImportSource::RescueTime, csv, &options()).unwrap();
+    let starts: Vec<_> = summary(&entries).into_iter().map(|(app, _, start, end)| (app, start, end)).collect();
+    assert_eq!(
+        starts,
+        vec![
+            (
+                "Visual Studio Code".to_string(),
+                "2026-03-02T08:00:00+00:00".to_string(),
+                "2026-03-02T08:04:00+00:00".to_string(),
+            ),
+            ("slack".to_string(), "2026-03-02T08:04:00+00:00".to_string(), "2026-03-02T08:05:00+00:00".to_string()),
+            (
+                "Visual Studio Code".to_string(),
+                "2026-03-02T08:05:00+00:00".to_string(),
+                "2026-03-02T08:10:00+00:00".to_string(),
+            ),
+        ]
+    );
+    assert_eq!(entries[0].category.as_deref(), Some("Editing & IDEs"));
+}
+
+#[test]
+fn maps_toggl_projects_descriptions_and_tags() {
+    let csv = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+        Ana,ana@example.com,Acme,Website,,\"Review, part 2\",Yes,2026-07-01,23:30:00,2026-07-02,00:15:00,00:45:00,\"client, review\"\n\
+        Ana,ana@example.com,,,,Planning,No,2026-07-02,09:00:00,2026-07-02,09:30:00,00:30:00,\n";
+    let entries = parse(ImportSource::Toggl, csv, &options()).unwrap();
+    assert_eq!(
+        summary(&entries),
+        vec![
+            (
+                "Website".to_string(),
+                Some("Review, part 2".to_string()),
+                "2026-07-01T21:30:00+00:00".to_string(),
+                "2026-07-01T22:15:00+00:00".to_string(),
+            ),
+            (
+                "Toggl".to_string(),
+                Some("Planning".to_string()),
+                "2026-07-02T07:00:00+00:00".to_string(),
+                "2026-07-02T07:30:00+00:00".to_string(),
+            ),
+        ]
+    );
+    assert_eq!(entries[0].tags, vec!["client", "review"]);
+}
+
+#[test]
+fn reports_the_row_that_could_not_be_read() {
+    let csv = "Date,Time Spent (seconds),Activity\n2026-03-02T09:00:00,60,Code\nyesterday,60,Code\n";
+    match parse(ImportSource::RescueTime, csv, &options()) {
+        Err(ImportError::Row { row: 3, message }) => assert!(message.contains("yesterday")),
+        other => panic!("expected a row error, got {:?}", other.map(|e| e.len())),
+    }
+
+    let mut bad_zone = options();
+    bad_zone.time_zone = "Mars/Olympus".to_string();
+    assert!(matches!(
+        parse(ImportSource::Toggl, "", &bad_zone),
+        Err(ImportError::UnknownTimeZone(_))
+    ));
+}
+
+#[test]
+fn detects_the_source_of_a_file() {
+    assert_eq!(
+        ImportSource::detect(Path::new("export.json"), "{}"),
+        Some(ImportSource::ActivityWatch)
+    );
+    assert_eq!(
+        ImportSource::detect(Path::new("rt.csv"), "Date,Time Spent (seconds),Activity\n"),
+        Some(ImportSource::RescueTime)
+    );
+    assert_eq!(
+        ImportSource::detect(Path::new("toggl.csv"), "\u{feff}User,Start date,Start time,End date,End time\n"),
+        Some(ImportSource::Toggl)
+    );
+    assert_eq!(ImportSource::detect(Path::new("other.csv"), "a,b\n"), None);
+}
+
+#[test]
+fn skips_sessions_already_recorded_or_excluded() {
+    let aw = r#"[
+        { "timestamp": "2026-03-02T09:00:00+00:00", "duration": 600, "data": { "app": "Code", "title": "a" } },
+        { "timestamp": "2026-03-02T09:10:00+00:00", "duration": 600, "data": { "app": "Code", "title": "b" } },
+        { "timestamp": "2026-03-02T09:12:00+00:00", "duration": 120, "data": { "app": "Code", "title": "b" } },
+        { "timestamp": "2026-03-02T09:20:00+00:00", "duration": 600, "data": { "app": "KeePass", "title": "" } },
+        { "timestamp": "2026-03-02T09:30:00+00:00", "duration": 600, "data": { "app": "Slack", "title": "" } }
+    ]"#;
+    let parsed = parse(ImportSource::ActivityWatch, aw, &options()).unwrap();
+
+    // Tick itself recorded 09:00-09:08; a session of another device does not count
+    let mut existing = parse(
+        ImportSource::ActivityWatch,
+        r#"[{ "timestamp": "2026-03-02T09:00:00+00:00", "duration": 480, "data": { "app": "Visual Studio Code" } }]"#,
+        &options(),
+    )
+    .unwrap();
+    let mut other_device = existing[0].clone();
+    other_device.session.device_id = "laptop".to_string();
+    other_device.session.start_time = "2026-03-02T09:30:00+00:00".to_string();
+    other_device.session.end_time = "2026-03-02T09:40:00+00:00".to_string();
+    existing.push(other_device);
+
+    let config = Config {
+        excluded_apps: vec!["keepass".to_string()],
+        ..Config::default()
+    };
+    let plan = plan(parsed, &existing, &config);
+    let kept: Vec<_> = plan.sessions.iter().map(|e| e.session.title.clone().unwrap_or_default()).collect();
+    assert_eq!(kept, vec!["b", ""]);
+    assert_eq!(plan.sessions[1].session.app_name, "Slack");
+    assert_eq!(plan.duplicates, 2);
+    assert_eq!(plan.excluded, 1);
+}
@@ -0,0 +1,65 @@
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::path::Path;
+use tick::{
+    agent::BrowserEvent,
+    native::{self, Browser, FIREFOX_EXTENSION_ID, HOST_NAME},
+};
+
+#[test]
+fn messages_are_length_prefixed_json() {
+    let mut buf = Vec::new();
+    let event = BrowserEvent::Tab { url: "https://docs.rs/serde".to_string(), title: "serde - Rust".to_string() };
+    native::write_message(&mut buf, &event).unwrap();
+    native::write_message(&mut buf, &BrowserEvent::None).unwrap();
+    let length = u32::from_ne_bytes(buf[..4].try_into().unwrap()) as usize;
+    assert_eq!(
+        serde_json::from_slice::<Value>(&buf[4..4 + length]).unwrap(),
+        json!({"type": "tab", "url": "https://docs.rs/serde", "title": "serde - Rust"})
+    );
+
+    let mut reader = Cursor::new(buf);
+    assert_eq!(native::read_message::<BrowserEvent>(&mut reader).unwrap(), Some(event));
+    assert_eq!(native::read_message::<BrowserEvent>(&mut reader).unwrap(), Some(BrowserEvent::None));
+    // The browser closes standard input when the extension disconnects
+    assert_eq!(native::read_message::<BrowserEvent>(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn oversized_and_truncated_messages_are_errors() {
+    let mut oversized = (2u32 * 1024 * 1024).to_ne_bytes().to_vec();
+    oversized.extend_from_slice(b"{}");
+    assert!(native::read_message::<Value>(&mut Cursor::new(oversized)).is_err());
+
+    let mut truncated = 10u32.to_ne_bytes().to_vec();
+    truncated.extend_from_slice(b"{\"ty");
+    assert!(native::read_message::<Value>(&mut Cursor::new(truncated)).is_err());
+}
+
+#[test]
+fn manifests_allow_only_the_extension() {
+    let host = Path::new("C:/Program Files/Tick/browser-host.exe");
+    let ids = vec!["abcdefghijklmnopabcdefghijklmnop".to_string()];
+
+    let chrome = native::manifest(Browser::Chrome, host, &ids).unwrap();
+    assert_eq!(chrome["name"], HOST_NAME);
+    assert_eq!(chrome["type"], "stdio");
+    assert_eq!(chrome["path"], "C:/Program Files/Tick/browser-host.exe");
+    assert_eq!(chrome["allowed_origins"], json!(["chrome-extension://abcdefghijklmnopabcdefghijklmnop/"]));
+    assert!(native::manifest(Browser::Edge, host, &[]).is_none());
+
+    let firefox = native::manifest(Browser::Firefox, host, &[]).unwrap();
+    assert_eq!(firefox["allowed_extensions"], json!([FIREFOX_EXTENSION_ID]));
+    assert!(firefox.get("allowed_origins").is_none());
+}
+
+#[test]
+fn recognizes_browsers_and_sites() {
+    assert!(native::is_browser("msedge.exe"));
+    assert!(native::is_browser("Firefox"));
+    assert!(!native::is_browser("Code.exe"));
+
+    assert_eq!(native::site("https://www.youtube.com/watch?v=1").as_deref(), Some("youtube.com"));
+    assert_eq!(native::site("https://mail.google.com/mail/u/0"), Some("mail.google.com".to_string()));
+    assert_eq!(native::site("not a url"), None);
+}
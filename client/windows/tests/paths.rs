@@ -0,0 +1,87 @@
+mod common;
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use tick::paths::{self, Paths, PORTABLE_MARKER};
+
+fn args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[test]
+fn config_flag_is_found_in_either_form() {
+    assert_eq!(paths::config_arg(args(&["--config", "work.json"])), Some(PathBuf::from("work.json")));
+    assert_eq!(
+        paths::config_arg(args(&["--print", "--config=C:\\tick\\work.json"])),
+        Some(PathBuf::from("C:\\tick\\work.json"))
+    );
+    assert_eq!(paths::config_arg(args(&["--print", "--configure"])), None);
+    assert_eq!(paths::config_arg(args(&["--config"])), None);
+    assert_eq!(paths::config_arg(args(&[])), None);
+}
+
+#[test]
+fn sibling_binaries_get_the_same_config() {
+    let dir = common::temp_dir("paths", "forward");
+    let exe_dir = dir.join("bin");
+    std::fs::create_dir_all(&exe_dir).unwrap();
+    std::fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+    let config = dir.join("work.json");
+
+    let paths = Paths::resolve_in(&exe_dir, Some(config.clone())).unwrap();
+    assert_eq!(paths::config_arg(paths.forward_args()), Some(config));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn portable_installs_keep_everything_in_the_install_folder() {
+    let dir = common::temp_dir("paths", "portable");
+    std::fs::write(dir.join(PORTABLE_MARKER), "").unwrap();
+    std::fs::write(dir.join("config.json"), "{}").unwrap();
+
+    let paths = Paths::resolve_in(&dir, None).unwrap();
+    assert!(paths.portable);
+    assert_eq!(paths.config_file, dir.join("config.json"));
+    assert_eq!(paths.data_dir, dir.join("data"));
+    assert!(paths.data_dir.is_dir());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_legacy_config_is_copied_once() {
+    let dir = common::temp_dir("paths", "migrate");
+    let exe_dir = dir.join("install");
+    std::fs::create_dir_all(&exe_dir).unwrap();
+    let config_file = dir.join("config.json");
+
+    // Nothing to migrate
+    paths::migrate_legacy_config(&exe_dir, &config_file).unwrap();
+    assert!(!config_file.exists());
+
+    std::fs::write(exe_dir.join("config.json"), "legacy").unwrap();
+    paths::migrate_legacy_config(&exe_dir, &config_file).unwrap();
+    assert_eq!(std::fs::read_to_string(&config_file).unwrap(), "legacy");
+    assert!(exe_dir.join("config.json").exists());
+
+    // Settings changed since are not overwritten
+    std::fs::write(&config_file, "current").unwrap();
+    paths::migrate_legacy_config(&exe_dir, &config_file).unwrap();
+    assert_eq!(std::fs::read_to_string(&config_file).unwrap(), "current");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_a_config_next_to_the_executables_marks_an_upgrade() {
+    let dir = common::temp_dir("paths", "upgrade");
+    let exe_dir = dir.join("install");
+    std::fs::create_dir_all(&exe_dir).unwrap();
+    std::fs::write(exe_dir.join(PORTABLE_MARKER), "").unwrap();
+
+    let paths = Paths::resolve_in(&exe_dir, Some(dir.join("config.json"))).unwrap();
+    std::fs::write(&paths.config_file, "{}").unwrap();
+    assert!(!paths.has_legacy_config());
+
+    std::fs::write(exe_dir.join("config.json"), "{}").unwrap();
+    assert!(paths.has_legacy_config());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use tick::{
+    config::{Config, ConfigError},
+    policy::{Policy, PolicyField},
+    sink::SinkKind,
+};
+
+#[test]
+fn a_missing_policy_file_is_no_policy() {
+    let dir = common::temp_dir("policy", "missing");
+    assert_eq!(Policy::load(&dir.join("policy.json")), Ok(None));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_policy_file_that_does_not_load_is_an_error() {
+    let dir = common::temp_dir("policy", "invalid");
+    let path = dir.join("policy.json");
+    for content in [
+        r#"{ "server_url": "https://tick.example.com", "idle_treshold_secs": 300 }"#,
+        r#"{ "server_url": "https://tick.example.com", "#,
+        r#"{ "idle_threshold_secs": "five minutes" }"#,
+        r#"{ "idle_threshold_secs": 30 }"#,
+        r#"{ "excluded_apps": ["Steam", " "] }"#,
+        r#"{ "server_url": " " }"#,
+        r#"{ "server_url": "tick.example.com" }"#,
+        r#"{ "server_url": "ftp://tick.example.com" }"#,
+    ] {
+        std::fs::write(&path, content).unwrap();
+        let error = Policy::load(&path).unwrap_err();
+        assert!(error.starts_with("Policy file"), "{}", error);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_policy_locks_the_fields_it_sets() {
+    let dir = common::temp_dir("policy", "valid");
+    let path = dir.join("policy.json");
+    std::fs::write(&path, r#"{ "server_url": "https://tick.example.com", "idle_threshold_secs": 600 }"#).unwrap();
+
+    let policy = Policy::load(&path).unwrap().unwrap();
+    assert_eq!(policy.locked_fields(), [PolicyField::ServerUrl, PolicyField::IdleThreshold]);
+    assert!(!policy.is_locked(PolicyField::ExcludedApps));
+    assert!(Policy::default().locked_fields().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn applying_a_policy_overrides_values_and_adds_exclusions() {
+    let mut config = Config {
+        server_url: "http://localhost:3000".to_string(),
+        excluded_apps: vec!["steam".to_string(), "Signal".to_string()],
+        idle_threshold_secs: None,
+        ..Config::default()
+    };
+    let policy = Policy {
+        server_url: Some("https://tick.example.com".to_string()),
+        excluded_apps: vec!["Steam".to_string(), "KeePass".to_string()],
+        idle_threshold_secs: Some(900),
+    };
+    policy.apply(&mut config);
+
+    assert_eq!(config.server_url, "https://tick.example.com");
+    assert_eq!(config.excluded_apps, ["steam", "Signal", "KeePass"]);
+    assert_eq!(config.idle_threshold_secs, Some(900));
+
+    // Without a policy the user's values stay
+    let before = config.clone();
+    Policy::default().apply(&mut config);
+    assert_eq!(config, before);
+}
+
+fn managed() -> Policy {
+    Policy {
+        server_url: Some("https://tick.example.com".to_string()),
+        ..Policy::default()
+    }
+}
+
+#[test]
+fn a_managed_server_applies_to_every_tick_sink() {
+    let mut config = Config::from_json(
+        r#"{ "version": 2, "sinks": [
+            { "type": "tick" },
+            { "type": "tick", "name": "home", "server_url": "https://home.example.com", "api_key": "sk_home" },
+            { "type": "jsonl", "path": "sessions.jsonl" }
+        ] }"#,
+    )
+    .unwrap();
+    managed().apply(&mut config);
+
+    for sink in &config.sinks[..2] {
+        match &sink.kind {
+            SinkKind::Tick { server_url, api_key, api_key_ref, .. } => {
+                assert_eq!(*server_url, None);
+                assert_eq!(*api_key, None);
+                assert_eq!(*api_key_ref, None);
+            }
+            other => panic!("expected a Tick sink, got {:?}", other),
+        }
+    }
+    assert_eq!(config.server_url, "https://tick.example.com");
+}
+
+#[test]
+fn a_managed_server_cannot_be_left_out_of_the_sinks() {
+    for sinks in [r#"[]"#, r#"[{ "type": "jsonl", "path": "sessions.jsonl" }]"#] {
+        let config = Config::from_json(&format!(r#"{{ "version": 2, "sinks": {} }}"#, sinks)).unwrap();
+        match managed().check(&config) {
+            Err(ConfigError::Invalid(problems)) => assert!(problems[0].contains("managed by policy")),
+            other => panic!("expected the config to be refused, got {:?}", other),
+        }
+        assert!(Policy::default().check(&config).is_ok());
+    }
+    assert!(managed().check(&Config::default()).is_ok());
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use tick::probe::{test_connection, ProbeOutcome};
+
+/// Answers like the Tick server's `POST /api/log-session` for a zero-length session.
+fn tick_server(request: &common::Request) -> (u16, String) {
+    if request.path != "/api/log-session" {
+        return (404, "NOT_FOUND".to_string());
+    }
+    if request.header("authorization") != Some("Bearer sk_good") {
+        return (401, r#"{"success":false,"error":"Unauthorized"}"#.to_string());
+    }
+    (200, r#"{"success":false,"error":"startTime must be before endTime"}"#.to_string())
+}
+
+#[tokio::test]
+async fn connects_with_valid_endpoint_and_token() {
+    let server = common::serve(tick_server).await;
+    let url = format!("{}/api/log-session", server.base_url);
+
+    assert_eq!(test_connection(&url, Some("sk_good")).await, ProbeOutcome::Connected);
+
+    let requests = server.requests.lock().unwrap();
+    assert_eq!((requests[0].method.as_str(), requests[0].path.as_str()), ("GET", "/api/capabilities"));
+    assert_eq!(requests[1].method, "POST");
+    let body = requests[1].json();
+    assert_eq!(body["startTime"], body["endTime"]);
+}
+
+#[tokio::test]
+async fn connects_to_the_endpoint_named_by_discovery() {
+    let server = common::serve(|request| match request.path.as_str() {
+        "/api/capabilities" => (200, r#"{"protocolVersion":2,"endpoints":{"logSession":"api/v2/log-session"}}"#.to_string()),
+        "/api/v2/log-session" => (200, r#"{"success":false,"error":"startTime must be before endTime"}"#.to_string()),
+        _ => (404, "NOT_FOUND".to_string()),
+    })
+    .await;
+
+    assert_eq!(test_connection(&server.base_url, None).await, ProbeOutcome::Connected);
+}
+
+#[tokio::test]
+async fn reports_invalid_token() {
+    let server = common::serve(tick_server).await;
+    let url = format!("{}/api/log-session", server.base_url);
+
+    assert_eq!(test_connection(&url, Some("sk_revoked")).await, ProbeOutcome::InvalidKey);
+    assert_eq!(test_connection(&url, None).await, ProbeOutcome::InvalidKey);
+}
+
+#[tokio::test]
+async fn reports_wrong_path() {
+    let server = common::serve(tick_server).await;
+    let url = format!("{}/api/log-sessions", server.base_url);
+
+    assert!(matches!(
+        test_connection(&url, Some("sk_good")).await,
+        ProbeOutcome::WrongPath(status) if status.as_u16() == 404
+    ));
+}
+
+#[tokio::test]
+async fn reports_version_mismatch() {
+    let server = common::serve(|_| (422, r#"{"type":"validation","on":"body"}"#.to_string())).await;
+    let url = format!("{}/api/log-session", server.base_url);
+    assert!(matches!(test_connection(&url, None).await, ProbeOutcome::VersionMismatch(_)));
+
+    let not_tick = common::serve(|_| (200, "<html>hello</html>".to_string())).await;
+    let url = format!("{}/api/log-session", not_tick.base_url);
+    assert!(matches!(test_connection(&url, None).await, ProbeOutcome::VersionMismatch(_)));
+}
+
+#[tokio::test]
+async fn reports_unreachable_server() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api/log-session", listener.local_addr().unwrap());
+    drop(listener);
+
+    assert!(matches!(test_connection(&url, None).await, ProbeOutcome::Unreachable(_)));
+}
+
+#[tokio::test]
+async fn reports_invalid_url() {
+    assert!(matches!(test_connection("localhost:3000", None).await, ProbeOutcome::InvalidUrl(_)));
+    assert!(matches!(test_connection("not a url", None).await, ProbeOutcome::InvalidUrl(_)));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tick::{
+    config::Config,
+    policy::Policy,
+    remote::{RemoteField, RemoteSettings, SettingsSync},
+};
+
+const CAPABILITIES: &str = r#"{
+    "protocolVersion": 2,
+    "endpoints": { "logSession": "api/log-session", "settings": "api/settings" }
+}"#;
+
+#[test]
+fn policy_beats_server_settings_which_beat_the_users_own() {
+    let mut config = Config {
+        excluded_apps: vec!["Signal".to_string()],
+        idle_threshold_secs: Some(600),
+        ..Config::default()
+    };
+    let remote = RemoteSettings {
+        excluded_apps: vec!["signal".to_string(), "Steam".to_string()],
+        idle_threshold_secs: Some(300),
+    };
+    remote.apply(&mut config);
+    assert_eq!(config.excluded_apps, ["Signal", "Steam"]);
+    assert_eq!(config.idle_threshold_secs, Some(300));
+    assert_eq!(remote.managed_fields(), [RemoteField::ExcludedApps, RemoteField::IdleThreshold]);
+
+    let policy = Policy {
+        idle_threshold_secs: Some(900),
+        ..Policy::default()
+    };
+    policy.apply(&mut config);
+    assert_eq!(config.idle_threshold_secs, Some(900));
+}
+
+#[tokio::test]
+async fn settings_are_revalidated_with_their_etag_and_kept_while_offline() {
+    let down = Arc::new(AtomicBool::new(false));
+    let server_down = down.clone();
+    let server = common::serve_with_headers(&[("ETag", "\"v1\"")], move |request| {
+        match request.path.as_str() {
+            "/api/capabilities" => (200, CAPABILITIES.to_string()),
+            _ if server_down.load(Ordering::SeqCst) => (503, String::new()),
+            "/api/settings?deviceId=device-1" if request.header("If-None-Match") == Some("\"v1\"") => {
+                (304, String::new())
+            }
+            "/api/settings?deviceId=device-1" => (200, r#"{"excludedApps":["Steam"],"idleThresholdSecs":300}"#.to_string()),
+            _ => (404, String::new()),
+        }
+    })
+    .await;
+    let dir = common::temp_dir("remote", "etag");
+    let sync = SettingsSync::new(&dir);
+    let expected = RemoteSettings {
+        excluded_apps: vec!["Steam".to_string()],
+        idle_threshold_secs: Some(300),
+    };
+
+    let fetched = sync.refresh(&server.base_url, Some("sk_test"), "device-1").await.unwrap();
+    assert_eq!(fetched.as_ref(), Some(&expected));
+    let revalidated = sync.refresh(&server.base_url, Some("sk_test"), "device-1").await.unwrap();
+    assert_eq!(revalidated.as_ref(), Some(&expected));
+    {
+        let requests = server.requests.lock().unwrap();
+        let settings: Vec<_> = requests.iter().filter(|r| r.path.starts_with("/api/settings")).collect();
+        assert_eq!(settings.len(), 2);
+        assert_eq!(settings[0].header("If-None-Match"), None);
+        assert_eq!(settings[0].header("Authorization"), Some("Bearer sk_test"));
+        assert_eq!(settings[1].header("If-None-Match"), Some("\"v1\""));
+        // Discovery is cached between refreshes
+        assert_eq!(requests.iter().filter(|r| r.path == "/api/capabilities").count(), 1);
+    }
+
+    down.store(true, Ordering::SeqCst);
+    assert!(sync.refresh(&server.base_url, Some("sk_test"), "device-1").await.is_err());
+    assert_eq!(SettingsSync::new(&dir).last_known(&server.base_url), Some(expected));
+    assert_eq!(sync.last_known("https://other.example.com"), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn servers_without_a_settings_endpoint_manage_nothing() {
+    let server = common::serve(|request| match request.path.as_str() {
+        "/api/capabilities" => (200, r#"{"protocolVersion":2}"#.to_string()),
+        _ => (404, String::new()),
+    })
+    .await;
+    let dir = common::temp_dir("remote", "none");
+    let sync = SettingsSync::new(&dir);
+
+    assert_eq!(sync.refresh(&server.base_url, None, "device-1").await.unwrap(), None);
+    assert_eq!(sync.last_known(&server.base_url), None);
+    let requests = server.requests.lock().unwrap();
+    assert!(requests.iter().all(|r| !r.path.starts_with("/api/settings")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,86 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use tick::config::Config;
+use tick::routing::{route, RouteRule};
+
+/// 2026-03-02 is a Monday.
+fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 3, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn work_rules() -> Vec<RouteRule> {
+    vec![
+        RouteRule {
+            to: vec!["work".to_string()],
+            apps: vec!["Code".to_string(), "Slack".to_string()],
+            days: vec!["mon".to_string(), "tue".to_string(), "wed".to_string(), "thu".to_string(), "fri".to_string()],
+            hours: Some("09:00-17:30".to_string()),
+            ..RouteRule::default()
+        },
+        RouteRule {
+            to: vec!["work".to_string()],
+            titles: vec!["jira".to_string()],
+            ..RouteRule::default()
+        },
+        RouteRule {
+            to: vec!["personal".to_string()],
+            ..RouteRule::default()
+        },
+    ]
+}
+
+#[test]
+fn work_apps_in_office_hours_go_to_work() {
+    let rules = work_rules();
+    assert_eq!(route(&rules, "code", "main.rs", at(2, 10, 0)), Some(&["work".to_string()][..]));
+    assert_eq!(route(&rules, "Code", "main.rs", at(2, 17, 30)), Some(&["personal".to_string()][..]));
+    // Saturday
+    assert_eq!(route(&rules, "Code", "main.rs", at(7, 10, 0)), Some(&["personal".to_string()][..]));
+}
+
+#[test]
+fn titles_match_case_insensitively() {
+    let rules = work_rules();
+    assert_eq!(
+        route(&rules, "Firefox", "PROJ-12 - JIRA", at(7, 22, 0)),
+        Some(&["work".to_string()][..])
+    );
+}
+
+#[test]
+fn hours_can_wrap_past_midnight() {
+    let rule = RouteRule {
+        hours: Some("22:00-06:00".to_string()),
+        ..RouteRule::default()
+    };
+    assert!(rule.matches("Code", "", at(2, 23, 0)));
+    assert!(rule.matches("Code", "", at(3, 5, 59)));
+    assert!(!rule.matches("Code", "", at(3, 6, 0)));
+}
+
+#[test]
+fn unmatched_sessions_go_everywhere() {
+    let rules = vec![RouteRule {
+        to: Vec::new(),
+        apps: vec!["Steam".to_string()],
+        ..RouteRule::default()
+    }];
+    assert_eq!(route(&rules, "Steam", "", at(2, 10, 0)), Some(&[][..]));
+    assert_eq!(route(&rules, "Code", "", at(2, 10, 0)), None);
+}
+
+#[test]
+fn config_rejects_unknown_destinations_and_bad_rules() {
+    let json = r#"{
+        "version": 2,
+        "sinks": [{ "type": "tick", "name": "work" }, { "type": "stdout", "name": "work" }],
+        "routes": [{ "to": ["personal"], "days": ["someday"], "hours": "9-5" }]
+    }"#;
+    let err = Config::from_json(json).unwrap_err().to_string();
+    assert!(err.contains("More than one sink is named `work`"));
+    assert!(err.contains("no sink has that name"));
+    assert!(err.contains("not a weekday"));
+    assert!(err.contains("hours must look like"));
+}
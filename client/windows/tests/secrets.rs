@@ -0,0 +1,239 @@
+mod common;
+
+use tick::config::Config;
+use tick::secrets::{EncryptedFileStore, MemoryStore, SecretBackend, SecretStore, Secrets};
+use tick::sink::SinkKind;
+
+fn memory_secrets() -> Secrets {
+    Secrets::with_stores(
+        Box::new(MemoryStore::new(SecretBackend::Keyring)),
+        Box::new(MemoryStore::new(SecretBackend::File)),
+    )
+}
+
+#[test]
+fn save_keeps_token_out_of_config_file() {
+    let dir = common::temp_dir("secrets", "save");
+    let path = dir.join("config.json");
+    let secrets = memory_secrets();
+
+    let mut config = Config {
+        api_key: Some("sk_live_123".to_string()),
+        ..Config::default()
+    };
+    config.save_with_secrets(&path, &secrets).unwrap();
+
+    let on_disk = std::fs::read_to_string(&path).unwrap();
+    assert!(!on_disk.contains("sk_live_123"));
+    assert_eq!(config.api_key_ref.as_ref().unwrap().backend, SecretBackend::Keyring);
+
+    let loaded = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert_eq!(loaded.api_key.as_deref(), Some("sk_live_123"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn migrates_plaintext_token_into_store() {
+    let dir = common::temp_dir("secrets", "migrate");
+    let path = dir.join("config.json");
+    std::fs::write(
+        &path,
+        r#"{ "server_url": "http://localhost:3000/api/log-session", "api_key": "sk_abc", "theme": "dark" }"#,
+    )
+    .unwrap();
+    let secrets = memory_secrets();
+
+    let config = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert_eq!(config.api_key.as_deref(), Some("sk_abc"));
+    assert!(config.api_key_ref.is_some());
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("sk_abc"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn moves_destination_tokens_into_store() {
+    let dir = common::temp_dir("secrets", "destination");
+    let path = dir.join("config.json");
+    std::fs::write(
+        &path,
+        r#"{
+            "version": 2,
+            "server_url": "http://localhost:3000/api/log-session",
+            "sinks": [{ "type": "tick", "name": "work", "server_url": "https://tick.example.com/api/log-session", "api_key": "sk_work" }]
+        }"#,
+    )
+    .unwrap();
+    let secrets = memory_secrets();
+
+    let config = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("sk_work"));
+    match &config.sinks[0].kind {
+        SinkKind::Tick { api_key, api_key_ref, .. } => {
+            assert_eq!(api_key.as_deref(), Some("sk_work"));
+            assert_eq!(api_key_ref.as_ref().unwrap().account, "sink:work:https://tick.example.com/api/log-session");
+        }
+        other => panic!("expected a tick sink, got {:?}", other),
+    }
+    assert_eq!(Config::load_with_secrets(&path, &secrets).unwrap(), config);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn falls_back_when_keychain_is_unavailable() {
+    let secrets = Secrets::with_stores(
+        Box::new(MemoryStore::unavailable(SecretBackend::Keyring)),
+        Box::new(MemoryStore::new(SecretBackend::File)),
+    );
+    let secret_ref = secrets.put("https://tick.example.com", "sk_abc").unwrap();
+    assert_eq!(secret_ref.backend, SecretBackend::File);
+    assert_eq!(secrets.get(&secret_ref).unwrap().as_deref(), Some("sk_abc"));
+}
+
+#[test]
+fn clearing_token_removes_stored_secret() {
+    let dir = common::temp_dir("secrets", "clear");
+    let path = dir.join("config.json");
+    let secrets = memory_secrets();
+
+    let mut config = Config {
+        api_key: Some("sk_abc".to_string()),
+        ..Config::default()
+    };
+    config.save_with_secrets(&path, &secrets).unwrap();
+    let old_ref = config.api_key_ref.clone().unwrap();
+
+    config.api_key = None;
+    config.save_with_secrets(&path, &secrets).unwrap();
+    assert!(config.api_key_ref.is_none());
+    assert_eq!(secrets.get(&old_ref).unwrap(), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tokens_for_the_same_server_are_kept_apart() {
+    let dir = common::temp_dir("secrets", "owners");
+    let path = dir.join("config.json");
+    let secrets = memory_secrets();
+
+    let mut config = Config::from_json(
+        r#"{
+            "version": 2,
+            "server_url": "https://tick.example.com",
+            "sinks": [
+                { "type": "tick" },
+                { "type": "tick", "name": "team", "server_url": "https://tick.example.com" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    config.api_key = Some("sk_mine".to_string());
+    let SinkKind::Tick { api_key, .. } = &mut config.sinks[1].kind else {
+        panic!("expected a tick sink");
+    };
+    *api_key = Some("sk_team".to_string());
+    config.save_with_secrets(&path, &secrets).unwrap();
+
+    let loaded = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert_eq!(loaded.api_key.as_deref(), Some("sk_mine"));
+    let SinkKind::Tick { api_key, .. } = &loaded.sinks[1].kind else {
+        panic!("expected a tick sink");
+    };
+    assert_eq!(api_key.as_deref(), Some("sk_team"));
+
+    // Moving the main server elsewhere leaves the sink's token alone
+    config.server_url = "https://other.example.com".to_string();
+    config.save_with_secrets(&path, &secrets).unwrap();
+    let loaded = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert_eq!(loaded.api_key.as_deref(), Some("sk_mine"));
+    let SinkKind::Tick { api_key, .. } = &loaded.sinks[1].kind else {
+        panic!("expected a tick sink");
+    };
+    assert_eq!(api_key.as_deref(), Some("sk_team"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_token_shared_by_older_versions_moves_to_each_owner() {
+    let dir = common::temp_dir("secrets", "shared");
+    let path = dir.join("config.json");
+    let secrets = memory_secrets();
+    // Versions that filed tokens by server URL gave both entries the same reference
+    let shared = secrets.put("https://tick.example.com", "sk_old").unwrap();
+    let shared_json = serde_json::to_string(&shared).unwrap();
+    std::fs::write(
+        &path,
+        format!(
+            r#"{{
+                "version": 2,
+                "server_url": "https://tick.example.com",
+                "api_key_ref": {shared_json},
+                "sinks": [{{ "type": "tick", "name": "team", "server_url": "https://tick.example.com", "api_key_ref": {shared_json} }}]
+            }}"#
+        ),
+    )
+    .unwrap();
+
+    let mut config = Config::load_with_secrets(&path, &secrets).unwrap();
+    config.api_key = None;
+    config.save_with_secrets(&path, &secrets).unwrap();
+    let loaded = Config::load_with_secrets(&path, &secrets).unwrap();
+    let SinkKind::Tick { api_key, .. } = &loaded.sinks[0].kind else {
+        panic!("expected a tick sink");
+    };
+    assert_eq!(api_key.as_deref(), Some("sk_old"));
+    assert_eq!(loaded.api_key, None);
+
+    // Both owners have moved to their own entries, so the shared one goes
+    assert_eq!(secrets.get(&shared).unwrap(), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn printed_config_saved_back_keeps_the_token() {
+    let dir = common::temp_dir("secrets", "template");
+    let path = dir.join("config.json");
+    let secrets = memory_secrets();
+
+    let mut config = Config {
+        api_key: Some("sk_abc".to_string()),
+        ..Config::default()
+    };
+    config.save_with_secrets(&path, &secrets).unwrap();
+
+    // `configure --print > copy.json`, then `configure --from-file copy.json`
+    let copy = dir.join("copy.json");
+    let printed = Config::load_with_secrets(&path, &secrets).unwrap().to_json();
+    assert!(!printed.contains("sk_abc"));
+    std::fs::write(&copy, &printed).unwrap();
+    let mut from_file = Config::load_template(&copy, &secrets).unwrap();
+    from_file.save_with_secrets(&path, &secrets).unwrap();
+
+    let reloaded = Config::load_with_secrets(&path, &secrets).unwrap();
+    assert_eq!(reloaded.api_key.as_deref(), Some("sk_abc"));
+    assert_eq!(std::fs::read_to_string(&copy).unwrap(), printed);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn encrypted_file_store_round_trips() {
+    let dir = common::temp_dir("secrets", "file");
+    let store = EncryptedFileStore::new(&dir);
+
+    assert_eq!(store.get("account").unwrap(), None);
+    store.set("account", "sk_secret_value").unwrap();
+    assert_eq!(store.get("account").unwrap().as_deref(), Some("sk_secret_value"));
+
+    let on_disk = std::fs::read_to_string(dir.join("secrets.json")).unwrap();
+    assert!(!on_disk.contains("sk_secret_value"));
+
+    store.delete("account").unwrap();
+    assert_eq!(store.get("account").unwrap(), None);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,582 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tick::config::Config;
+use tick::device::{DeviceIdentity, LegacyLink};
+use tick::discovery::CapabilityCache;
+use tick::policy::Policy;
+use tick::routing;
+use tick::session::SessionPayload;
+use tick::sink::{
+    render_template, ActivityWatchSink, Delivery, JsonlSink, RetryPolicy, SessionSink, SinkConfig, SinkKind, SinkQueue,
+    TickFeatures, TickSink, WebhookSink, DEFAULT_ACTIVITYWATCH_URL,
+};
+
+fn session(app_name: &str) -> SessionPayload {
+    SessionPayload {
+        device_id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        device_name: Some("Workstation".to_string()),
+        previous_device_id: None,
+        device_platform: "windows".to_string(),
+        app_name: app_name.to_string(),
+        title: None,
+        url: None,
+        editor: None,
+        tag: None,
+        ticket: None,
+        start_time: "2026-03-02T09:00:00+00:00".to_string(),
+        end_time: "2026-03-02T09:25:00+00:00".to_string(),
+        time_zone: "Europe/Berlin".to_string(),
+    }
+}
+
+fn fast_retry() -> RetryPolicy {
+    RetryPolicy {
+        initial: Duration::from_millis(20),
+        max: Duration::from_millis(40),
+        shutdown_grace: Duration::from_millis(200),
+    }
+}
+
+fn webhook(url: &str) -> Box<dyn SessionSink> {
+    Box::new(WebhookSink::new(url, BTreeMap::new(), None, "application/json".to_string()))
+}
+
+/// Waits for the stand-in to have seen `count` requests.
+async fn wait_for_requests(server: &common::StandIn, count: usize) {
+    for _ in 0..200 {
+        if server.requests.lock().unwrap().len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("expected {} requests", count);
+}
+
+#[test]
+fn config_defaults_to_the_tick_server() {
+    assert_eq!(Config::default().sinks, vec![SinkConfig::tick()]);
+
+    let legacy = r#"{ "version": 2, "server_url": "http://localhost:3000/api/log-session" }"#;
+    assert_eq!(Config::from_json(legacy).unwrap().sinks, vec![SinkConfig::tick()]);
+}
+
+#[test]
+fn config_accepts_several_sinks() {
+    let json = r#"{
+        "version": 2,
+        "server_url": "http://localhost:3000/api/log-session",
+        "sinks": [
+            { "type": "tick" },
+            { "type": "jsonl", "path": "sessions.jsonl" },
+            { "type": "webhook", "url": "https://hooks.example.com/tick", "body": "{\"text\": \"{{app_name}}\"}" }
+        ]
+    }"#;
+    let config = Config::from_json(json).unwrap();
+    assert_eq!(config.sinks.len(), 3);
+    assert_eq!(Config::from_json(&config.to_json()).unwrap(), config);
+}
+
+#[test]
+fn config_rejects_incomplete_sinks() {
+    let config = Config {
+        sinks: vec![
+            SinkKind::Jsonl { path: "".into() }.into(),
+            SinkKind::Webhook {
+                url: "not a url".to_string(),
+                headers: BTreeMap::new(),
+                body: None,
+                content_type: "application/json".to_string(),
+            }
+            .into(),
+        ],
+        ..Config::default()
+    };
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("Sink 1 (jsonl)"));
+    assert!(err.contains("Sink 2 (webhook)"));
+}
+
+#[test]
+fn template_escapes_values_for_json() {
+    let mut session = session("Notes \"draft\"");
+    session.device_name = None;
+    let body = render_template(
+        r#"{"text": "{{app_name}} for {{duration_secs}}s on {{device_name}}"}"#,
+        &session,
+        true,
+    );
+    assert_eq!(body, r#"{"text": "Notes \"draft\" for 1500s on "}"#);
+    let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(parsed["text"], "Notes \"draft\" for 1500s on ");
+}
+
+#[tokio::test]
+async fn jsonl_sink_appends_one_line_per_session() {
+    let dir = common::temp_dir("sink", "jsonl");
+    let path = dir.join("nested").join("sessions.jsonl");
+    let sink = JsonlSink::new(path.clone());
+
+    sink.send(&session("Code")).await.unwrap();
+    sink.send(&session("Browser")).await.unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<SessionPayload> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines, vec![session("Code"), session("Browser")]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn queue_retries_until_the_sink_recovers() {
+    let attempts = AtomicUsize::new(0);
+    let server = common::serve(move |_| {
+        if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+            (503, "busy".to_string())
+        } else {
+            (200, "ok".to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "retry");
+
+    let queue = SinkQueue::start("0-webhook".to_string(), webhook(&server.base_url), &dir, fast_retry());
+    queue.push(session("Code"));
+    wait_for_requests(&server, 3).await;
+    queue.shutdown().await;
+
+    let requests = server.requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].json()["appName"], "Code");
+    assert!(!dir.join("0-webhook.jsonl").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn queue_drops_rejected_sessions_and_moves_on() {
+    let server = common::serve(|request| {
+        if request.json()["appName"] == "Bad" {
+            (400, "bad request".to_string())
+        } else {
+            (200, "ok".to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "reject");
+
+    let queue = SinkQueue::start("0-webhook".to_string(), webhook(&server.base_url), &dir, fast_retry());
+    queue.push(session("Bad"));
+    queue.push(session("Good"));
+    wait_for_requests(&server, 2).await;
+    queue.shutdown().await;
+
+    assert_eq!(server.requests.lock().unwrap().len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn undelivered_sessions_are_kept_for_the_next_run() {
+    let down = common::serve(|_| (503, "down".to_string())).await;
+    let dir = common::temp_dir("sink", "outbox");
+
+    let queue = SinkQueue::start("0-webhook".to_string(), webhook(&down.base_url), &dir, fast_retry());
+    queue.push(session("Code"));
+    queue.push(session("Browser"));
+    queue.shutdown().await;
+    assert!(dir.join("0-webhook.jsonl").exists());
+
+    let up = common::serve(|_| (200, "ok".to_string())).await;
+    let queue = SinkQueue::start("0-webhook".to_string(), webhook(&up.base_url), &dir, fast_retry());
+    wait_for_requests(&up, 2).await;
+    queue.shutdown().await;
+
+    let apps: Vec<_> = up.requests.lock().unwrap().iter().map(|r| r.json()["appName"].clone()).collect();
+    assert_eq!(apps, vec!["Code", "Browser"]);
+    assert!(!dir.join("0-webhook.jsonl").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn queued_sessions_are_on_disk_until_delivered() {
+    let down = Arc::new(AtomicBool::new(true));
+    let server_down = down.clone();
+    let server = common::serve(move |_| {
+        if server_down.load(Ordering::SeqCst) {
+            (503, "down".to_string())
+        } else {
+            (200, "ok".to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "durable");
+    let outbox = dir.join("0-webhook.jsonl");
+
+    let queue = SinkQueue::start("0-webhook".to_string(), webhook(&server.base_url), &dir, fast_retry());
+    queue.push(session("Code"));
+    queue.push(session("Browser"));
+    wait_for_requests(&server, 2).await;
+    // Without a clean shutdown, as after a crash
+    let saved = std::fs::read_to_string(&outbox).unwrap();
+    assert_eq!(saved.lines().count(), 2);
+    assert!(saved.contains("\"appName\":\"Code\""));
+
+    down.store(false, Ordering::SeqCst);
+    for _ in 0..200 {
+        if !outbox.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!outbox.exists());
+    queue.shutdown().await;
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn activitywatch_sink_defaults_to_the_local_server() {
+    let config = Config::from_json(r#"{ "version": 2, "sinks": [{ "type": "activitywatch" }] }"#).unwrap();
+    assert_eq!(
+        config.sinks[0].kind,
+        SinkKind::ActivityWatch {
+            url: DEFAULT_ACTIVITYWATCH_URL.to_string(),
+            bucket: None,
+            pulsetime_secs: 60,
+        }
+    );
+    assert!(config.validate().is_ok());
+
+    let config = Config {
+        sinks: vec![SinkKind::ActivityWatch {
+            url: DEFAULT_ACTIVITYWATCH_URL.to_string(),
+            bucket: Some("tick/window".to_string()),
+            pulsetime_secs: 60,
+        }
+        .into()],
+        ..Config::default()
+    };
+    assert!(config.validate().unwrap_err().to_string().contains("bucket names"));
+}
+
+#[tokio::test]
+async fn activitywatch_sink_creates_its_bucket_once_and_sends_heartbeats() {
+    let server = common::serve(|request| {
+        if request.path.ends_with("/tick-test") {
+            (304, String::new())
+        } else {
+            (200, "{}".to_string())
+        }
+    })
+    .await;
+    let sink = ActivityWatchSink::new(&format!("{}/", server.base_url), "tick-test", "workstation", 30);
+
+    let mut first = session("Code");
+    first.title = Some("main.rs".to_string());
+    sink.send(&first).await.unwrap();
+    sink.send(&session("Browser")).await.unwrap();
+
+    let requests = server.requests.lock().unwrap();
+    let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/api/0/buckets/tick-test",
+            "/api/0/buckets/tick-test/heartbeat?pulsetime=30",
+            "/api/0/buckets/tick-test/heartbeat?pulsetime=30",
+        ]
+    );
+    assert_eq!(requests[0].json()["type"], "currentwindow");
+    assert_eq!(requests[0].json()["hostname"], "workstation");
+    let heartbeat = requests[1].json();
+    assert_eq!(heartbeat["timestamp"], "2026-03-02T09:00:00+00:00");
+    assert_eq!(heartbeat["duration"], 1500.0);
+    assert_eq!(heartbeat["data"]["app"], "Code");
+    assert_eq!(heartbeat["data"]["title"], "main.rs");
+    assert_eq!(requests[2].json()["data"]["title"], "");
+}
+
+#[tokio::test]
+async fn only_activitywatch_receives_window_titles() {
+    let server = common::serve(|_| (200, "{}".to_string())).await;
+    let dir = common::temp_dir("sink", "titles");
+    let config = Config {
+        sinks: vec![
+            SinkKind::Jsonl { path: "sessions.jsonl".into() }.into(),
+            SinkKind::ActivityWatch {
+                url: server.base_url.clone(),
+                bucket: Some("tick-test".to_string()),
+                pulsetime_secs: 60,
+            }
+            .into(),
+        ],
+        ..Config::default()
+    };
+    let identity = DeviceIdentity {
+        id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        legacy_id: None,
+    };
+    let mut delivery = Delivery::start_with(&config, &dir, &dir.join("outbox"), &LegacyLink::new(&dir, &identity), fast_retry());
+
+    let mut titled = session("Code");
+    titled.title = Some("secret.txt".to_string());
+    assert_eq!(delivery.submit(titled, None), vec!["jsonl", "activitywatch"]);
+    wait_for_requests(&server, 2).await;
+    delivery.shutdown().await;
+
+    let line = std::fs::read_to_string(dir.join("sessions.jsonl")).unwrap();
+    assert!(!line.contains("secret.txt"));
+    assert_eq!(server.requests.lock().unwrap()[1].json()["data"]["title"], "secret.txt");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn routes_cannot_keep_sessions_from_a_managed_server() {
+    let dir = common::temp_dir("sink", "managed");
+    let mut config = Config::from_json(
+        r#"{ "version": 2,
+            "sinks": [{ "type": "tick" }, { "type": "jsonl", "name": "log", "path": "sessions.jsonl" }],
+            "routes": [{ "apps": ["Steam"], "to": [] }, { "to": ["log"] }] }"#,
+    )
+    .unwrap();
+    let policy = Policy {
+        server_url: Some("http://127.0.0.1:9".to_string()),
+        ..Policy::default()
+    };
+    policy.apply(&mut config);
+    let identity = DeviceIdentity {
+        id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        legacy_id: None,
+    };
+    let mut delivery = Delivery::start_with(&config, &dir, &dir.join("outbox"), &LegacyLink::new(&dir, &identity), fast_retry());
+    let start = chrono::NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(9, 0, 0).unwrap();
+
+    let only = routing::route(&config.routes, "Code", "main.rs", start);
+    assert_eq!(delivery.submit(session("Code"), only), vec!["tick", "log"]);
+    let only = routing::route(&config.routes, "Steam", "", start);
+    assert_eq!(delivery.submit(session("Steam"), only), vec!["tick"]);
+    delivery.shutdown().await;
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn named_webhook(name: &str, url: &str) -> SinkConfig {
+    SinkConfig {
+        name: Some(name.to_string()),
+        kind: SinkKind::Webhook {
+            url: url.to_string(),
+            headers: BTreeMap::new(),
+            body: None,
+            content_type: "application/json".to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn restarting_rebuilds_only_the_sinks_that_changed() {
+    let down = common::serve(|_| (503, "down".to_string())).await;
+    let up = common::serve(|_| (200, "ok".to_string())).await;
+    let steady = common::serve(|_| (200, "ok".to_string())).await;
+    let dir = common::temp_dir("sink", "restart");
+    let identity = DeviceIdentity {
+        id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        legacy_id: None,
+    };
+    let link = LegacyLink::new(&dir, &identity);
+    let mut config = Config {
+        sinks: vec![named_webhook("moved", &down.base_url), named_webhook("steady", &steady.base_url)],
+        ..Config::default()
+    };
+    let mut delivery = Delivery::start_with(&config, &dir, &dir.join("outbox"), &link, fast_retry());
+    delivery.submit(session("Code"), None);
+    wait_for_requests(&steady, 1).await;
+
+    // The session stuck on the old address follows the sink to its new one
+    config.sinks[0] = named_webhook("moved", &up.base_url);
+    delivery.restart(&config, &dir, &link);
+    delivery.submit(session("Browser"), None);
+    wait_for_requests(&up, 2).await;
+    wait_for_requests(&steady, 2).await;
+
+    config.sinks.remove(0);
+    delivery.restart(&config, &dir, &link);
+    delivery.submit(session("Terminal"), None);
+    wait_for_requests(&steady, 3).await;
+    delivery.shutdown().await;
+
+    let apps: Vec<_> = up.requests.lock().unwrap().iter().map(|r| r.json()["appName"].clone()).collect();
+    assert_eq!(apps, vec!["Code", "Browser"]);
+    let apps: Vec<_> = steady.requests.lock().unwrap().iter().map(|r| r.json()["appName"].clone()).collect();
+    assert_eq!(apps, vec!["Code", "Browser", "Terminal"]);
+    assert!(!dir.join("outbox").join("moved.jsonl").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn tick_sink(server_url: &str, dir: &std::path::Path, titles: bool) -> TickSink {
+    let identity = DeviceIdentity {
+        id: "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11".to_string(),
+        legacy_id: None,
+    };
+    TickSink::new(
+        server_url,
+        None,
+        LegacyLink::new(dir, &identity),
+        CapabilityCache::new(dir),
+        TickFeatures {
+            compression: true,
+            batch: true,
+            titles,
+        },
+    )
+}
+
+#[tokio::test]
+async fn tick_sink_sends_plain_sessions_to_servers_that_advertise_nothing() {
+    let server = common::serve(|_| (200, r#"{"success":true}"#.to_string())).await;
+    let dir = common::temp_dir("sink", "tick-plain");
+    let sink = tick_sink(&format!("{}/api/log-session", server.base_url), &dir, false);
+    let sessions = vec![session("Code"), session("Browser")];
+
+    assert_eq!(sink.send_batch(&sessions).await.unwrap(), vec![Ok(())]);
+    assert_eq!(sink.send_batch(&sessions[1..]).await.unwrap(), vec![Ok(())]);
+
+    let requests = server.requests.lock().unwrap();
+    // Discovery answers like any other path, so the server predates it
+    assert_eq!(requests[0].path, "/api/capabilities");
+    for request in &requests[1..] {
+        assert_eq!(request.path, "/api/log-session");
+        assert_eq!(request.header("content-encoding"), None);
+    }
+    assert_eq!(requests[2].json()["appName"], "Browser");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn tick_sink_batches_and_compresses_once_the_server_advertises_it() {
+    const ADVERTISED: &[(&str, &str)] = &[("Accept-Encoding", "gzip, zstd"), ("Tick-Batch-Url", "/api/log-sessions")];
+    let server = common::serve_with_headers(ADVERTISED, |request| {
+        if request.path.ends_with("/log-sessions") {
+            // The third session was not stored and stays queued
+            let body = r#"{"success":true,"results":[{"success":true},{"success":false,"error":"Duration cannot exceed 24 hours"}]}"#;
+            (200, body.to_string())
+        } else {
+            (200, r#"{"success":true}"#.to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "tick-batch");
+    let sink = tick_sink(&format!("{}/api/log-session", server.base_url), &dir, false);
+    let mut long = session("Browser");
+    long.end_time = "2026-03-04T09:00:00+00:00".to_string();
+    let mut tagged = session("Mail");
+    tagged.tag = Some("Client A".to_string());
+    let sessions = vec![session("Code"), session("Code"), long, tagged];
+
+    // Nothing is known about the server before its first answer
+    assert_eq!(sink.send_batch(&sessions).await.unwrap(), vec![Ok(())]);
+    assert_eq!(
+        sink.send_batch(&sessions[1..]).await.unwrap(),
+        vec![Ok(()), Err("Duration cannot exceed 24 hours".to_string())]
+    );
+
+    let requests = server.requests.lock().unwrap();
+    assert_eq!(requests[1].header("content-encoding"), None);
+    let batch = &requests[2];
+    assert_eq!(batch.path, "/api/log-sessions");
+    assert_eq!(batch.header("content-encoding"), Some("zstd"));
+    let body: serde_json::Value = serde_json::from_slice(&zstd::decode_all(batch.body.as_slice()).unwrap()).unwrap();
+    assert_eq!(body["device"]["deviceId"], "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11");
+    assert_eq!(body["apps"], serde_json::json!(["Code", "Browser", "Mail"]));
+    assert_eq!(body["timeZones"], serde_json::json!(["Europe/Berlin"]));
+    assert_eq!(body["sessions"][0], serde_json::json!([0, 0, 1772442000000i64, 1772443500000i64]));
+    assert_eq!(body["sessions"][2][0], 2);
+    assert_eq!(body["tags"], serde_json::json!([null, null, "Client A"]));
+    assert!(body.get("urls").is_none());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn tick_sink_falls_back_to_plain_sessions_when_compact_uploads_are_refused() {
+    const ADVERTISED: &[(&str, &str)] = &[("Accept-Encoding", "gzip"), ("Tick-Batch-Url", "/api/log-sessions")];
+    let server = common::serve_with_headers(ADVERTISED, |request| {
+        if request.header("content-encoding").is_some() {
+            (415, r#"{"success":false}"#.to_string())
+        } else {
+            (200, r#"{"success":true}"#.to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "tick-fallback");
+    let sink = tick_sink(&format!("{}/api/log-session", server.base_url), &dir, false);
+    let sessions = vec![session("Code"), session("Browser"), session("Mail")];
+
+    assert!(sink.send_batch(&sessions).await.is_ok());
+    assert!(sink.send_batch(&sessions[1..]).await.is_err());
+    assert_eq!(sink.send_batch(&sessions[1..]).await.unwrap(), vec![Ok(())]);
+
+    let requests = server.requests.lock().unwrap();
+    assert_eq!(requests[2].header("content-encoding"), Some("gzip"));
+    assert_eq!(requests[3].path, "/api/log-session");
+    assert_eq!(requests[3].header("content-encoding"), None);
+
+    // Either can also be turned off in the config
+    let config = Config::from_json(r#"{ "version": 2, "sinks": [{ "type": "tick", "compression": false }] }"#).unwrap();
+    assert!(matches!(
+        config.sinks[0].kind,
+        SinkKind::Tick { compression: false, batch: true, .. }
+    ));
+    assert!(!config.to_json().contains("batch"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+const CAPABILITIES: &str = r#"{
+    "protocolVersion": 2,
+    "endpoints": { "logSession": "api/v2/log-session", "logSessions": "api/v2/log-sessions" },
+    "features": { "compression": ["gzip"], "batch": { "maxSessions": 2 }, "idempotency": true, "titles": true, "later": {} }
+}"#;
+
+#[tokio::test]
+async fn tick_sink_follows_what_the_server_discovers() {
+    let server = common::serve(|request| {
+        if request.path == "/tick/api/capabilities" {
+            (200, CAPABILITIES.to_string())
+        } else if request.path == "/tick/api/v2/log-sessions" {
+            (200, r#"{"success":true,"results":[{"success":true},{"success":true}]}"#.to_string())
+        } else if request.path == "/tick/api/v2/log-session" {
+            (200, r#"{"success":true}"#.to_string())
+        } else {
+            (404, "NOT_FOUND".to_string())
+        }
+    })
+    .await;
+    let dir = common::temp_dir("sink", "tick-discovery");
+    let mut titled = session("Code");
+    titled.title = Some("main.rs".to_string());
+    let sessions = vec![titled, session("Browser"), session("Mail")];
+
+    let sink = tick_sink(&format!("{}/tick", server.base_url), &dir, true);
+    assert_eq!(sink.send_batch(&sessions).await.unwrap(), vec![Ok(()), Ok(())]);
+    // A new sink, as after a restart, uses the cached result
+    let sink = tick_sink(&format!("{}/tick/", server.base_url), &dir, false);
+    assert_eq!(sink.send_batch(&sessions[2..]).await.unwrap(), vec![Ok(())]);
+
+    let requests = server.requests.lock().unwrap();
+    let paths: Vec<_> = requests.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, vec!["/tick/api/capabilities", "/tick/api/v2/log-sessions", "/tick/api/v2/log-session"]);
+    let batch = &requests[1];
+    assert_eq!(batch.header("content-encoding"), Some("gzip"));
+    let key = "0b7c6a5e-1f7e-4a4e-9a51-3f8f2a0c9d11/2026-03-02T09:00:00+00:00/2";
+    assert_eq!(batch.header("idempotency-key"), Some(key));
+    let mut body = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::GzDecoder::new(batch.body.as_slice()), &mut body).unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["sessions"].as_array().unwrap().len(), 2);
+    assert_eq!(body["titles"], serde_json::json!(["main.rs", null]));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
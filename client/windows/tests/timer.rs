@@ -0,0 +1,50 @@
+mod common;
+
+use chrono::{DateTime, Duration, Utc};
+use tick::timer::{TimerState, MAX_RECENT};
+
+#[test]
+fn starting_a_timer_switches_projects_and_keeps_them_recent() {
+    let now = DateTime::parse_from_rfc3339("2026-10-18T09:00:00Z").unwrap().with_timezone(&Utc);
+    let mut timer = TimerState::default();
+
+    assert!(timer.start(" Client A ", now));
+    assert!(!timer.start("Client A", now + Duration::hours(1)));
+    assert!(!timer.start("  ", now));
+    assert_eq!(timer.project(), Some("Client A"));
+    assert_eq!(timer.active.as_ref().unwrap().started_at, now);
+
+    assert!(timer.start("Internal", now + Duration::hours(2)));
+    assert_eq!(timer.project(), Some("Internal"));
+    assert_eq!(timer.recent, vec!["Internal", "Client A"]);
+
+    assert_eq!(timer.stop().unwrap().project, "Internal");
+    assert_eq!(timer.project(), None);
+    assert!(timer.stop().is_none());
+
+    // Restarting a project moves it to the front; the list stays short
+    for i in 0..MAX_RECENT + 2 {
+        timer.start(&format!("Project {}", i), now);
+    }
+    timer.start("Client A", now);
+    assert_eq!(timer.recent.len(), MAX_RECENT);
+    assert_eq!(timer.recent[0], "Client A");
+    assert_eq!(timer.recent.iter().filter(|p| *p == "Client A").count(), 1);
+}
+
+#[test]
+fn a_running_timer_survives_a_restart() {
+    let dir = common::temp_dir("timer", "restart");
+    assert_eq!(TimerState::load(&dir), TimerState::default());
+
+    let mut timer = TimerState::default();
+    timer.start("Client A", Utc::now());
+    timer.save(&dir).unwrap();
+    assert_eq!(TimerState::load(&dir), timer);
+    // Written in snake_case like the other files in the data folder
+    assert!(std::fs::read_to_string(dir.join("timer.json")).unwrap().contains("\"started_at\""));
+
+    std::fs::write(dir.join("timer.json"), "{ broken").unwrap();
+    assert_eq!(TimerState::load(&dir), TimerState::default());
+    let _ = std::fs::remove_dir_all(&dir);
+}
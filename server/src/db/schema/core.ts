@@ -1,5 +1,5 @@
 
-import { pgTable, text, timestamp, date, unique, bigint, uuid, pgEnum, boolean } from 'drizzle-orm/pg-core';
+import { pgTable, text, timestamp, date, unique, bigint, uuid, pgEnum, boolean, jsonb } from 'drizzle-orm/pg-core';
 import { user } from './auth';
 import { apps } from './apps';
 
@@ -9,6 +9,7 @@ export const devices = pgTable('devices', {
     id: uuid('id').defaultRandom().primaryKey(),
     externalDeviceId: text('external_device_id').notNull().unique(),
     platform: platformEnum('platform').notNull(),
+    name: text('name'),
     userId: text('user_id').references(() => user.id),
 });
 
@@ -34,8 +35,36 @@ export const usageTimelines = pgTable('usage_timelines', {
     appUsageId: uuid('app_usage_id').references(() => appUsages.id).notNull(),
     startTime: timestamp('start_time', { withTimezone: true }).notNull(),
     endTime: timestamp('end_time', { withTimezone: true }).notNull(),
+    // Window title when the client sends one
+    title: text('title'),
+    // Page shown in the browser, from the extension or the desktop tracker's native host
+    url: text('url'),
+    // Reported by editor plugins through the desktop tracker
+    project: text('project'),
+    file: text('file'),
+    language: text('language'),
+    // Project from the desktop tracker's timer or project rules, and a ticket its rules found
+    tag: text('tag'),
+    ticket: text('ticket'),
 });
 
+export type TrackerSettings = {
+    excludedApps?: string[];
+    idleThresholdSecs?: number | null;
+};
+
+// Settings the trackers pull from the server; a row without a device applies to all of the
+// user's devices
+export const trackerSettings = pgTable('tracker_settings', {
+    id: uuid('id').defaultRandom().primaryKey(),
+    userId: text('user_id').references(() => user.id).notNull(),
+    deviceId: uuid('device_id').references(() => devices.id),
+    settings: jsonb('settings').$type<TrackerSettings>().notNull().default({}),
+    updatedAt: timestamp('updated_at').defaultNow().notNull(),
+}, (t) => [
+    unique().on(t.userId, t.deviceId).nullsNotDistinct(),
+]);
+
 export const apiKeys = pgTable('api_keys', {
     id: uuid('id').defaultRandom().primaryKey(),
     key: text('key').notNull().unique(),
@@ -7,6 +7,10 @@ import { auth } from './modules/auth';
 import { sessionController } from './modules/session';
 import { analyticsController } from './modules/analytics';
 import { appsController } from './modules/apps';
+import { capabilitiesController } from './modules/capabilities';
+import { settingsController } from './modules/settings';
+import { categoriesController } from './modules/categories';
+import { requestDecompression } from './utils/request-encoding';
 
 
 // Global Middleware to handle Compression
@@ -63,6 +67,7 @@ const app = new Elysia()
         credentials: true
     }))
     .use(compressionMiddleware)
+    .use(requestDecompression)
     .get('/', () => 'Time Tracker API')
 
     // Auth Routes (Auth.js handles its own routing usually, or we wrap it)
@@ -71,7 +76,10 @@ const app = new Elysia()
     // Feature Modules through Controller
     .use(sessionController)
     .use(analyticsController)
-    .use(appsController);
+    .use(appsController)
+    .use(capabilitiesController)
+    .use(settingsController)
+    .use(categoriesController);
 
 export default app;
 
@@ -0,0 +1,31 @@
+
+import { Elysia } from 'elysia';
+import { MAX_BATCH_SESSIONS } from '../session/model';
+import { REQUEST_ENCODINGS } from '../../utils/request-encoding';
+
+// 1 is one session per POST to log-session, which every client speaks; 2 adds this document
+// and the optional features it lists
+const PROTOCOL_VERSION = 2;
+
+// Public, so clients can find out what to send before they authenticate. Paths are relative
+// to the server's base URL.
+export const capabilitiesController = new Elysia({ prefix: '/api' })
+    .get('/capabilities', () => ({
+        protocolVersion: PROTOCOL_VERSION,
+        minProtocolVersion: 1,
+        endpoints: {
+            logSession: 'api/log-session',
+            logSessions: 'api/log-sessions',
+            settings: 'api/settings',
+            categories: 'api/categories',
+            time: 'api/time'
+        },
+        features: {
+            compression: REQUEST_ENCODINGS,
+            batch: { maxSessions: MAX_BATCH_SESSIONS },
+            idempotency: false,
+            titles: true
+        }
+    }))
+    // Lets clients measure how far their clock is off, more precisely than the Date header
+    .get('/time', () => ({ now: Date.now() }));
@@ -0,0 +1,27 @@
+import { Elysia } from 'elysia';
+import { CategoriesService, categoriesETag } from './service';
+import { getUser } from '../../utils/auth-utils';
+
+// Lets trackers categorize sessions offline the way the dashboard does
+export const categoriesController = new Elysia({ prefix: '/api' })
+    .get('/categories', async ({ request, set }) => {
+        const currentUser = await getUser(request);
+        if (!currentUser) {
+            set.status = 401;
+            return { success: false, error: 'Unauthorized' };
+        }
+
+        try {
+            const categories = await CategoriesService.getCategories(currentUser.id);
+            const etag = categoriesETag(categories);
+            if (request.headers.get('if-none-match') === etag) {
+                return new Response(null, { status: 304, headers: { etag } });
+            }
+            set.headers['etag'] = etag;
+            return categories;
+        } catch (e: any) {
+            console.error('Get Categories Error:', e);
+            set.status = 500;
+            return { success: false, error: e.message || 'Internal Server Error' };
+        }
+    });
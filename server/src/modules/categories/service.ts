@@ -0,0 +1,34 @@
+import { createHash } from 'node:crypto';
+import { db } from '../../db';
+import { apps, appUsages, dailyActivities, devices, urlPatterns } from '../../db/schema';
+import { eq, and, ne, asc, desc } from 'drizzle-orm';
+
+export interface UserCategories {
+    apps: { name: string; category: 'productive' | 'distracting' | 'neutral' | 'uncategorized' }[];
+    urlPatterns: { pattern: string; category: 'productive' | 'distracting' | 'neutral' | 'uncategorized' }[];
+}
+
+export abstract class CategoriesService {
+    // The categorized apps the user has used on any device, and their URL patterns in the
+    // order the session service tries them
+    static async getCategories(userId: string): Promise<UserCategories> {
+        const usedApps = await db.selectDistinct({ name: apps.name, category: apps.category })
+            .from(apps)
+            .innerJoin(appUsages, eq(appUsages.appId, apps.id))
+            .innerJoin(dailyActivities, eq(appUsages.dailyActivityId, dailyActivities.id))
+            .innerJoin(devices, eq(dailyActivities.deviceId, devices.id))
+            .where(and(eq(devices.userId, userId), ne(apps.category, 'uncategorized')))
+            .orderBy(asc(apps.name));
+
+        const patterns = await db.select({ pattern: urlPatterns.pattern, category: urlPatterns.category })
+            .from(urlPatterns)
+            .where(eq(urlPatterns.userId, userId))
+            .orderBy(desc(urlPatterns.priority), asc(urlPatterns.createdAt));
+
+        return { apps: usedApps, urlPatterns: patterns };
+    }
+}
+
+// Changes whenever the document does, so trackers can revalidate with If-None-Match
+export const categoriesETag = (categories: UserCategories) =>
+    `"${createHash('sha256').update(JSON.stringify(categories)).digest('base64url').slice(0, 27)}"`;
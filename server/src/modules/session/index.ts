@@ -1,10 +1,16 @@
 
 import { Elysia } from 'elysia';
-import { SessionService } from './service';
+import { SessionService, isRejectedSession } from './service';
 import { SessionModel } from './model';
 import { getUser } from '../../utils/auth-utils';
+import { REQUEST_ENCODINGS } from '../../utils/request-encoding';
 
 export const sessionController = new Elysia({ prefix: '/api' })
+    // Lets clients without discovery switch to compressed and batched uploads (RFC 7694)
+    .onAfterHandle(({ set }) => {
+        set.headers['accept-encoding'] = REQUEST_ENCODINGS.join(', ');
+        set.headers['tick-batch-url'] = '/api/log-sessions';
+    })
     .post('/log-session', async ({ body, request, set }) => {
         const currentUser = await getUser(request);
         if (!currentUser) {
@@ -19,12 +25,25 @@ export const sessionController = new Elysia({ prefix: '/api' })
             });
         } catch (e: any) {
             console.error('Session Log Error:', e);
-            if (e.message.includes('startTime must be before endTime')) return { success: false, error: e.message };
-            if (e.message.includes('Duration cannot exceed')) return { success: false, error: e.message };
+            if (isRejectedSession(e)) return { success: false, error: e.message };
 
             set.status = 500;
             return { success: false, error: e.message || 'Internal Server Error' };
         }
     }, {
         body: SessionModel.logSessionBody
+    })
+    .post('/log-sessions', async ({ body, request, set }) => {
+        const currentUser = await getUser(request);
+        if (!currentUser) {
+            set.status = 401;
+            return { success: false, error: 'Unauthorized' };
+        }
+
+        return await SessionService.logSessions({
+            userId: currentUser.id,
+            ...body
+        });
+    }, {
+        body: SessionModel.logSessionsBody
     });
@@ -1,21 +1,66 @@
 
 import { t } from 'elysia';
 
+export const MAX_BATCH_SESSIONS = 100;
+
+const devicePlatform = t.Union([
+    t.Literal('web'),
+    t.Literal('windows'),
+    t.Literal('macos'),
+    t.Literal('linux'),
+    t.Literal('android'),
+    t.Literal('ios')
+]);
+
+// Project, file and language from an editor plugin
+const editorContext = t.Object({
+    project: t.Optional(t.String()),
+    file: t.Optional(t.String()),
+    language: t.Optional(t.String())
+});
+
 export const SessionModel = {
     logSessionBody: t.Object({
         deviceId: t.String(),
-        devicePlatform: t.Union([
-            t.Literal('web'),
-            t.Literal('windows'),
-            t.Literal('macos'),
-            t.Literal('linux'),
-            t.Literal('android'),
-            t.Literal('ios')
-        ]),
+        deviceName: t.Optional(t.String()),
+        // ID this device reported before; its history is moved to `deviceId`
+        previousDeviceId: t.Optional(t.String()),
+        devicePlatform,
         appName: t.String(),
+        // Window title; only sent by clients that learned from discovery that it is stored
+        title: t.Optional(t.String()),
         startTime: t.Union([t.String(), t.Date()]), // Allow string ISO or Date object
         endTime: t.Union([t.String(), t.Date()]),
         timeZone: t.String(),
-        url: t.Optional(t.String())
+        url: t.Optional(t.String()),
+        editor: t.Optional(editorContext),
+        // Project from the tracker's timer or project rules
+        tag: t.Optional(t.String()),
+        // Ticket the tracker's project rules found, such as a Jira key
+        ticket: t.Optional(t.String())
+    }),
+
+    // Sessions of one device with the shared fields sent once. Each session is
+    // [app index, time zone index, start, end] with times in epoch milliseconds.
+    logSessionsBody: t.Object({
+        device: t.Object({
+            deviceId: t.String(),
+            deviceName: t.Optional(t.String()),
+            previousDeviceId: t.Optional(t.String()),
+            devicePlatform
+        }),
+        apps: t.Array(t.String()),
+        timeZones: t.Array(t.String()),
+        sessions: t.Array(
+            t.Tuple([t.Integer({ minimum: 0 }), t.Integer({ minimum: 0 }), t.Number(), t.Number()]),
+            { maxItems: MAX_BATCH_SESSIONS }
+        ),
+        // Window title of each session, when the client sends them
+        titles: t.Optional(t.Array(t.Union([t.String(), t.Null()]))),
+        // Browser page of each session, when the client sends them
+        urls: t.Optional(t.Array(t.Union([t.String(), t.Null()]))),
+        editors: t.Optional(t.Array(t.Union([editorContext, t.Null()]))),
+        tags: t.Optional(t.Array(t.Union([t.String(), t.Null()]))),
+        tickets: t.Optional(t.Array(t.Union([t.String(), t.Null()])))
     })
 };
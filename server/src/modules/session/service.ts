@@ -10,17 +10,46 @@ import { AIService } from '../../services/ai';
 const BROWSER_KEYWORDS = ['vivaldi', 'chrome', 'msedge', 'edge', 'firefox', 'opera', 'brave', 'arc', 'safari'];
 const isBrowserApp = (name: string) => BROWSER_KEYWORDS.some(k => name.toLowerCase().includes(k));
 
+interface EditorContext {
+    project?: string;
+    file?: string;
+    language?: string;
+}
+
 interface LogSessionParams {
     userId: string;
     deviceId: string;
+    deviceName?: string;
+    previousDeviceId?: string;
     devicePlatform: 'web' | 'windows' | 'macos' | 'linux' | 'android' | 'ios';
     appName: string;
+    title?: string;
     startTime: string | Date; // Accept string input, convert internally
     endTime: string | Date;
     timeZone: string;
     url?: string;
+    editor?: EditorContext;
+    tag?: string;
+    ticket?: string;
+}
+
+interface LogSessionsParams {
+    userId: string;
+    device: Pick<LogSessionParams, 'deviceId' | 'deviceName' | 'previousDeviceId' | 'devicePlatform'>;
+    apps: string[];
+    timeZones: string[];
+    sessions: [number, number, number, number][];
+    titles?: (string | null)[];
+    urls?: (string | null)[];
+    editors?: (EditorContext | null)[];
+    tags?: (string | null)[];
+    tickets?: (string | null)[];
 }
 
+// Errors that mean the session itself is invalid, so sending it again won't help
+export const isRejectedSession = (e: any) =>
+    e.message.includes('startTime must be before endTime') || e.message.includes('Duration cannot exceed');
+
 export abstract class SessionService {
     static async logSession(params: LogSessionParams) {
         const { userId, deviceId, devicePlatform, appName, timeZone, url } = params;
@@ -66,7 +95,14 @@ export abstract class SessionService {
                 await tx.insert(usageTimelines).values({
                     appUsageId: usage.id,
                     startTime: seg.start,
-                    endTime: seg.end
+                    endTime: seg.end,
+                    title: params.title,
+                    url: params.url,
+                    project: params.editor?.project,
+                    file: params.editor?.file,
+                    language: params.editor?.language,
+                    tag: params.tag,
+                    ticket: params.ticket
                 });
             }
 
@@ -77,10 +113,58 @@ export abstract class SessionService {
                     .where(eq(appUsages.id, usage.id));
             }
 
-            return { success: true, filtered: false, durationAdded: actualDurationToAdd };
+            return {
+                success: true,
+                filtered: false,
+                durationAdded: actualDurationToAdd,
+                // Tells the client it can stop sending previousDeviceId
+                ...(params.previousDeviceId && { deviceLinked: true }),
+            };
         });
     }
 
+    // Stores sessions in order, each in its own transaction. Stops at the first unexpected
+    // error; sessions after it get no result so the client sends them again.
+    static async logSessions(params: LogSessionsParams) {
+        const { userId, device, apps, timeZones, sessions, titles, urls, editors, tags, tickets } = params;
+        const results: { success: boolean; error?: string }[] = [];
+        let deviceLinked = false;
+
+        for (const [index, [app, zone, startMs, endMs]] of sessions.entries()) {
+            const appName = apps[app];
+            const timeZone = timeZones[zone];
+            if (appName === undefined || timeZone === undefined) {
+                results.push({ success: false, error: 'Unknown app or time zone index' });
+                continue;
+            }
+            try {
+                const result = await this.logSession({
+                    userId,
+                    ...device,
+                    appName,
+                    title: titles?.[index] ?? undefined,
+                    url: urls?.[index] ?? undefined,
+                    editor: editors?.[index] ?? undefined,
+                    tag: tags?.[index] ?? undefined,
+                    ticket: tickets?.[index] ?? undefined,
+                    timeZone,
+                    startTime: new Date(startMs),
+                    endTime: new Date(endMs)
+                });
+                deviceLinked ||= 'deviceLinked' in result && Boolean(result.deviceLinked);
+                results.push({ success: true });
+            } catch (e: any) {
+                if (isRejectedSession(e)) {
+                    results.push({ success: false, error: e.message });
+                    continue;
+                }
+                console.error('Session Batch Error:', e);
+                return { success: results.length > 0, error: e.message || 'Internal Server Error', results, deviceLinked };
+            }
+        }
+        return { success: true, results, deviceLinked };
+    }
+
     private static async pruneRetroactively(tx: any, userId: string, start: Date, end: Date) {
         // Find overlapping web timelines
         const overlaps = await tx.select({
@@ -187,15 +271,24 @@ export abstract class SessionService {
 
 
     private static async upsertCoreData(tx: any, params: LogSessionParams, start: Date) {
-        const { userId, deviceId, devicePlatform, appName, url, timeZone } = params;
+        const { userId, deviceId, deviceName, previousDeviceId, devicePlatform, appName, url, timeZone } = params;
 
         // 1. Upsert Device & Link to User
         let [device] = await tx.select().from(devices).where(eq(devices.externalDeviceId, deviceId));
 
+        if (!device && previousDeviceId) {
+            // Re-key the old device record so its history stays with the new ID
+            [device] = await tx.update(devices)
+                .set({ externalDeviceId: deviceId })
+                .where(and(eq(devices.externalDeviceId, previousDeviceId), eq(devices.userId, userId)))
+                .returning();
+        }
+
         if (!device) {
             [device] = await tx.insert(devices).values({
                 externalDeviceId: deviceId,
                 platform: devicePlatform,
+                name: deviceName,
                 userId: userId
             }).returning();
         } else if (!device.userId) {
@@ -208,6 +301,13 @@ export abstract class SessionService {
         }
         if (!device) throw new Error('Failed to ensure device');
 
+        if (deviceName && device.name !== deviceName) {
+            [device] = await tx.update(devices)
+                .set({ name: deviceName })
+                .where(eq(devices.id, device.id))
+                .returning();
+        }
+
         // 2. Upsert Daily Activity
         const dateStr = start.toLocaleDateString('en-CA', { timeZone });
         let [daily] = await tx.select().from(dailyActivities).where(
@@ -0,0 +1,49 @@
+import { Elysia } from 'elysia';
+import { SettingsService, settingsETag } from './service';
+import { SettingsModel } from './model';
+import { getUser } from '../../utils/auth-utils';
+
+export const settingsController = new Elysia({ prefix: '/api' })
+    .get('/settings', async ({ query, request, set }) => {
+        const currentUser = await getUser(request);
+        if (!currentUser) {
+            set.status = 401;
+            return { success: false, error: 'Unauthorized' };
+        }
+
+        try {
+            const settings = await SettingsService.getSettings(currentUser.id, query.deviceId);
+            const etag = settingsETag(settings);
+            if (request.headers.get('if-none-match') === etag) {
+                return new Response(null, { status: 304, headers: { etag } });
+            }
+            set.headers['etag'] = etag;
+            return settings;
+        } catch (e: any) {
+            console.error('Get Settings Error:', e);
+            set.status = 500;
+            return { success: false, error: e.message || 'Internal Server Error' };
+        }
+    }, {
+        query: SettingsModel.query
+    })
+    .put('/settings', async ({ query, body, request, set }) => {
+        const currentUser = await getUser(request);
+        if (!currentUser) {
+            set.status = 401;
+            return { success: false, error: 'Unauthorized' };
+        }
+
+        try {
+            const result = await SettingsService.putSettings(currentUser.id, query.deviceId, body);
+            if (!result.success) set.status = 404;
+            return result;
+        } catch (e: any) {
+            console.error('Update Settings Error:', e);
+            set.status = 500;
+            return { success: false, error: e.message || 'Internal Server Error' };
+        }
+    }, {
+        query: SettingsModel.query,
+        body: SettingsModel.settingsBody
+    });
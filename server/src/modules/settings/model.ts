@@ -0,0 +1,14 @@
+import { t } from 'elysia';
+
+export const SettingsModel = {
+    // Without a device ID the user's settings are read or written; with one, that device's
+    query: t.Object({
+        deviceId: t.Optional(t.String())
+    }),
+
+    settingsBody: t.Object({
+        excludedApps: t.Optional(t.Array(t.String({ minLength: 1 }))),
+        // Trackers never pause sooner than after a minute
+        idleThresholdSecs: t.Optional(t.Nullable(t.Integer({ minimum: 60 })))
+    })
+};
@@ -0,0 +1,55 @@
+import { createHash } from 'node:crypto';
+import { db } from '../../db';
+import { devices, trackerSettings, type TrackerSettings } from '../../db/schema';
+import { eq, and, isNull } from 'drizzle-orm';
+
+export abstract class SettingsService {
+    // The user's settings with the device's laid over them; excluded apps add up
+    static async getSettings(userId: string, externalDeviceId?: string): Promise<TrackerSettings> {
+        const [userRow] = await db.select().from(trackerSettings)
+            .where(and(eq(trackerSettings.userId, userId), isNull(trackerSettings.deviceId)));
+        const device = externalDeviceId ? await this.findDevice(userId, externalDeviceId) : undefined;
+        const [deviceRow] = device
+            ? await db.select().from(trackerSettings)
+                .where(and(eq(trackerSettings.userId, userId), eq(trackerSettings.deviceId, device.id)))
+            : [];
+
+        const user = userRow?.settings ?? {};
+        const own = deviceRow?.settings ?? {};
+        const excludedApps = [...new Set([...(user.excludedApps ?? []), ...(own.excludedApps ?? [])])];
+        const idleThresholdSecs = own.idleThresholdSecs !== undefined ? own.idleThresholdSecs : user.idleThresholdSecs;
+
+        return {
+            ...(excludedApps.length > 0 && { excludedApps }),
+            ...(idleThresholdSecs != null && { idleThresholdSecs })
+        };
+    }
+
+    static async putSettings(userId: string, externalDeviceId: string | undefined, settings: TrackerSettings) {
+        let deviceId: string | null = null;
+        if (externalDeviceId) {
+            const device = await this.findDevice(userId, externalDeviceId);
+            if (!device) return { success: false, error: 'Unknown device' };
+            deviceId = device.id;
+        }
+
+        await db.insert(trackerSettings)
+            .values({ userId, deviceId, settings })
+            .onConflictDoUpdate({
+                target: [trackerSettings.userId, trackerSettings.deviceId],
+                set: { settings, updatedAt: new Date() }
+            });
+
+        return { success: true };
+    }
+
+    private static async findDevice(userId: string, externalDeviceId: string) {
+        const [device] = await db.select().from(devices)
+            .where(and(eq(devices.externalDeviceId, externalDeviceId), eq(devices.userId, userId)));
+        return device;
+    }
+}
+
+// Changes whenever the merged document does, so trackers can revalidate with If-None-Match
+export const settingsETag = (settings: TrackerSettings) =>
+    `"${createHash('sha256').update(JSON.stringify(settings)).digest('base64url').slice(0, 27)}"`;
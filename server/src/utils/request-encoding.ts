@@ -0,0 +1,54 @@
+import { Elysia } from 'elysia';
+import * as zlib from 'node:zlib';
+
+// Far above a full batch of sessions with titles, so only a compression bomb hits it
+export const MAX_DECODED_BYTES = 8 * 1024 * 1024;
+
+type DecodeOptions = { maxOutputLength: number };
+
+// zstd needs a recent runtime, so only what this one can decode is offered to clients
+const zstdDecompressSync = (zlib as any).zstdDecompressSync as
+    | ((data: Buffer, options: DecodeOptions) => Buffer)
+    | undefined;
+const decoders: Record<string, (data: Buffer) => Buffer> = {
+    ...(zstdDecompressSync && {
+        zstd: (data: Buffer) => zstdDecompressSync(data, { maxOutputLength: MAX_DECODED_BYTES }),
+    }),
+    gzip: (data) => zlib.gunzipSync(data, { maxOutputLength: MAX_DECODED_BYTES }),
+};
+
+// Content-Encodings clients may compress uploads with
+export const REQUEST_ENCODINGS = Object.keys(decoders);
+
+class UnsupportedEncodingError extends Error {
+    status = 415;
+
+    constructor(encoding: string) {
+        super(`Unsupported Content-Encoding: ${encoding}`);
+    }
+}
+
+class BodyTooLargeError extends Error {
+    status = 413;
+
+    constructor() {
+        super(`Decompressed body exceeds ${MAX_DECODED_BYTES} bytes`);
+    }
+}
+
+// Decodes compressed JSON bodies; uncompressed ones are left to Elysia's own parsers
+export const requestDecompression = (app: Elysia) => app.onParse({ as: 'global' }, async ({ request }) => {
+    const encoding = request.headers.get('content-encoding')?.trim().toLowerCase();
+    if (!encoding || encoding === 'identity') return;
+
+    const decode = decoders[encoding];
+    if (!decode) throw new UnsupportedEncodingError(encoding);
+    let body: Buffer;
+    try {
+        body = decode(Buffer.from(await request.arrayBuffer()));
+    } catch (error) {
+        if ((error as NodeJS.ErrnoException).code === 'ERR_BUFFER_TOO_LARGE') throw new BodyTooLargeError();
+        throw error;
+    }
+    return JSON.parse(body.toString('utf8'));
+});